@@ -0,0 +1,671 @@
+//! Local automation API
+//!
+//! An optional loopback HTTP server, in the spirit of `FileServer`, that lets
+//! scripts and browser extensions queue downloads. Every request must carry
+//! the token from settings, either as `Authorization: Bearer <token>` or as a
+//! `token` query parameter (browsers' `EventSource` cannot set headers).
+//!
+//! - `GET  /api/info?url=<url>` video information
+//! - `POST /api/jobs` queue a `DownloadRequest` (507 when its disk is too full)
+//! - `GET  /api/jobs` list queued, running and finished jobs
+//! - `GET  /api/jobs/<id>` a single job
+//! - `GET  /api/events[?job=<id>]` job updates as Server-Sent Events
+
+use crate::error::{AppError, Result};
+use crate::jobs::{Job, JobQueue};
+use crate::types::DownloadRequest;
+use crate::ytdlp;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
+
+/// Largest request (headers and body) the server accepts
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// Interval between SSE comments that keep idle connections open
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Connection details handed to clients
+#[derive(Debug, Clone, Serialize)]
+pub struct AutomationInfo {
+    pub url: String,
+    pub port: u16,
+    pub token: String,
+}
+
+pub struct AutomationServer {
+    shutdown_tx: watch::Sender<bool>,
+    port: u16,
+    token: String,
+}
+
+impl AutomationServer {
+    /// Listen on `127.0.0.1:<port>`; port 0 picks a free one
+    pub async fn start(port: u16, token: String, jobs: Arc<JobQueue>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+        let port = listener.local_addr()?.port();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let api = Arc::new(Api {
+            token: token.clone(),
+            port,
+            jobs,
+        });
+        tokio::spawn(Self::run(listener, api, shutdown_rx));
+
+        Ok(Self {
+            shutdown_tx,
+            port,
+            token,
+        })
+    }
+
+    pub fn info(&self) -> AutomationInfo {
+        AutomationInfo {
+            url: format!("http://127.0.0.1:{}/api", self.port),
+            port: self.port,
+            token: self.token.clone(),
+        }
+    }
+
+    pub fn stop(&self) {
+        let _ = self.shutdown_tx.send(true);
+    }
+
+    async fn run(listener: TcpListener, api: Arc<Api>, mut shutdown_rx: watch::Receiver<bool>) {
+        loop {
+            tokio::select! {
+                result = listener.accept() => {
+                    if let Ok((stream, _)) = result {
+                        tokio::spawn(api.clone().handle_connection(stream, shutdown_rx.clone()));
+                    }
+                }
+                _ = shutdown_rx.changed() => {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Generate a random token for the automation API
+pub fn generate_token() -> Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| AppError::AutomationError(format!("Failed to generate token: {}", e)))?;
+
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// A parsed HTTP request
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A JSON response
+struct Response {
+    status: u16,
+    body: serde_json::Value,
+}
+
+impl Response {
+    fn ok(body: impl Serialize) -> Self {
+        Self::with_status(200, body)
+    }
+
+    fn with_status(status: u16, body: impl Serialize) -> Self {
+        Self {
+            status,
+            body: json!(body),
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.into() }),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let body = self.body.to_string();
+        format!(
+            "HTTP/1.1 {} {}\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\
+             Cache-Control: no-store\r\n\
+             Connection: close\r\n\r\n\
+             {}",
+            self.status,
+            reason_phrase(self.status),
+            body.len(),
+            body
+        )
+        .into_bytes()
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        507 => "Insufficient Storage",
+        _ => "Internal Server Error",
+    }
+}
+
+struct Api {
+    token: String,
+    port: u16,
+    jobs: Arc<JobQueue>,
+}
+
+impl Api {
+    async fn handle_connection(
+        self: Arc<Self>,
+        mut stream: TcpStream,
+        shutdown_rx: watch::Receiver<bool>,
+    ) {
+        let request = match read_request(&mut stream).await {
+            Ok(request) => request,
+            Err(response) => {
+                let _ = stream.write_all(&response.to_bytes()).await;
+                return;
+            }
+        };
+
+        if let Err(response) = self.authorize(&request) {
+            let _ = stream.write_all(&response.to_bytes()).await;
+            return;
+        }
+
+        if request.method == "GET" && request.path == "/api/events" {
+            let filter = request.query("job").and_then(|id| id.parse().ok());
+            self.stream_events(stream, filter, shutdown_rx).await;
+            return;
+        }
+
+        let response = self.route(&request).await;
+        let _ = stream.write_all(&response.to_bytes()).await;
+    }
+
+    /// Check the Host header and the token
+    fn authorize(&self, request: &Request) -> std::result::Result<(), Response> {
+        // A page on another origin can resolve its own domain to 127.0.0.1
+        // (DNS rebinding); such requests still carry that domain as Host
+        let host = request.header("Host").unwrap_or("");
+        if !is_loopback_host(host, self.port) {
+            return Err(Response::error(403, "Invalid Host header"));
+        }
+
+        let provided = request
+            .header("Authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .or_else(|| request.query("token"));
+
+        match provided {
+            Some(token) if constant_time_eq(token.trim().as_bytes(), self.token.as_bytes()) => {
+                Ok(())
+            }
+            _ => Err(Response::error(401, "Missing or invalid token")),
+        }
+    }
+
+    async fn route(&self, request: &Request) -> Response {
+        let method = request.method.as_str();
+        match request.path.as_str() {
+            "/api/info" if method == "GET" => self.info(request).await,
+            "/api/jobs" if method == "GET" => Response::ok(self.jobs.list()),
+            "/api/jobs" if method == "POST" => self.enqueue(request).await,
+            path if path.starts_with("/api/jobs/") && method == "GET" => {
+                let job = path["/api/jobs/".len()..]
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(|id| self.jobs.get(id));
+                match job {
+                    Some(job) => Response::ok(job),
+                    None => Response::error(404, "Job not found"),
+                }
+            }
+            "/api/info" | "/api/jobs" | "/api/events" => Response::error(405, "Method not allowed"),
+            _ => Response::error(404, "Not found"),
+        }
+    }
+
+    async fn info(&self, request: &Request) -> Response {
+        let Some(url) = request.query("url") else {
+            return Response::error(400, "Missing `url` parameter");
+        };
+
+        match ytdlp::fetch_video_info(url, &self.jobs.settings()).await {
+            Ok(info) => Response::ok(info),
+            Err(e @ AppError::InvalidUrl) => Response::error(400, e.to_string()),
+            Err(e) => Response::error(500, e.to_string()),
+        }
+    }
+
+    async fn enqueue(&self, request: &Request) -> Response {
+        let download: DownloadRequest = match serde_json::from_slice(&request.body) {
+            Ok(download) => download,
+            Err(e) => return Response::error(400, format!("Invalid download request: {}", e)),
+        };
+
+        match self.jobs.enqueue(download).await {
+            Ok(job) => Response::with_status(202, job),
+            Err(AppError::InvalidRange(range)) => Response {
+                status: 400,
+                body: json!({ "error": AppError::InvalidRange(range.clone()).to_string(), "range": range }),
+            },
+            Err(e @ AppError::InsufficientSpace { needed, available }) => Response {
+                status: 507,
+                body: json!({
+                    "error": e.to_string(),
+                    "space": { "needed": needed, "available": available },
+                }),
+            },
+            Err(e @ (AppError::InvalidUrl | AppError::InvalidTimestamp(_))) => {
+                Response::error(400, e.to_string())
+            }
+            Err(e) => Response::error(500, e.to_string()),
+        }
+    }
+
+    /// Send the current jobs, then every update, until the client or server goes away
+    async fn stream_events(
+        &self,
+        mut stream: TcpStream,
+        filter: Option<u64>,
+        mut shutdown_rx: watch::Receiver<bool>,
+    ) {
+        let header = "HTTP/1.1 200 OK\r\n\
+                      Content-Type: text/event-stream\r\n\
+                      Cache-Control: no-store\r\n\
+                      Connection: keep-alive\r\n\r\n";
+        if stream.write_all(header.as_bytes()).await.is_err() {
+            return;
+        }
+
+        // Subscribe before taking the snapshot so no update falls in between
+        let mut events = self.jobs.subscribe();
+        let wanted = |job: &Job| filter.is_none_or(|id| id == job.id);
+
+        for job in self.jobs.list().iter().filter(|job| wanted(job)) {
+            if stream.write_all(sse_event(job).as_bytes()).await.is_err() {
+                return;
+            }
+        }
+
+        let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+        keep_alive.tick().await;
+
+        loop {
+            let message = tokio::select! {
+                event = events.recv() => match event {
+                    Ok(job) if wanted(&job) => sse_event(&job),
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = keep_alive.tick() => ": keep-alive\n\n".to_string(),
+                _ = shutdown_rx.changed() => break,
+            };
+
+            if stream.write_all(message.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Format a job snapshot as a Server-Sent Event
+fn sse_event(job: &Job) -> String {
+    format!("event: job\ndata: {}\n\n", json!(job))
+}
+
+/// Read and parse one request, answering with an error response if it is malformed
+async fn read_request(stream: &mut TcpStream) -> std::result::Result<Request, Response> {
+    let mut buf = Vec::with_capacity(4096);
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() > MAX_REQUEST_BYTES {
+            return Err(Response::error(413, "Request too large"));
+        }
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return Err(Response::error(400, "Incomplete request")),
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut request = parse_head(&head).ok_or_else(|| Response::error(400, "Malformed request"))?;
+
+    let content_length = match request.header("Content-Length") {
+        Some(value) => value
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| Response::error(400, "Invalid Content-Length"))?,
+        None => 0,
+    };
+    if header_end + content_length > MAX_REQUEST_BYTES {
+        return Err(Response::error(413, "Request too large"));
+    }
+
+    let mut body = buf.split_off(header_end);
+    while body.len() < content_length {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return Err(Response::error(400, "Incomplete request body")),
+            Ok(n) => body.extend_from_slice(&chunk[..n]),
+        }
+    }
+    body.truncate(content_length);
+    request.body = body;
+
+    Ok(request)
+}
+
+/// Parse the request line and headers
+fn parse_head(head: &str) -> Option<Request> {
+    let mut lines = head.split("\r\n");
+    let mut parts = lines.next()?.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    if !parts.next()?.starts_with("HTTP/1.") {
+        return None;
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let headers = lines
+        .take_while(|line| !line.is_empty())
+        .map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(Request {
+        method,
+        path: path.to_string(),
+        query: parse_query(query),
+        headers,
+        body: Vec::new(),
+    })
+}
+
+/// Decode `a=1&b=two%20words` into key/value pairs
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    let decode = |s: &str| {
+        percent_encoding::percent_decode_str(&s.replace('+', " "))
+            .decode_utf8_lossy()
+            .to_string()
+    };
+
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+/// Whether a Host header names this server on a loopback address
+fn is_loopback_host(host: &str, port: u16) -> bool {
+    let Some((name, host_port)) = host.rsplit_once(':') else {
+        return false;
+    };
+
+    host_port.parse() == Ok(port) && matches!(name, "127.0.0.1" | "localhost" | "[::1]")
+}
+
+/// Compare secrets without exiting early on the first mismatch
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn test_parse_head() {
+        let request = parse_head(
+            "GET /api/info?url=https%3A%2F%2Fyoutu.be%2Fabc&token=t+1 HTTP/1.1\r\n\
+             Host: 127.0.0.1:47815\r\n\
+             Authorization: Bearer secret\r\n\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/api/info");
+        assert_eq!(request.query("url"), Some("https://youtu.be/abc"));
+        assert_eq!(request.query("token"), Some("t 1"));
+        assert_eq!(request.header("authorization"), Some("Bearer secret"));
+
+        assert!(parse_head("GET /\r\n\r\n").is_none());
+        assert!(parse_head("GET / HTTP/1.1\r\nno colon here\r\n\r\n").is_none());
+    }
+
+    #[test]
+    fn test_is_loopback_host() {
+        assert!(is_loopback_host("127.0.0.1:47815", 47815));
+        assert!(is_loopback_host("localhost:47815", 47815));
+        assert!(!is_loopback_host("127.0.0.1:8080", 47815));
+        assert!(!is_loopback_host("evil.example:47815", 47815));
+        assert!(!is_loopback_host("127.0.0.1", 47815));
+    }
+
+    #[test]
+    fn test_generate_token() {
+        let a = generate_token().unwrap();
+        let b = generate_token().unwrap();
+        assert_eq!(a.len(), 64);
+        assert_ne!(a, b);
+        assert!(constant_time_eq(a.as_bytes(), a.clone().as_bytes()));
+        assert!(!constant_time_eq(a.as_bytes(), b.as_bytes()));
+    }
+
+    /// Stand-in for yt-dlp: prints metadata for `--dump-json`, otherwise
+    /// reports progress and writes a file to the `-o` path
+    #[cfg(unix)]
+    fn fake_ytdlp(dir: &std::path::Path) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = r#"#!/bin/sh
+case "$*" in
+  *--version*) echo 2024.01.01; exit 0 ;;
+  *--dump-json*) echo '{"id":"abc","title":"Fake video","duration":10,"formats":[]}'; exit 0 ;;
+esac
+out=""
+while [ $# -gt 0 ]; do
+  if [ "$1" = "-o" ]; then out="$2"; fi
+  shift
+done
+echo "[download]  50.0% of 1.00MiB at 1.00MiB/s ETA 00:01"
+echo "[download] 100.0% of 1.00MiB at 1.00MiB/s ETA 00:00"
+printf data > "$out"
+"#;
+        let path = dir.join("yt-dlp");
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    /// Stand-in for ffprobe: describes every file as the 10 s 720p video
+    /// the fake yt-dlp downloads
+    #[cfg(unix)]
+    fn fake_ffprobe(dir: &std::path::Path) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = r#"#!/bin/sh
+echo '{"format":{"format_name":"mov,mp4","duration":"10.0"},"streams":[{"index":0,"codec_type":"video","codec_name":"h264","width":1280,"height":720,"duration":"10.0"},{"index":1,"codec_type":"audio","codec_name":"aac","duration":"10.0"}]}'
+"#;
+        let path = dir.join("ffprobe");
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_api_with_fake_ytdlp() {
+        let dir = test_support::temp_dir("automation");
+
+        let mut settings = crate::settings::Settings::default();
+        settings.binary_paths.ytdlp = Some(fake_ytdlp(&dir).to_string_lossy().to_string());
+        settings.binary_paths.ffprobe = Some(fake_ffprobe(&dir).to_string_lossy().to_string());
+        // Keep JS runtimes on the host out of the yt-dlp arguments
+        settings.resolution_policy = crate::settings::ResolutionPolicy::BundledOnly;
+
+        let jobs = Arc::new(JobQueue::with_settings(settings));
+        let server = AutomationServer::start(0, "secret".to_string(), jobs)
+            .await
+            .unwrap();
+        let base = server.info().url;
+        let client = reqwest::Client::new();
+
+        // Requests without the token are rejected
+        let response = client.get(format!("{}/jobs", base)).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+
+        let response = client
+            .get(format!("{}/info", base))
+            .query(&[("url", "https://www.youtube.com/watch?v=dQw4w9WgXcQ")])
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let info: serde_json::Value =
+            serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        assert_eq!(info["title"], "Fake video");
+
+        // Follow progress over SSE while the job runs
+        let mut events = client
+            .get(format!("{}/events?token=secret", base))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(events.status(), 200);
+
+        let output = dir.join("video.mp4");
+
+        // Ranges are checked against the 10 s the fake video lasts
+        let body = json!({
+            "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "quality": "720",
+            "mode": "video_with_audio",
+            "output_path": output.to_string_lossy(),
+            "start_time": 5.0,
+            "end_time": 60.0,
+        });
+        let response = client
+            .post(format!("{}/jobs", base))
+            .bearer_auth("secret")
+            .body(body.to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let error: serde_json::Value =
+            serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        assert_eq!(error["range"]["reason"], "past_end");
+        assert_eq!(error["range"]["bound"], "end");
+
+        let body = json!({
+            "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "quality": "720",
+            "mode": "video_with_audio",
+            "output_path": output.to_string_lossy(),
+            "start_time": null,
+            "end_time": null,
+        });
+        let response = client
+            .post(format!("{}/jobs", base))
+            .bearer_auth("secret")
+            .body(body.to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 202);
+        let job: serde_json::Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        assert_eq!(job["status"], "queued");
+        let id = job["id"].as_u64().unwrap();
+
+        let mut stream = String::new();
+        let finished = tokio::time::timeout(Duration::from_secs(10), async {
+            while !stream.contains(r#""status":"completed""#) {
+                match events.chunk().await.unwrap() {
+                    Some(chunk) => stream.push_str(&String::from_utf8_lossy(&chunk)),
+                    None => break,
+                }
+            }
+        })
+        .await;
+        assert!(finished.is_ok(), "no completion event, got: {}", stream);
+        assert!(stream.contains(r#""status":"running""#));
+        assert!(stream.contains(r#""percent":50.0"#));
+
+        let response = client
+            .get(format!("{}/jobs/{}", base, id))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        let job: serde_json::Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        assert_eq!(job["status"], "completed");
+        assert_eq!(job["output_path"], output.to_string_lossy().as_ref());
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "data");
+        // Checked by the pinned ffprobe, not one from the saved settings
+        assert_eq!(job["verification"]["verified"], true);
+        assert_eq!(job["verification"]["video_codec"], "h264");
+
+        // Invalid URLs never reach the queue
+        let response = client
+            .post(format!("{}/jobs", base))
+            .bearer_auth("secret")
+            .body(body.to_string().replace("www.youtube.com", "example.com"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        server.stop();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
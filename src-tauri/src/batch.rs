@@ -0,0 +1,577 @@
+//! Batch import
+//!
+//! Turns pasted links or spreadsheet rows (`url,start,end,mode,quality,filename`)
+//! into queued downloads. Rows are checked one by one, so a bad link in a
+//! list of fifty is reported instead of blocking the rest.
+//!
+//! Input can be comma, semicolon or tab separated (the latter is what
+//! spreadsheets put on the clipboard). A first row starting with `url` is
+//! read as a header and may list the columns in any order.
+
+use crate::error::{AppError, Result};
+use crate::filename::{self, TemplateFields};
+use crate::jobs::JobQueue;
+use crate::routing;
+use crate::timestamp;
+use crate::types::{normalize_quality, DownloadMode, DownloadRequest};
+use crate::ytdlp;
+use futures_util::stream::{self, StreamExt};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Most rows accepted in one import
+pub const MAX_BATCH_ROWS: usize = 500;
+
+/// Largest input accepted, in bytes
+const MAX_BATCH_BYTES: usize = 1024 * 1024;
+
+/// Titles fetched at once for rows without a file name
+const CONCURRENT_LOOKUPS: usize = 4;
+
+/// Columns in the order used when there is no header row
+const COLUMNS: [&str; 6] = ["url", "start", "end", "mode", "quality", "filename"];
+
+/// A row that parsed and validated, ready to become a download
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchItem {
+    pub url: String,
+    pub mode: DownloadMode,
+    pub quality: String,
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+    /// File name to save as, already sanitized and with the mode's extension
+    pub file_name: Option<String>,
+}
+
+/// One data row of the input
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchRow {
+    /// 1-based line number in the input
+    pub line: usize,
+    /// The URL as written, for the report
+    pub url: String,
+    pub item: std::result::Result<BatchItem, String>,
+}
+
+/// Outcome of one row
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchRowReport {
+    /// 1-based line number in the input
+    pub line: usize,
+    pub url: String,
+    /// Queued job, for valid rows
+    pub job_id: Option<u64>,
+    pub output_path: Option<String>,
+    /// Why the row was skipped, for invalid rows
+    pub error: Option<String>,
+}
+
+/// Outcome of a whole import
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BatchReport {
+    pub queued: usize,
+    pub invalid: usize,
+    pub rows: Vec<BatchRowReport>,
+}
+
+impl BatchReport {
+    fn push(
+        &mut self,
+        line: usize,
+        url: String,
+        outcome: std::result::Result<(u64, String), String>,
+    ) {
+        let row = match outcome {
+            Ok((job_id, output_path)) => {
+                self.queued += 1;
+                BatchRowReport {
+                    line,
+                    url,
+                    job_id: Some(job_id),
+                    output_path: Some(output_path),
+                    error: None,
+                }
+            }
+            Err(error) => {
+                self.invalid += 1;
+                BatchRowReport {
+                    line,
+                    url,
+                    job_id: None,
+                    output_path: None,
+                    error: Some(error),
+                }
+            }
+        };
+        self.rows.push(row);
+    }
+}
+
+/// Parse and validate every row without queueing anything
+pub fn parse_batch(text: &str) -> Result<Vec<BatchRow>> {
+    if text.len() > MAX_BATCH_BYTES {
+        return Err(AppError::BatchError("The list is too large".to_string()));
+    }
+
+    // Blank lines and `#` comments are skipped but still count for line numbers
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+
+    let Some(&(_, first)) = lines.peek() else {
+        return Ok(Vec::new());
+    };
+    let delimiter = detect_delimiter(first);
+
+    let mut columns: Vec<String> = COLUMNS.iter().map(|c| c.to_string()).collect();
+    let header = split_row(first, delimiter).unwrap_or_default();
+    if header
+        .first()
+        .is_some_and(|name| name.trim().eq_ignore_ascii_case("url"))
+    {
+        columns = header
+            .iter()
+            .map(|name| name.trim().to_lowercase())
+            .collect();
+        if let Some(unknown) = columns.iter().find(|c| !COLUMNS.contains(&c.as_str())) {
+            return Err(AppError::BatchError(format!("Unknown column: {}", unknown)));
+        }
+        lines.next();
+    }
+
+    let mut rows = Vec::new();
+    for (line, text) in lines {
+        if rows.len() == MAX_BATCH_ROWS {
+            return Err(AppError::BatchError(format!(
+                "Too many rows; import at most {} at a time",
+                MAX_BATCH_ROWS
+            )));
+        }
+
+        let row = match split_row(text, delimiter) {
+            Ok(fields) => {
+                let get = |name: &str| {
+                    columns
+                        .iter()
+                        .position(|c| c == name)
+                        .and_then(|i| fields.get(i))
+                        .map(|value| value.trim())
+                        .filter(|value| !value.is_empty())
+                };
+                let item = if fields.len() > columns.len() {
+                    Err(format!(
+                        "Expected at most {} columns, found {}",
+                        columns.len(),
+                        fields.len()
+                    ))
+                } else {
+                    parse_item(&get)
+                };
+                BatchRow {
+                    line,
+                    url: get("url").unwrap_or_default().to_string(),
+                    item,
+                }
+            }
+            Err(error) => BatchRow {
+                line,
+                url: String::new(),
+                item: Err(error),
+            },
+        };
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+/// Validate rows, work out where each one is saved and queue the valid ones
+///
+/// Rows without a file name are named by the filename template and, with
+/// output rules set, every row is sorted into its folder; both cost a yt-dlp
+/// lookup per row. Existing files are handled by the collision policy.
+pub async fn import(text: &str, output_dir: &Path, jobs: &Arc<JobQueue>) -> Result<BatchReport> {
+    let rows = parse_batch(text)?;
+    let settings = jobs.settings();
+
+    let resolved: Vec<_> = stream::iter(rows)
+        .map(|row| async {
+            let path = match &row.item {
+                Ok(item) => output_path(item, output_dir, &settings).await.map(Some),
+                Err(_) => Ok(None),
+            };
+            (row, path)
+        })
+        .buffered(CONCURRENT_LOOKUPS)
+        .collect()
+        .await;
+
+    let mut report = BatchReport::default();
+    let mut taken = HashSet::new();
+
+    for (row, path) in resolved {
+        let outcome = match (row.item, path) {
+            (Err(error), _) | (Ok(_), Err(error)) => Err(error),
+            (Ok(item), Ok(path)) => {
+                // Names from the list are kept as written; two rows asking
+                // for the same file is a mistake in the list
+                let named = item.file_name.is_some();
+                let path = filename::resolve_collision(
+                    &path.unwrap_or_default(),
+                    settings.collision_policy,
+                    |p| p.exists() || (!named && taken.contains(p)),
+                );
+                match path {
+                    Err(error) => Err(error.to_string()),
+                    Ok(path) if !taken.insert(path.clone()) => Err(format!(
+                        "Another row already saves to {}",
+                        path.file_name().unwrap_or_default().to_string_lossy()
+                    )),
+                    Ok(path) => {
+                        let output_path = path.to_string_lossy().to_string();
+                        let request = DownloadRequest {
+                            url: item.url,
+                            quality: item.quality,
+                            mode: item.mode,
+                            output_path: output_path.clone(),
+                            start_time: item.start_time,
+                            end_time: item.end_time,
+                            if_exists: None,
+                            target_size: None,
+                        };
+                        jobs.enqueue(request)
+                            .await
+                            .map(|job| (job.id, output_path))
+                            .map_err(|e| e.to_string())
+                    }
+                }
+            }
+        };
+        report.push(row.line, row.url, outcome);
+    }
+
+    Ok(report)
+}
+
+/// Where a row's download is saved
+///
+/// The video's metadata is needed to name the file by the template and to
+/// match the output rules, so it is only fetched when either applies.
+async fn output_path(
+    item: &BatchItem,
+    output_dir: &Path,
+    settings: &crate::settings::Settings,
+) -> std::result::Result<PathBuf, String> {
+    let named = item.file_name.as_ref();
+    if let Some(file_name) = named.filter(|_| settings.output_rules.is_empty()) {
+        return Ok(output_dir.join(file_name));
+    }
+
+    let info = ytdlp::fetch_video_info(&item.url, settings)
+        .await
+        .map_err(|e| e.to_string())?;
+    let fields = TemplateFields::for_download(
+        &info,
+        &item.mode,
+        &item.quality,
+        item.start_time,
+        item.end_time,
+    );
+
+    let dir = routing::output_dir(output_dir, &fields, &item.mode, settings)
+        .map_err(|e| e.to_string())?;
+    let file_name = match named {
+        Some(file_name) => file_name.clone(),
+        None => filename::render(&settings.filename_template, &fields, item.mode.extension())
+            .map_err(|e| e.to_string())?,
+    };
+
+    Ok(dir.join(file_name))
+}
+
+/// Validate the fields of one row
+fn parse_item<'a>(
+    get: &impl Fn(&str) -> Option<&'a str>,
+) -> std::result::Result<BatchItem, String> {
+    let url = get("url").ok_or("Missing URL")?;
+    if url.chars().any(char::is_whitespace) {
+        return Err("The URL contains spaces".to_string());
+    }
+    let parsed = ytdlp::parse_url(url).map_err(|e| e.to_string())?;
+
+    let mode = match get("mode").map(str::to_lowercase).as_deref() {
+        None if parsed.platform.supports_video() => DownloadMode::VideoWithAudio,
+        None => DownloadMode::AudioOnly,
+        Some("video" | "video_with_audio" | "mp4") => DownloadMode::VideoWithAudio,
+        Some("audio" | "audio_only" | "mp3") => DownloadMode::AudioOnly,
+        Some(other) => return Err(format!("Unknown mode: {} (use video or audio)", other)),
+    };
+    if mode == DownloadMode::VideoWithAudio && !parsed.platform.supports_video() {
+        return Err("This platform only supports audio downloads".to_string());
+    }
+
+    let quality = match get("quality").map(str::to_lowercase) {
+        None => mode.default_quality().to_string(),
+        Some(quality) if quality == "best" => mode.default_quality().to_string(),
+        Some(quality) => normalize_quality(&quality, Some(&mode)).ok_or_else(|| match mode {
+            DownloadMode::VideoWithAudio => format!("Invalid video quality: {}", quality),
+            DownloadMode::AudioOnly => format!(
+                "Invalid audio quality: {} (use high, medium or low)",
+                quality
+            ),
+        })?,
+    };
+
+    // Times in the link itself (`?t=90`) apply when the row has none
+    let (start, end) = (get("start"), get("end"));
+    let (start_time, end_time) = if start.is_none() && end.is_none() {
+        (parsed.start_time, parsed.end_time)
+    } else {
+        timestamp::resolve_range(start, end, None, None).map_err(|e| e.to_string())?
+    };
+
+    let file_name = get("filename")
+        .map(|name| file_name(name, &mode))
+        .transpose()?;
+
+    Ok(BatchItem {
+        url: url.to_string(),
+        mode,
+        quality,
+        start_time,
+        end_time,
+        file_name,
+    })
+}
+
+/// Turn a file name from the list into a safe one with the mode's extension
+fn file_name(name: &str, mode: &DownloadMode) -> std::result::Result<String, String> {
+    let extension = mode.extension();
+    let stem = match name.rsplit_once('.') {
+        Some((stem, ext)) if ext.eq_ignore_ascii_case(extension) => stem,
+        _ => name,
+    };
+
+    if stem.trim().trim_matches('.').is_empty() {
+        return Err(format!("Invalid file name: {}", name));
+    }
+
+    Ok(filename::file_name(stem, extension))
+}
+
+fn detect_delimiter(line: &str) -> char {
+    if line.contains('\t') {
+        '\t'
+    } else if line.contains(';') && !line.contains(',') {
+        ';'
+    } else {
+        ','
+    }
+}
+
+/// Split a row on the delimiter, honouring double-quoted fields with `""`
+/// escapes
+fn split_row(line: &str, delimiter: char) -> std::result::Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err("Unclosed quote".to_string());
+    }
+    fields.push(field);
+
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{ResolutionPolicy, Settings};
+
+    fn valid(rows: &[BatchRow]) -> Vec<&BatchItem> {
+        rows.iter()
+            .filter_map(|row| row.item.as_ref().ok())
+            .collect()
+    }
+
+    #[test]
+    fn test_plain_list() {
+        let text = "\
+# links from the team sheet
+https://www.youtube.com/watch?v=dQw4w9WgXcQ
+
+https://youtu.be/dQw4w9WgXcQ?t=1m30s
+https://soundcloud.com/artist/track-name
+";
+        let rows = parse_batch(text).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows.iter().map(|row| row.line).collect::<Vec<_>>(),
+            [2, 4, 5]
+        );
+
+        let items = valid(&rows);
+        assert_eq!(items[0].mode, DownloadMode::VideoWithAudio);
+        assert_eq!(items[0].quality, "0");
+        assert_eq!(items[1].start_time, Some(90.0));
+        assert_eq!(items[2].mode, DownloadMode::AudioOnly);
+        assert_eq!(items[2].quality, "high");
+    }
+
+    #[test]
+    fn test_csv_columns() {
+        let text = "\
+https://youtu.be/dQw4w9WgXcQ,1:30,+45,audio,medium,\"Intro, part 1\"
+https://youtu.be/dQw4w9WgXcQ,,2:00,video,1080p,clip.mp4
+";
+        let items = parse_batch(text).unwrap();
+        let items = valid(&items);
+        assert_eq!(items.len(), 2);
+
+        assert_eq!(items[0].mode, DownloadMode::AudioOnly);
+        assert_eq!(items[0].quality, "medium");
+        assert_eq!(items[0].start_time, Some(90.0));
+        assert_eq!(items[0].end_time, Some(135.0));
+        assert_eq!(items[0].file_name.as_deref(), Some("Intro, part 1.mp3"));
+
+        assert_eq!(items[1].quality, "1080");
+        assert_eq!(items[1].start_time, None);
+        assert_eq!(items[1].end_time, Some(120.0));
+        assert_eq!(items[1].file_name.as_deref(), Some("clip.mp4"));
+    }
+
+    #[test]
+    fn test_header_and_delimiters() {
+        // Spreadsheet paste: tab separated, header in a different order
+        let text = "URL\tFilename\tStart\nhttps://youtu.be/dQw4w9WgXcQ\tintro\t0:10\n";
+        let items = parse_batch(text).unwrap();
+        let items = valid(&items);
+        assert_eq!(items[0].file_name.as_deref(), Some("intro.mp4"));
+        assert_eq!(items[0].start_time, Some(10.0));
+
+        let text = "https://youtu.be/dQw4w9WgXcQ;0:10;0:20\n";
+        let items = parse_batch(text).unwrap();
+        assert_eq!(valid(&items)[0].end_time, Some(20.0));
+
+        let error = parse_batch("url,output\nhttps://youtu.be/dQw4w9WgXcQ,x\n").unwrap_err();
+        assert!(error.to_string().contains("Unknown column: output"));
+    }
+
+    #[test]
+    fn test_invalid_rows() {
+        let text = "\
+https://example.com/video
+https://youtu.be/dQw4w9WgXcQ,2:00,1:00
+https://youtu.be/dQw4w9WgXcQ,99:99
+https://youtu.be/dQw4w9WgXcQ,,,sideways
+https://soundcloud.com/artist/track-name,,,video
+https://youtu.be/dQw4w9WgXcQ,,,audio,720
+https://youtu.be/dQw4w9WgXcQ,,,,,..
+https://youtu.be/dQw4w9WgXcQ,,,,,,extra
+https://youtu.be/dQw4w9WgXcQ,,,,,\"unclosed
+https://youtu.be/dQw4w9WgXcQ watch this
+,1:00
+";
+        let rows = parse_batch(text).unwrap();
+        assert_eq!(rows.len(), 11);
+        let errors: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                row.item
+                    .clone()
+                    .expect_err(&format!("line {} passed", row.line))
+            })
+            .collect();
+
+        assert!(errors[0].contains("Unsupported or invalid URL"));
+        assert!(errors[1].contains("must be before end time"));
+        assert!(errors[2].contains("seconds must be below 60"));
+        assert!(errors[3].contains("Unknown mode: sideways"));
+        assert!(errors[4].contains("only supports audio"));
+        assert!(errors[5].contains("Invalid audio quality: 720"));
+        assert!(errors[6].contains("Invalid file name"));
+        assert!(errors[7].contains("at most 6 columns"));
+        assert!(errors[8].contains("Unclosed quote"));
+        assert!(errors[9].contains("contains spaces"));
+        assert!(errors[10].contains("Missing URL"));
+        assert_eq!(rows[1].url, "https://youtu.be/dQw4w9WgXcQ");
+    }
+
+    #[test]
+    fn test_limits() {
+        assert!(parse_batch("").unwrap().is_empty());
+
+        let text = "https://youtu.be/dQw4w9WgXcQ\n".repeat(MAX_BATCH_ROWS + 1);
+        assert!(parse_batch(&text).is_err());
+    }
+
+    #[test]
+    fn test_split_row() {
+        assert_eq!(split_row("a,b,,c", ',').unwrap(), ["a", "b", "", "c"]);
+        assert_eq!(
+            split_row("\"a, \"\"b\"\"\",c", ',').unwrap(),
+            ["a, \"b\"", "c"]
+        );
+        assert_eq!(split_row("it\"s,x", ',').unwrap(), ["it\"s", "x"]);
+    }
+
+    #[tokio::test]
+    async fn test_import_report() {
+        // A yt-dlp that does not exist, so queued jobs fail without downloading
+        let mut settings = Settings::default();
+        settings.binary_paths.ytdlp = Some("/nonexistent/yt-dlp".to_string());
+        settings.resolution_policy = ResolutionPolicy::BundledOnly;
+        let jobs = Arc::new(JobQueue::with_settings(settings));
+
+        let text = "\
+url,filename
+https://youtu.be/dQw4w9WgXcQ,first
+https://example.com/video,second
+https://www.youtube.com/watch?v=dQw4w9WgXcQ,first.mp4
+https://youtu.be/abc,third
+";
+        let dir = std::env::temp_dir();
+        let report = import(text, &dir, &jobs).await.unwrap();
+
+        assert_eq!(report.queued, 2);
+        assert_eq!(report.invalid, 2);
+        assert_eq!(report.rows.len(), 4);
+        assert_eq!(report.rows[0].line, 2);
+        assert!(report.rows[0].job_id.is_some());
+        assert_eq!(
+            report.rows[0].output_path.as_deref(),
+            Some(dir.join("first.mp4").to_string_lossy().as_ref())
+        );
+        assert!(report.rows[1].error.is_some());
+        assert!(report.rows[2]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("already saves to first.mp4"));
+        assert!(report.rows[3].job_id.is_some());
+        assert_eq!(jobs.list().len(), 2);
+    }
+}
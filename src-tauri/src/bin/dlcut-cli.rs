@@ -0,0 +1,983 @@
+//! DLCut command-line interface
+//!
+//! Headless access to the same download, cut and dependency logic the
+//! desktop app uses, for scripting DLCut on servers and in CI. Progress is
+//! drawn on a TTY; when stdout is piped (or with `--json`) every event is
+//! printed as one JSON object per line.
+//!
+//! The same binary is the browser native messaging host: when a browser
+//! starts it, it speaks the native messaging protocol on stdin/stdout instead.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use dlcut_lib::commands;
+use dlcut_lib::deps::{self, DependencyInfo};
+use dlcut_lib::error::{AppError, Result};
+use dlcut_lib::ffmpeg;
+use dlcut_lib::filename::TemplateFields;
+use dlcut_lib::history::{self, HistoryEntry, HistoryKind};
+use dlcut_lib::native_messaging;
+use dlcut_lib::probe::{self, MediaInfo};
+use dlcut_lib::routing;
+use dlcut_lib::settings::{self, CollisionPolicy};
+use dlcut_lib::types::{
+    format_duration, AspectPreset, CropRect, CutOptions, DownloadMode, DownloadRequest,
+    EncodeQuality, FillMode, ProgressUpdate, QualityPreset, Reframe, VideoInfo,
+};
+use dlcut_lib::validation;
+use dlcut_lib::verification::VerifiedOutput;
+use dlcut_lib::ytdlp;
+use serde_json::json;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tokio::sync::mpsc;
+
+#[derive(Parser)]
+#[command(
+    name = "dlcut-cli",
+    version,
+    about = "Download and cut videos without the DLCut window"
+)]
+struct Cli {
+    /// Print JSON lines even when attached to a terminal
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show metadata and available qualities for a URL
+    Info { url: String },
+    /// Show the container, streams, chapters and tags of a local file
+    Probe { file: PathBuf },
+    /// Download a video, or only its audio
+    Download {
+        url: String,
+        /// Video height (e.g. 720) or audio quality (high, medium, low); best by default
+        #[arg(short, long)]
+        quality: Option<String>,
+        #[arg(short, long, value_enum, default_value_t = Mode::Video)]
+        mode: Mode,
+        /// Start of the section to keep (e.g. 1:30)
+        #[arg(long)]
+        start: Option<String>,
+        /// End of the section to keep (e.g. 2:45)
+        #[arg(long)]
+        end: Option<String>,
+        /// Output file or directory; defaults to the downloads folder. Files in
+        /// a directory are sorted by the output rules and named by the
+        /// filename template in the settings
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Largest size the file may be, e.g. 25M; a larger download is
+        /// re-encoded to fit
+        #[arg(long, value_parser = parse_size)]
+        target_size: Option<u64>,
+        /// What to do if the output file exists; the setting by default
+        #[arg(long, value_enum)]
+        if_exists: Option<IfExists>,
+    },
+    /// Cut a local file, without re-encoding where possible
+    Cut {
+        file: PathBuf,
+        /// Start time; defaults to the beginning
+        #[arg(long)]
+        start: Option<String>,
+        /// End time; defaults to the end of the file
+        #[arg(long)]
+        end: Option<String>,
+        /// Output file; defaults to `<name>_cut.<ext>` next to the input
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Input streams to keep by index, as listed by `probe`; all by default
+        #[arg(long, value_delimiter = ',')]
+        streams: Option<Vec<u32>>,
+        /// Quality if the cut has to be re-encoded
+        #[arg(long, value_enum, default_value_t = Quality::Balanced)]
+        quality: Quality,
+        /// Constant rate factor for a re-encode instead of the preset's
+        #[arg(long, conflicts_with = "video_bitrate")]
+        crf: Option<u32>,
+        /// Video bitrate in kbps for a re-encode instead of a constant quality
+        #[arg(long)]
+        video_bitrate: Option<u32>,
+        /// Audio bitrate in kbps for a re-encode instead of the preset's
+        #[arg(long)]
+        audio_bitrate: Option<u32>,
+        /// Largest size the cut may be, e.g. 25M; the cut is then re-encoded
+        /// in two passes to fit
+        #[arg(long, value_parser = parse_size, conflicts_with_all = ["crf", "video_bitrate"])]
+        target_size: Option<u64>,
+        /// Reframe the cut to an aspect ratio, e.g. 9:16 for vertical video
+        #[arg(long, value_enum)]
+        aspect: Option<Aspect>,
+        /// Output width in pixels when reframing
+        #[arg(long)]
+        width: Option<u32>,
+        /// Output height in pixels when reframing
+        #[arg(long)]
+        height: Option<u32>,
+        /// Crop the source to WxH+X+Y before reframing, e.g. 1080x1080+420+0
+        #[arg(long, value_parser = parse_crop)]
+        crop: Option<CropRect>,
+        /// How the picture fills a reframed output of another shape
+        #[arg(long, value_enum, default_value_t = Fill::Crop)]
+        fill: Fill,
+        /// Color of the bars with `--fill pad`, a name or hex like #1a1a1a
+        #[arg(long)]
+        pad_color: Option<String>,
+        /// What to do if the output file exists; the setting by default
+        #[arg(long, value_enum)]
+        if_exists: Option<IfExists>,
+    },
+    /// Check, install or update yt-dlp, ffmpeg and ffprobe
+    Deps {
+        #[command(subcommand)]
+        action: DepsAction,
+    },
+    /// List completed downloads and cuts, newest first
+    History {
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Manage the browser native messaging host
+    NativeHost {
+        #[command(subcommand)]
+        action: NativeHostAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum NativeHostAction {
+    /// Register this binary as the host for the given extensions (Linux)
+    Install {
+        /// Chromium extension ID allowed to connect (repeatable)
+        #[arg(long = "chromium-extension")]
+        chromium: Vec<String>,
+        /// Firefox extension ID allowed to connect (repeatable)
+        #[arg(long = "firefox-extension")]
+        firefox: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DepsAction {
+    /// Report installed versions and where each binary was found
+    Check,
+    /// Install missing dependencies into the DLCut data directory
+    Install,
+    /// Update yt-dlp from the configured release channel
+    Update,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Mode {
+    /// Video with audio, merged into mp4
+    Video,
+    /// Audio only, converted to mp3
+    Audio,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Quality {
+    /// Quick encodes at a lower quality
+    Fast,
+    Balanced,
+    /// Slow encodes that are close to the source
+    High,
+}
+
+impl From<Quality> for QualityPreset {
+    fn from(value: Quality) -> Self {
+        match value {
+            Quality::Fast => QualityPreset::Fast,
+            Quality::Balanced => QualityPreset::Balanced,
+            Quality::High => QualityPreset::High,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Aspect {
+    /// Vertical, for shorts and stories
+    #[value(name = "9:16")]
+    Vertical,
+    #[value(name = "1:1")]
+    Square,
+    #[value(name = "4:5")]
+    Portrait,
+}
+
+impl From<Aspect> for AspectPreset {
+    fn from(value: Aspect) -> Self {
+        match value {
+            Aspect::Vertical => AspectPreset::Vertical,
+            Aspect::Square => AspectPreset::Square,
+            Aspect::Portrait => AspectPreset::Portrait,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Fill {
+    /// Scale to cover the frame and crop what overflows
+    Crop,
+    /// Scale to fit the frame and add bars
+    Pad,
+    /// Scale to fit the frame over a blurred copy of the picture
+    Blur,
+}
+
+impl From<Fill> for FillMode {
+    fn from(value: Fill) -> Self {
+        match value {
+            Fill::Crop => FillMode::Crop,
+            Fill::Pad => FillMode::Pad,
+            Fill::Blur => FillMode::Blur,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum IfExists {
+    /// Replace the existing file
+    Overwrite,
+    /// Keep the existing file and fail
+    Skip,
+    /// Save as "name (2).ext" and so on
+    Number,
+}
+
+impl From<IfExists> for CollisionPolicy {
+    fn from(value: IfExists) -> Self {
+        match value {
+            IfExists::Overwrite => CollisionPolicy::Overwrite,
+            IfExists::Skip => CollisionPolicy::Skip,
+            IfExists::Number => CollisionPolicy::Number,
+        }
+    }
+}
+
+/// How results and progress are written
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    /// Readable text, with progress redrawn in place on stderr
+    Human,
+    /// One JSON object per line on stdout
+    Json,
+}
+
+impl Output {
+    fn detect(force_json: bool) -> Self {
+        if force_json || !std::io::stdout().is_terminal() {
+            Output::Json
+        } else {
+            Output::Human
+        }
+    }
+
+    /// Print a JSON event tagged with `event`
+    fn event(&self, event: &str, mut value: serde_json::Value) {
+        if let Some(object) = value.as_object_mut() {
+            object.insert("event".to_string(), json!(event));
+        }
+        println!("{}", value);
+    }
+
+    fn progress(&self, update: &ProgressUpdate) {
+        match self {
+            Output::Json => self.event("progress", json!(update)),
+            Output::Human => {
+                let mut line = format!("{:5.1}%  {}", update.percent, update.message);
+                if let Some(speed) = &update.speed {
+                    line.push_str(&format!("  {}", speed));
+                }
+                if let Some(eta) = &update.eta {
+                    line.push_str(&format!("  ETA {}", eta));
+                }
+                self.status_line(&line);
+            }
+        }
+    }
+
+    /// Progress reported as a message and a percentage, as dependency installs do
+    fn setup_progress(&self, message: &str, progress: f64) {
+        match self {
+            Output::Json => self.event(
+                "progress",
+                json!({ "message": message, "percent": progress }),
+            ),
+            Output::Human => self.status_line(&format!("{:5.1}%  {}", progress, message)),
+        }
+    }
+
+    /// Redraw the current progress line in place
+    fn status_line(&self, line: &str) {
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r{:<78}", line);
+        let _ = stderr.flush();
+    }
+
+    /// Move past the progress line before printing anything else
+    fn end_progress(&self) {
+        if *self == Output::Human {
+            eprintln!();
+        }
+    }
+
+    fn error(&self, error: &AppError) {
+        match self {
+            Output::Json => self.event("error", json!({ "message": error.to_string() })),
+            Output::Human => eprintln!("error: {}", error),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    if native_messaging::is_native_messaging_launch(&args) {
+        // stdout carries protocol messages, so errors can only go to stderr
+        return match native_messaging::run_host().await {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let cli = Cli::parse_from(args);
+    let out = Output::detect(cli.json);
+
+    let result = match cli.command {
+        Command::Info { url } => info(out, &url).await,
+        Command::Probe { file } => probe_file(out, &file).await,
+        Command::Download {
+            url,
+            quality,
+            mode,
+            start,
+            end,
+            output,
+            target_size,
+            if_exists,
+        } => {
+            download(
+                out,
+                url,
+                quality,
+                mode,
+                start,
+                end,
+                output,
+                target_size,
+                if_exists,
+            )
+            .await
+        }
+        Command::Cut {
+            file,
+            start,
+            end,
+            output,
+            streams,
+            quality,
+            crf,
+            video_bitrate,
+            audio_bitrate,
+            target_size,
+            aspect,
+            width,
+            height,
+            crop,
+            fill,
+            pad_color,
+            if_exists,
+        } => {
+            // Reframe only when asked for a shape or size
+            let reframe =
+                (aspect.is_some() || width.is_some() || height.is_some() || crop.is_some()).then(
+                    || Reframe {
+                        aspect: aspect.map(Into::into),
+                        width,
+                        height,
+                        crop,
+                        fill: fill.into(),
+                        pad_color,
+                    },
+                );
+            let options = CutOptions {
+                streams,
+                quality: EncodeQuality {
+                    preset: quality.into(),
+                    crf,
+                    video_bitrate,
+                    audio_bitrate,
+                },
+                target_size,
+                reframe,
+            };
+            cut(out, &file, start, end, output, options, if_exists).await
+        }
+        Command::Deps { action } => deps_command(out, action).await,
+        Command::History { limit } => {
+            show_history(out, limit);
+            Ok(())
+        }
+        Command::NativeHost { action } => native_host_command(out, action),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            out.error(&e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn info(out: Output, url: &str) -> Result<()> {
+    let info = ytdlp::fetch_video_info(url, &settings::load()).await?;
+
+    match out {
+        Output::Json => out.event("info", json!(info)),
+        Output::Human => print_info(&info),
+    }
+
+    Ok(())
+}
+
+async fn probe_file(out: Output, file: &Path) -> Result<()> {
+    let media = probe::probe_media(file, &settings::load()).await?;
+
+    match out {
+        Output::Json => out.event("media", json!(media)),
+        Output::Human => print_media(&media),
+    }
+
+    Ok(())
+}
+
+fn print_media(media: &MediaInfo) {
+    println!("Format:   {}", media.describe());
+    if let Some(title) = media.tags.get("title") {
+        println!("Title:    {}", title);
+    }
+    for stream in &media.streams {
+        println!(
+            "Stream {}: {} {}",
+            stream.index,
+            json!(stream.kind).as_str().unwrap_or_default(),
+            stream.describe()
+        );
+    }
+    for chapter in &media.chapters {
+        println!(
+            "Chapter:  {} - {} {}",
+            format_duration(chapter.start),
+            format_duration(chapter.end),
+            chapter.title.as_deref().unwrap_or_default()
+        );
+    }
+}
+
+fn print_info(info: &VideoInfo) {
+    println!("Title:    {}", info.title);
+    if let Some(uploader) = &info.uploader {
+        println!("Uploader: {}", uploader);
+    }
+    println!("Duration: {}", info.duration_string);
+    println!(
+        "Platform: {}",
+        json!(info.platform).as_str().unwrap_or_default()
+    );
+    if info.start_time.is_some() || info.end_time.is_some() {
+        println!(
+            "Range:    {} - {}",
+            format_duration(info.start_time.unwrap_or(0.0)),
+            format_duration(info.end_time.unwrap_or(info.duration))
+        );
+    }
+
+    let video: Vec<String> = info
+        .video_qualities
+        .iter()
+        .map(|q| match &q.filesize_approx {
+            Some(size) => format!("{} (~{})", q.label, size),
+            None => q.label.clone(),
+        })
+        .collect();
+    if !video.is_empty() {
+        println!("Video:    {}", video.join(", "));
+    }
+
+    let audio: Vec<&str> = info
+        .audio_qualities
+        .iter()
+        .map(|q| q.quality_id.as_str())
+        .collect();
+    println!("Audio:    {}", audio.join(", "));
+}
+
+#[allow(clippy::too_many_arguments)] // one per command-line option
+async fn download(
+    out: Output,
+    url: String,
+    quality: Option<String>,
+    mode: Mode,
+    start: Option<String>,
+    end: Option<String>,
+    output: Option<PathBuf>,
+    target_size: Option<u64>,
+    if_exists: Option<IfExists>,
+) -> Result<()> {
+    // Fetch metadata first, like the app does, for the title and duration
+    let settings = settings::load();
+    let info = ytdlp::fetch_video_info(&url, &settings).await?;
+    let fps = info.formats.iter().find_map(|format| format.fps);
+    let (start_time, end_time) = commands::validate_timestamps(start, end, info.duration, fps)?;
+
+    let mode = match mode {
+        Mode::Video => DownloadMode::VideoWithAudio,
+        Mode::Audio => DownloadMode::AudioOnly,
+    };
+    let quality = quality
+        .map(|q| q.trim().trim_end_matches('p').to_string())
+        .unwrap_or_else(|| mode.default_quality().to_string());
+
+    let fields = TemplateFields::for_download(&info, &mode, &quality, start_time, end_time);
+    let output_path = match output {
+        Some(path) if path.is_dir() => routing::output_path(&path, &fields, &mode, &settings)?,
+        Some(path) => path,
+        None => commands::default_output_path(&fields, &mode, &settings)?,
+    };
+
+    let request = DownloadRequest {
+        url,
+        quality,
+        mode,
+        output_path: output_path.to_string_lossy().to_string(),
+        start_time,
+        end_time,
+        if_exists: if_exists.map(CollisionPolicy::from),
+        target_size,
+    };
+    let request = validation::validate_download(request, &settings).await?;
+
+    let (tx, rx) = mpsc::channel::<ProgressUpdate>(32);
+    let (result, ()) = tokio::join!(
+        ytdlp::download_video(&request, &settings, tx),
+        forward_progress(out, rx)
+    );
+    out.end_progress();
+    let output = result?;
+
+    let _ = history::record(HistoryEntry::download(
+        &request,
+        &output.path,
+        Some(info.title),
+    ));
+    finished(out, &output);
+
+    Ok(())
+}
+
+async fn cut(
+    out: Output,
+    file: &Path,
+    start: Option<String>,
+    end: Option<String>,
+    output: Option<PathBuf>,
+    options: CutOptions,
+    if_exists: Option<IfExists>,
+) -> Result<()> {
+    let input_path = file.to_string_lossy().to_string();
+    let duration = ffmpeg::get_duration(&input_path).await?;
+    let (start_time, end_time) = commands::validate_timestamps(start, end, duration, None)?;
+    let (start_time, end_time) = (start_time.unwrap_or(0.0), end_time.unwrap_or(duration));
+
+    // Sized and reframed exports are worth making of the whole file
+    let reencodes = options.target_size.is_some() || options.reframe.is_some();
+    if !reencodes && start_time <= 0.0 && end_time >= duration {
+        return Err(AppError::InvalidTimestamp(
+            "Nothing to cut: the range covers the whole file".to_string(),
+        ));
+    }
+
+    let output_path = output.unwrap_or_else(|| default_cut_path(file));
+    let output_path = output_path.to_string_lossy().to_string();
+    let (start_time, end_time) =
+        validation::validate_cut(&input_path, &output_path, start_time, end_time, &options).await?;
+    let if_exists = if_exists.map_or(settings::load().collision_policy, CollisionPolicy::from);
+
+    let (tx, rx) = mpsc::channel::<ProgressUpdate>(32);
+    let (result, ()) = tokio::join!(
+        ffmpeg::cut_video(
+            &input_path,
+            &output_path,
+            start_time,
+            end_time,
+            &options,
+            if_exists,
+            tx
+        ),
+        forward_progress(out, rx)
+    );
+    out.end_progress();
+    let output = result?;
+
+    let _ = history::record(HistoryEntry::cut(
+        &input_path,
+        &output.path,
+        start_time,
+        end_time,
+    ));
+    finished(out, &output);
+
+    Ok(())
+}
+
+/// `<dir>/<name>_cut.<ext>` next to the input file
+fn default_cut_path(input: &Path) -> PathBuf {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match input.extension() {
+        Some(ext) => format!("{}_cut.{}", stem, ext.to_string_lossy()),
+        None => format!("{}_cut", stem),
+    };
+    input.with_file_name(file_name)
+}
+
+/// Parse a size such as `25M`, `500K` or `1.5G` into bytes; units are
+/// binary, like the sizes the CLI prints
+fn parse_size(value: &str) -> std::result::Result<u64, String> {
+    let value = value.trim();
+    let upper = value.to_ascii_uppercase();
+    let number = upper.trim_end_matches("IB").trim_end_matches('B');
+    let (number, unit) = match number.chars().last() {
+        Some('K') => (&number[..number.len() - 1], 1u64 << 10),
+        Some('M') => (&number[..number.len() - 1], 1 << 20),
+        Some('G') => (&number[..number.len() - 1], 1 << 30),
+        _ => (number, 1),
+    };
+
+    match number.trim().parse::<f64>() {
+        Ok(number) if number > 0.0 => Ok((number * unit as f64) as u64),
+        _ => Err(format!("`{}` is not a size like 25M or 500K", value)),
+    }
+}
+
+/// Parse a crop rectangle given as `WxH+X+Y`, or `W:H:X:Y` as ffmpeg writes
+/// it; the offsets may be left out for the top-left corner
+fn parse_crop(value: &str) -> std::result::Result<CropRect, String> {
+    let invalid = || format!("`{}` is not a crop like 1080x1080+420+0", value);
+    let parts: Vec<&str> = if value.contains(':') {
+        value.split(':').collect()
+    } else {
+        let (size, offset) = value.split_once('+').unwrap_or((value, "0+0"));
+        let (width, height) = size.split_once(['x', 'X']).ok_or_else(invalid)?;
+        let (x, y) = offset.split_once('+').ok_or_else(invalid)?;
+        vec![width, height, x, y]
+    };
+
+    let numbers = parts
+        .iter()
+        .map(|part| part.trim().parse::<u32>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|_| invalid())?;
+    match numbers[..] {
+        [width, height] => Ok(CropRect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }),
+        [width, height, x, y] => Ok(CropRect {
+            x,
+            y,
+            width,
+            height,
+        }),
+        _ => Err(invalid()),
+    }
+}
+
+async fn forward_progress(out: Output, mut rx: mpsc::Receiver<ProgressUpdate>) {
+    while let Some(update) = rx.recv().await {
+        out.progress(&update);
+    }
+}
+
+fn finished(out: Output, output: &VerifiedOutput) {
+    match out {
+        Output::Json => out.event(
+            "complete",
+            json!({ "path": output.path, "verification": output.verification }),
+        ),
+        Output::Human => {
+            println!("Saved to {}", output.path);
+            for warning in &output.verification.warnings {
+                eprintln!("warning: {}", warning);
+            }
+        }
+    }
+}
+
+async fn deps_command(out: Output, action: DepsAction) -> Result<()> {
+    match action {
+        DepsAction::Check => {
+            let status = deps::check_deps_status().await;
+            match out {
+                Output::Json => out.event("deps", json!(status)),
+                Output::Human => {
+                    print_dependency("yt-dlp", &status.ytdlp);
+                    print_dependency("ffmpeg", &status.ffmpeg);
+                    print_dependency("ffprobe", &status.ffprobe);
+                    print_dependency("aria2c", &status.aria2c);
+                    match &status.js_runtime {
+                        Some(runtime) => println!(
+                            "{:<10} {} {} ({})",
+                            "js",
+                            runtime.runtime.ytdlp_name(),
+                            runtime.version.as_deref().unwrap_or("unknown version"),
+                            runtime.path
+                        ),
+                        None => println!("{:<10} not installed", "js"),
+                    }
+                    println!();
+                    println!(
+                        "{}",
+                        if status.ready {
+                            "Ready"
+                        } else {
+                            "Missing required dependencies; run `dlcut-cli deps install`"
+                        }
+                    );
+                }
+            }
+            if !status.ready {
+                return Err(AppError::DependencyError(
+                    "Required dependencies are missing".to_string(),
+                ));
+            }
+        }
+        DepsAction::Install => {
+            deps::install_dependencies(move |message: &str, progress| {
+                out.setup_progress(message, progress)
+            })
+            .await?;
+            out.end_progress();
+            let status = deps::check_deps_status().await;
+            match out {
+                Output::Json => out.event("deps", json!(status)),
+                Output::Human => println!("Dependencies installed"),
+            }
+        }
+        DepsAction::Update => {
+            let version = deps::update_ytdlp(&settings::load(), |message, progress| {
+                out.setup_progress(message, progress)
+            })
+            .await?;
+            out.end_progress();
+            match out {
+                Output::Json => out.event(
+                    "updated",
+                    json!({ "dependency": "ytdlp", "version": version }),
+                ),
+                Output::Human => println!("yt-dlp is now {}", version),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn native_host_command(out: Output, action: NativeHostAction) -> Result<()> {
+    match action {
+        NativeHostAction::Install { chromium, firefox } => {
+            let exe = std::env::current_exe().map_err(|e| {
+                AppError::NativeMessagingError(format!("Failed to locate dlcut-cli: {}", e))
+            })?;
+            let written = native_messaging::install_manifests(&exe, &chromium, &firefox)?;
+
+            for path in written {
+                match out {
+                    Output::Json => out.event("manifest", json!({ "path": path })),
+                    Output::Human => println!("Wrote {}", path.display()),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_dependency(name: &str, info: &DependencyInfo) {
+    if !info.installed {
+        println!("{:<10} not installed", name);
+        return;
+    }
+
+    let source = info
+        .source
+        .map(|s| json!(s).as_str().unwrap_or_default().to_string());
+    println!(
+        "{:<10} {} [{}] ({})",
+        name,
+        info.version.as_deref().unwrap_or("unknown version"),
+        source.unwrap_or_default(),
+        info.path.as_deref().unwrap_or_default()
+    );
+}
+
+fn show_history(out: Output, limit: usize) {
+    for entry in history::load().into_iter().take(limit) {
+        match out {
+            Output::Json => out.event("history", json!(entry)),
+            Output::Human => {
+                let kind = match entry.kind {
+                    HistoryKind::Download => "download",
+                    HistoryKind::Cut => "cut",
+                };
+                let range = match (entry.start_time, entry.end_time) {
+                    (None, None) => String::new(),
+                    (start, end) => format!(
+                        " [{}-{}]",
+                        format_duration(start.unwrap_or(0.0)),
+                        end.map(format_duration)
+                            .unwrap_or_else(|| "end".to_string())
+                    ),
+                };
+                println!(
+                    "{}  {:<8} {}{}\n{:<27}-> {}",
+                    format_utc(entry.completed_at),
+                    kind,
+                    entry.title.as_deref().unwrap_or(&entry.source),
+                    range,
+                    "",
+                    entry.output_path
+                );
+            }
+        }
+    }
+}
+
+/// Format a Unix timestamp as `YYYY-MM-DD HH:MM:SS UTC`
+fn format_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_utc(1_735_689_599), "2024-12-31 23:59:59 UTC");
+    }
+
+    #[test]
+    fn test_default_cut_path() {
+        assert_eq!(
+            default_cut_path(Path::new("/videos/clip.mp4")),
+            PathBuf::from("/videos/clip_cut.mp4")
+        );
+        assert_eq!(
+            default_cut_path(Path::new("recording")),
+            PathBuf::from("recording_cut")
+        );
+    }
+
+    #[test]
+    fn test_cli_parses_download() {
+        let cli = Cli::try_parse_from([
+            "dlcut-cli",
+            "download",
+            "https://youtu.be/dQw4w9WgXcQ",
+            "--quality",
+            "720p",
+            "--mode",
+            "audio",
+            "--start",
+            "1:30",
+            "--end",
+            "2:45",
+            "-o",
+            "/tmp/out.mp3",
+        ])
+        .unwrap();
+
+        match cli.command {
+            Command::Download {
+                quality,
+                mode,
+                start,
+                end,
+                output,
+                ..
+            } => {
+                assert_eq!(quality.as_deref(), Some("720p"));
+                assert!(matches!(mode, Mode::Audio));
+                assert_eq!(start.as_deref(), Some("1:30"));
+                assert_eq!(end.as_deref(), Some("2:45"));
+                assert_eq!(output, Some(PathBuf::from("/tmp/out.mp3")));
+            }
+            _ => panic!("expected download"),
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("25M"), Ok(25 * 1024 * 1024));
+        assert_eq!(parse_size("500k"), Ok(500 * 1024));
+        assert_eq!(parse_size("1.5GB"), Ok(1536 * 1024 * 1024));
+        assert_eq!(parse_size("10 MiB"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert!(parse_size("0M").is_err());
+        assert!(parse_size("big").is_err());
+    }
+
+    #[test]
+    fn test_parse_crop() {
+        let crop = |x, y, width, height| CropRect {
+            x,
+            y,
+            width,
+            height,
+        };
+        assert_eq!(parse_crop("1080x1080+420+0"), Ok(crop(420, 0, 1080, 1080)));
+        assert_eq!(parse_crop("640X480"), Ok(crop(0, 0, 640, 480)));
+        assert_eq!(parse_crop("800:600:100:50"), Ok(crop(100, 50, 800, 600)));
+        assert_eq!(parse_crop("800:600"), Ok(crop(0, 0, 800, 600)));
+        assert!(parse_crop("800x600+100").is_err());
+        assert!(parse_crop("wide").is_err());
+    }
+}
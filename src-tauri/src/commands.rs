@@ -3,11 +3,24 @@
 //! These are the IPC endpoints exposed to the frontend.
 //! All inputs are validated before processing.
 
-use crate::deps::{self, DepsStatus};
+use crate::automation::{self, AutomationInfo, AutomationServer};
+use crate::batch::{self, BatchReport};
+use crate::deeplink::{self, DeepLinkRequest};
+use crate::deps::{self, BinaryValidation, DependencyUpdates, DepsStatus};
 use crate::error::{AppError, Result};
 use crate::ffmpeg;
 use crate::fileserver::FileServer;
-use crate::types::{parse_timestamp, DownloadRequest, ProgressStage, ProgressUpdate, VideoInfo};
+use crate::filename::{self, TemplateFields};
+use crate::history::{self, HistoryEntry};
+use crate::jobs::{Job, JobQueue};
+use crate::probe::{self, MediaInfo};
+use crate::routing;
+use crate::settings::{self, CollisionPolicy, Settings};
+use crate::timestamp;
+use crate::types::{
+    CutOptions, DownloadMode, DownloadRequest, ProgressStage, ProgressUpdate, Reframe, VideoInfo,
+};
+use crate::validation;
 use crate::ytdlp;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -20,6 +33,14 @@ pub struct AppState {
     pub active_download: Mutex<Option<tokio::task::JoinHandle<()>>>,
     /// Local file server for video preview
     pub file_server: Mutex<Option<FileServer>>,
+    /// Downloads queued from the automation API and batch imports
+    pub jobs: Arc<JobQueue>,
+    /// Loopback automation API, when enabled
+    pub automation_server: Mutex<Option<AutomationServer>>,
+    /// Latest dlcut:// link the frontend has not picked up yet
+    pub pending_deep_link: Mutex<Option<DeepLinkRequest>>,
+    /// Last reframe preview frame, removed when the next one is rendered
+    pub preview_frame: Mutex<Option<PathBuf>>,
 }
 
 impl Default for AppState {
@@ -27,6 +48,10 @@ impl Default for AppState {
         Self {
             active_download: Mutex::new(None),
             file_server: Mutex::new(None),
+            jobs: Arc::new(JobQueue::default()),
+            automation_server: Mutex::new(None),
+            pending_deep_link: Mutex::new(None),
+            preview_frame: Mutex::new(None),
         }
     }
 }
@@ -50,6 +75,220 @@ pub async fn install_dependencies(app: AppHandle) -> Result<()> {
     Ok(())
 }
 
+/// Install dependencies from a local yt-dlp binary and/or ffmpeg archive
+#[tauri::command]
+pub async fn install_dependencies_from_path(
+    ytdlp_path: Option<String>,
+    ffmpeg_archive_path: Option<String>,
+    app: AppHandle,
+) -> Result<()> {
+    deps::install_dependencies_from_path(
+        ytdlp_path.as_deref().map(std::path::Path::new),
+        ffmpeg_archive_path.as_deref().map(std::path::Path::new),
+        |message, progress| {
+            let _ = app.emit("setup-progress", serde_json::json!({
+                "message": message,
+                "progress": progress
+            }));
+        },
+    ).await
+}
+
+/// Check pinned binary paths, explaining why any of them would be rejected
+///
+/// Validates the given settings if provided (e.g. before saving them),
+/// otherwise the stored ones.
+#[tauri::command]
+pub async fn validate_dependency_paths(settings: Option<Settings>) -> Vec<BinaryValidation> {
+    let settings = settings.map(Settings::normalized).unwrap_or_else(settings::load);
+    deps::validate_pinned_binaries(&settings).await
+}
+
+/// Compare installed dependencies against their latest releases
+#[tauri::command]
+pub async fn check_dependency_updates() -> Result<DependencyUpdates> {
+    deps::check_dependency_updates(&settings::load()).await
+}
+
+/// Update yt-dlp from the configured release channel
+#[tauri::command]
+pub async fn update_ytdlp(app: AppHandle) -> Result<String> {
+    deps::update_ytdlp(&settings::load(), |message, progress| {
+        let _ = app.emit("setup-progress", serde_json::json!({
+            "message": message,
+            "progress": progress
+        }));
+    }).await
+}
+
+/// Install the optional aria2c external downloader
+#[tauri::command]
+pub async fn install_aria2c(app: AppHandle) -> Result<()> {
+    deps::download_aria2c(|message, progress| {
+        let _ = app.emit("setup-progress", serde_json::json!({
+            "message": message,
+            "progress": progress
+        }));
+    }).await?;
+
+    Ok(())
+}
+
+/// Install Deno so yt-dlp can solve YouTube's JavaScript challenges
+#[tauri::command]
+pub async fn install_js_runtime(app: AppHandle) -> Result<()> {
+    deps::download_deno(|message, progress| {
+        let _ = app.emit("setup-progress", serde_json::json!({
+            "message": message,
+            "progress": progress
+        }));
+    }).await?;
+
+    Ok(())
+}
+
+/// Get the current user settings
+#[tauri::command]
+pub fn get_settings() -> Settings {
+    settings::load()
+}
+
+/// Persist new user settings, returning the normalized values
+#[tauri::command]
+pub fn update_settings(settings: Settings) -> Result<Settings> {
+    // Normalizing would silently drop a template or rule with a typo
+    filename::validate_template(&settings.filename_template)?;
+    for rule in &settings.output_rules {
+        routing::validate_folder(rule.folder.trim())?;
+    }
+    let settings = settings.normalized();
+    settings::save(&settings)?;
+    Ok(settings)
+}
+
+/// Queue a download behind any running ones
+#[tauri::command]
+pub async fn enqueue_download(request: DownloadRequest, state: State<'_, Arc<AppState>>) -> Result<Job> {
+    state.jobs.enqueue(request).await
+}
+
+/// Validate pasted links or CSV rows and queue the valid ones, reporting on
+/// every row
+#[tauri::command]
+pub async fn import_batch(
+    text: String,
+    output_dir: Option<String>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<BatchReport> {
+    let output_dir = output_dir
+        .filter(|dir| !dir.trim().is_empty())
+        .or_else(get_default_download_dir)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    if !output_dir.is_dir() {
+        return Err(AppError::BatchError("The output folder does not exist".to_string()));
+    }
+
+    batch::import(&text, &output_dir, &state.jobs).await
+}
+
+/// List queued, running and recently finished jobs
+#[tauri::command]
+pub fn list_jobs(state: State<'_, Arc<AppState>>) -> Vec<Job> {
+    state.jobs.list()
+}
+
+/// Start the automation API and remember to start it on launch
+#[tauri::command]
+pub async fn start_automation_server(state: State<'_, Arc<AppState>>) -> Result<AutomationInfo> {
+    let mut settings = settings::load();
+    if !settings.automation_api {
+        settings.automation_api = true;
+        settings::save(&settings)?;
+    }
+
+    start_automation(state.inner()).await
+}
+
+/// Stop the automation API and keep it off on launch
+#[tauri::command]
+pub async fn stop_automation_server(state: State<'_, Arc<AppState>>) -> Result<()> {
+    if let Some(server) = state.automation_server.lock().await.take() {
+        server.stop();
+    }
+
+    let mut settings = settings::load();
+    settings.automation_api = false;
+    settings::save(&settings)
+}
+
+/// Get the automation API address and token, if it is running
+#[tauri::command]
+pub async fn get_automation_info(state: State<'_, Arc<AppState>>) -> Result<Option<AutomationInfo>> {
+    Ok(state.automation_server.lock().await.as_ref().map(AutomationServer::info))
+}
+
+/// Start the automation API if it is not already running, generating its
+/// token on first use
+pub async fn start_automation(state: &AppState) -> Result<AutomationInfo> {
+    let mut server = state.automation_server.lock().await;
+    if let Some(server) = server.as_ref() {
+        return Ok(server.info());
+    }
+
+    let mut settings = settings::load();
+    let token = match settings.automation_token.clone() {
+        Some(token) => token,
+        None => {
+            let token = automation::generate_token()?;
+            settings.automation_token = Some(token.clone());
+            settings::save(&settings)?;
+            token
+        }
+    };
+
+    let started = AutomationServer::start(settings.automation_port, token, state.jobs.clone())
+        .await
+        .map_err(|e| {
+            AppError::AutomationError(format!("Failed to listen on port {}: {}", settings.automation_port, e))
+        })?;
+    let info = started.info();
+    *server = Some(started);
+
+    Ok(info)
+}
+
+/// Validate a dlcut:// link and let the frontend know it can pick it up
+pub async fn open_deep_link(app: &AppHandle, state: &AppState, link: &str) {
+    match deeplink::parse_deep_link(link) {
+        Ok(request) => {
+            *state.pending_deep_link.lock().await = Some(request);
+            let _ = app.emit("deep-link", ());
+        }
+        Err(e) => {
+            let _ = app.emit("deep-link-error", e.to_string());
+        }
+    }
+}
+
+/// Take the link that opened or was forwarded to the app, if any
+#[tauri::command]
+pub async fn take_pending_deep_link(state: State<'_, Arc<AppState>>) -> Result<Option<DeepLinkRequest>> {
+    Ok(state.pending_deep_link.lock().await.take())
+}
+
+/// Get completed downloads and cuts, newest first
+#[tauri::command]
+pub fn get_history() -> Vec<HistoryEntry> {
+    history::load()
+}
+
+/// Remove all history entries
+#[tauri::command]
+pub fn clear_history() -> Result<()> {
+    history::clear()
+}
+
 /// Fetch video information from a supported URL
 #[tauri::command]
 pub async fn fetch_video_info(url: String, app: AppHandle) -> Result<VideoInfo> {
@@ -62,7 +301,7 @@ pub async fn fetch_video_info(url: String, app: AppHandle) -> Result<VideoInfo>
         eta: None,
     });
 
-    let info = ytdlp::fetch_video_info(&url).await?;
+    let info = ytdlp::fetch_video_info(&url, &settings::load()).await?;
 
     let _ = app.emit("progress", ProgressUpdate {
         stage: ProgressStage::Fetching,
@@ -76,62 +315,16 @@ pub async fn fetch_video_info(url: String, app: AppHandle) -> Result<VideoInfo>
 }
 
 /// Validate timestamps against video duration
+///
+/// Accepts every form `timestamp::parse` does; `fps` enables SMPTE timecodes.
 #[tauri::command]
 pub fn validate_timestamps(
     start: Option<String>,
     end: Option<String>,
     duration: f64,
+    fps: Option<f64>,
 ) -> Result<(Option<f64>, Option<f64>)> {
-    let start_secs = if let Some(ref s) = start {
-        if s.trim().is_empty() {
-            None
-        } else {
-            Some(parse_timestamp(s).ok_or_else(|| {
-                AppError::InvalidTimestamp(format!("Invalid start time: {}", s))
-            })?)
-        }
-    } else {
-        None
-    };
-
-    let end_secs = if let Some(ref e) = end {
-        if e.trim().is_empty() {
-            None
-        } else {
-            Some(parse_timestamp(e).ok_or_else(|| {
-                AppError::InvalidTimestamp(format!("Invalid end time: {}", e))
-            })?)
-        }
-    } else {
-        None
-    };
-
-    // Validate ranges
-    if let Some(start) = start_secs {
-        if start < 0.0 {
-            return Err(AppError::InvalidTimestamp("Start time cannot be negative".to_string()));
-        }
-        if start >= duration {
-            return Err(AppError::InvalidTimestamp("Start time exceeds video duration".to_string()));
-        }
-    }
-
-    if let Some(end) = end_secs {
-        if end <= 0.0 {
-            return Err(AppError::InvalidTimestamp("End time must be positive".to_string()));
-        }
-        if end > duration {
-            return Err(AppError::InvalidTimestamp("End time exceeds video duration".to_string()));
-        }
-    }
-
-    if let (Some(start), Some(end)) = (start_secs, end_secs) {
-        if start >= end {
-            return Err(AppError::InvalidTimestamp("Start time must be before end time".to_string()));
-        }
-    }
-
-    Ok((start_secs, end_secs))
+    timestamp::resolve_range(start.as_deref(), end.as_deref(), Some(duration), fps)
 }
 
 /// Start downloading a video
@@ -141,9 +334,6 @@ pub async fn start_download(
     state: State<'_, Arc<AppState>>,
     app: AppHandle,
 ) -> Result<()> {
-    // Validate URL
-    ytdlp::validate_url(&request.url)?;
-
     // Check if there's already an active download
     {
         let active = state.active_download.lock().await;
@@ -152,16 +342,12 @@ pub async fn start_download(
         }
     }
 
+    // Validate URL and cut range
+    let settings = settings::load();
+    let request = validation::validate_download(request, &settings).await?;
+
     // Create progress channel
     let (tx, mut rx) = tokio::sync::mpsc::channel::<ProgressUpdate>(32);
-
-    // Clone values for the spawned task
-    let url = request.url.clone();
-    let mode = request.mode.clone();
-    let quality = request.quality.clone();
-    let output_path = request.output_path.clone();
-    let start_time = request.start_time;
-    let end_time = request.end_time;
     let app_clone = app.clone();
 
     // Spawn progress forwarding task
@@ -175,21 +361,13 @@ pub async fn start_download(
     // Spawn download task
     let state_clone = state.inner().clone();
     let handle = tokio::spawn(async move {
-        let result = ytdlp::download_video(
-            &url,
-            &mode,
-            &quality,
-            &output_path,
-            start_time,
-            end_time,
-            tx.clone(),
-        )
-        .await;
+        let result = ytdlp::download_video(&request, &settings, tx.clone()).await;
 
         // Emit final status
         match result {
-            Ok(actual_path) => {
-                let _ = app_clone.emit("download-complete", &actual_path);
+            Ok(output) => {
+                let _ = history::record(HistoryEntry::download(&request, &output.path, None));
+                let _ = app_clone.emit("download-complete", &output);
             }
             Err(e) => {
                 let _ = app_clone.emit("progress", ProgressUpdate {
@@ -232,23 +410,23 @@ pub async fn cancel_download(state: State<'_, Arc<AppState>>) -> Result<()> {
 /// Generate output filename from video info
 #[tauri::command]
 pub fn generate_filename(title: String, format_ext: String) -> String {
-    // Sanitize filename by removing invalid characters
-    let sanitized: String = title
-        .chars()
-        .map(|c| match c {
-            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
-            _ => c,
-        })
-        .collect();
-
-    // Truncate if too long (max 200 chars for safety)
-    let truncated = if sanitized.len() > 200 {
-        sanitized[..200].to_string()
-    } else {
-        sanitized
-    };
+    filename::file_name(&title, &format_ext)
+}
 
-    format!("{}.{}", truncated.trim(), format_ext)
+/// Where a download would be saved by default, routed by the output rules
+/// and named by the filename template, for the save dialog to suggest
+#[tauri::command]
+pub fn suggest_output_path(
+    info: VideoInfo,
+    mode: DownloadMode,
+    quality: String,
+    start_time: Option<f64>,
+    end_time: Option<f64>,
+) -> Result<String> {
+    let settings = settings::load();
+    let fields = TemplateFields::for_download(&info, &mode, &quality, start_time, end_time);
+    let path = default_output_path(&fields, &mode, &settings)?;
+    Ok(path.to_string_lossy().to_string())
 }
 
 /// Reveal a file in the system file manager
@@ -291,34 +469,74 @@ pub fn get_default_download_dir() -> Option<String> {
         .map(|p| p.to_string_lossy().to_string())
 }
 
+/// Path a video is saved to when no output is chosen: the default download
+/// directory, or the folder of a matching output rule inside it
+pub fn default_output_path(
+    fields: &TemplateFields,
+    mode: &DownloadMode,
+    settings: &Settings,
+) -> Result<PathBuf> {
+    let dir = PathBuf::from(get_default_download_dir().unwrap_or_else(|| ".".into()));
+    routing::output_path(&dir, fields, mode, settings)
+}
+
 /// Get video duration using ffprobe
 #[tauri::command]
 pub async fn get_video_duration(path: String) -> Result<f64> {
     ffmpeg::get_duration(&path).await
 }
 
-/// Cut a local video file
+/// Inspect a local media file: container, streams, chapters and tags
+#[tauri::command]
+pub async fn probe_media(path: String) -> Result<MediaInfo> {
+    probe::probe_media(std::path::Path::new(&path), &settings::load()).await
+}
+
+/// Render the frame of a local file at `time` as `reframe` would make it,
+/// returning the path of the image
+#[tauri::command]
+pub async fn preview_reframe(
+    input_path: String,
+    time: f64,
+    reframe: Reframe,
+    state: State<'_, Arc<AppState>>,
+) -> Result<String> {
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    // A new name each time, so the webview doesn't show a cached frame
+    let path = std::env::temp_dir().join(format!(
+        "dlcut-preview-{}-{}.jpg",
+        std::process::id(),
+        stamp
+    ));
+    ffmpeg::render_frame(&input_path, time, Some(&reframe), &path).await?;
+
+    let mut previous = state.preview_frame.lock().await;
+    if let Some(old) = previous.replace(path.clone()) {
+        let _ = std::fs::remove_file(old);
+    }
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Cut a local video file, keeping all streams or only the input streams
+/// selected in `options`, which also set the quality of a re-encode
 #[tauri::command]
 pub async fn cut_local_video(
     input_path: String,
     output_path: String,
     start_time: f64,
     end_time: f64,
+    options: Option<CutOptions>,
     state: State<'_, Arc<AppState>>,
     app: AppHandle,
 ) -> Result<()> {
-    // Validate inputs
-    if start_time < 0.0 {
-        return Err(AppError::InvalidTimestamp("Start time cannot be negative".to_string()));
-    }
-    if end_time <= start_time {
-        return Err(AppError::InvalidTimestamp("End time must be after start time".to_string()));
-    }
+    let options = options.unwrap_or_default();
 
-    let input = std::path::Path::new(&input_path);
-    if !input.exists() {
-        return Err(AppError::CutError("Input file not found".to_string()));
-    }
+    // Validate inputs against the file's duration, streams and encoders
+    let (start_time, end_time) =
+        validation::validate_cut(&input_path, &output_path, start_time, end_time, &options).await?;
 
     // Create progress channel
     let (tx, mut rx) = tokio::sync::mpsc::channel::<ProgressUpdate>(32);
@@ -338,18 +556,27 @@ pub async fn cut_local_video(
     // Spawn cut task
     let state_clone = state.inner().clone();
     let handle = tokio::spawn(async move {
+        // The save dialog already asked before replacing an existing file
         let result = ffmpeg::cut_video(
             &input_clone,
             &output_clone,
             start_time,
             end_time,
+            &options,
+            CollisionPolicy::Overwrite,
             tx,
         )
         .await;
 
         match result {
-            Ok(_) => {
-                let _ = app_clone.emit("cut-complete", &output_clone);
+            Ok(output) => {
+                let _ = history::record(HistoryEntry::cut(
+                    &input_clone,
+                    &output.path,
+                    start_time,
+                    end_time,
+                ));
+                let _ = app_clone.emit("cut-complete", &output);
             }
             Err(e) => {
                 let _ = app_clone.emit("cut-error", e.to_string());
@@ -421,25 +648,25 @@ mod tests {
     #[test]
     fn test_validate_timestamps() {
         // Valid timestamps
-        let result = validate_timestamps(Some("0:30".to_string()), Some("1:30".to_string()), 120.0);
+        let result = validate_timestamps(Some("0:30".to_string()), Some("1:30".to_string()), 120.0, None);
         assert!(result.is_ok());
         let (start, end) = result.unwrap();
         assert_eq!(start, Some(30.0));
         assert_eq!(end, Some(90.0));
 
         // Empty strings should be None
-        let result = validate_timestamps(Some("".to_string()), Some("".to_string()), 120.0);
+        let result = validate_timestamps(Some("".to_string()), Some("".to_string()), 120.0, None);
         assert!(result.is_ok());
         let (start, end) = result.unwrap();
         assert_eq!(start, None);
         assert_eq!(end, None);
 
         // Invalid: start >= end
-        let result = validate_timestamps(Some("1:00".to_string()), Some("0:30".to_string()), 120.0);
+        let result = validate_timestamps(Some("1:00".to_string()), Some("0:30".to_string()), 120.0, None);
         assert!(result.is_err());
 
         // Invalid: exceeds duration
-        let result = validate_timestamps(Some("2:30".to_string()), None, 120.0);
+        let result = validate_timestamps(Some("2:30".to_string()), None, 120.0, None);
         assert!(result.is_err());
     }
 }
@@ -0,0 +1,309 @@
+//! `dlcut://` deep links
+//!
+//! Links such as `dlcut://download?url=...&start=1:30&end=2:00&mode=audio`
+//! open DLCut with a download pre-filled. They arrive from other apps and
+//! chat messages, so everything is validated before it reaches the UI: the
+//! video URL goes through `validate_url`, times through `parse_timestamp`,
+//! and unknown, repeated or malformed parameters reject the whole link.
+
+use crate::error::{AppError, Result};
+use crate::types::{normalize_quality, parse_timestamp, DownloadMode, Platform};
+use crate::ytdlp::validate_url;
+use serde::Serialize;
+
+/// URL scheme registered with the OS
+pub const SCHEME: &str = "dlcut";
+
+/// Longest link accepted; real links are a video URL plus a few short values
+const MAX_LINK_LEN: usize = 4096;
+
+/// A validated request to pre-fill the download form
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DeepLinkRequest {
+    pub url: String,
+    pub platform: Platform,
+    pub mode: Option<DownloadMode>,
+    /// Video height (e.g. "720") or audio quality id (e.g. "high")
+    pub quality: Option<String>,
+    /// Start time in seconds
+    pub start_time: Option<f64>,
+    /// End time in seconds
+    pub end_time: Option<f64>,
+}
+
+fn invalid(message: impl Into<String>) -> AppError {
+    AppError::InvalidDeepLink(message.into())
+}
+
+/// Parse and validate a `dlcut://download?...` link
+///
+/// A `+` is kept as-is rather than read as a space, since it can be part of
+/// the video URL.
+pub fn parse_deep_link(link: &str) -> Result<DeepLinkRequest> {
+    if link.len() > MAX_LINK_LEN {
+        return Err(invalid("Link is too long"));
+    }
+    if link.chars().any(|c| c.is_control() || c.is_whitespace()) {
+        return Err(invalid("Link contains whitespace or control characters"));
+    }
+
+    let (scheme, rest) = link
+        .split_once("://")
+        .ok_or_else(|| invalid("Not a dlcut:// link"))?;
+    if !scheme.eq_ignore_ascii_case(SCHEME) {
+        return Err(invalid("Not a dlcut:// link"));
+    }
+
+    let rest = rest.split_once('#').map_or(rest, |(before, _)| before);
+    let (action, query) = rest.split_once('?').unwrap_or((rest, ""));
+    if !action
+        .trim_end_matches('/')
+        .eq_ignore_ascii_case("download")
+    {
+        return Err(invalid(format!("Unknown action: {}", action)));
+    }
+
+    let mut url = None;
+    let mut start = None;
+    let mut end = None;
+    let mut mode = None;
+    let mut quality = None;
+
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let key = decode(key)?;
+        let value = decode(value)?;
+
+        let slot = match key.as_str() {
+            "url" => &mut url,
+            "start" => &mut start,
+            "end" => &mut end,
+            "mode" => &mut mode,
+            "quality" => &mut quality,
+            _ => return Err(invalid(format!("Unknown parameter: {}", key))),
+        };
+        if slot.is_some() {
+            return Err(invalid(format!("Duplicate parameter: {}", key)));
+        }
+        *slot = Some(value);
+    }
+
+    let url = url
+        .filter(|url| !url.is_empty())
+        .ok_or_else(|| invalid("Missing url parameter"))?;
+    let platform = validate_url(&url)?;
+
+    let mode = match mode.as_deref() {
+        None | Some("") => None,
+        Some("video") => Some(DownloadMode::VideoWithAudio),
+        Some("audio") => Some(DownloadMode::AudioOnly),
+        Some(other) => return Err(invalid(format!("Unknown mode: {}", other))),
+    };
+    if mode == Some(DownloadMode::VideoWithAudio) && !platform.supports_video() {
+        return Err(invalid("This platform only supports audio downloads"));
+    }
+
+    let quality = match quality.as_deref() {
+        None | Some("") => None,
+        Some(quality) => Some(
+            normalize_quality(quality, mode.as_ref())
+                .ok_or_else(|| invalid(format!("Invalid quality: {}", quality)))?,
+        ),
+    };
+
+    let start_time = parse_time("start", start.as_deref())?;
+    let end_time = parse_time("end", end.as_deref())?;
+    if let (Some(start), Some(end)) = (start_time, end_time) {
+        if start >= end {
+            return Err(invalid("Start time must be before end time"));
+        }
+    }
+
+    Ok(DeepLinkRequest {
+        url,
+        platform,
+        mode,
+        quality,
+        start_time,
+        end_time,
+    })
+}
+
+/// Strictly percent-decode a query component
+fn decode(component: &str) -> Result<String> {
+    let decoded = percent_encoding::percent_decode_str(component)
+        .decode_utf8()
+        .map_err(|_| invalid("Parameter is not valid UTF-8"))?;
+
+    // Encoded newlines or NULs could smuggle extra lines into logs or arguments
+    if decoded.chars().any(char::is_control) {
+        return Err(invalid("Parameter contains control characters"));
+    }
+
+    Ok(decoded.into_owned())
+}
+
+/// Parse an optional timestamp parameter into seconds
+fn parse_time(name: &str, value: Option<&str>) -> Result<Option<f64>> {
+    let Some(value) = value.filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+
+    match parse_timestamp(value) {
+        // Rejects NaN and infinity, which parse as floats
+        Some(seconds) if seconds.is_finite() && seconds >= 0.0 => Ok(Some(seconds)),
+        _ => Err(AppError::InvalidTimestamp(format!(
+            "Invalid {} time: {}",
+            name, value
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIDEO: &str = "https%3A%2F%2Fwww.youtube.com%2Fwatch%3Fv%3DdQw4w9WgXcQ";
+
+    #[test]
+    fn test_parse_full_link() {
+        let link = format!(
+            "dlcut://download?url={}&start=1:30&end=2:00&mode=audio&quality=medium",
+            VIDEO
+        );
+        let request = parse_deep_link(&link).unwrap();
+
+        assert_eq!(request.url, "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+        assert_eq!(request.platform, Platform::YouTube);
+        assert_eq!(request.mode, Some(DownloadMode::AudioOnly));
+        assert_eq!(request.quality.as_deref(), Some("medium"));
+        assert_eq!(request.start_time, Some(90.0));
+        assert_eq!(request.end_time, Some(120.0));
+    }
+
+    #[test]
+    fn test_parse_minimal_link() {
+        let request =
+            parse_deep_link("DLCUT://download/?url=https://youtu.be/dQw4w9WgXcQ").unwrap();
+        assert_eq!(request.url, "https://youtu.be/dQw4w9WgXcQ");
+        assert_eq!(request.mode, None);
+        assert_eq!(request.quality, None);
+        assert_eq!(request.start_time, None);
+
+        // Empty optional values are ignored, as are fragments
+        let link = format!("dlcut://download?url={}&start=&mode=#share", VIDEO);
+        assert_eq!(parse_deep_link(&link).unwrap().start_time, None);
+
+        let link = format!("dlcut://download?url={}&quality=1080p", VIDEO);
+        assert_eq!(
+            parse_deep_link(&link).unwrap().quality.as_deref(),
+            Some("1080")
+        );
+    }
+
+    #[test]
+    fn test_rejects_wrong_scheme_or_action() {
+        for link in [
+            "https://download?url=https://youtu.be/dQw4w9WgXcQ",
+            "dlcut:download?url=https://youtu.be/dQw4w9WgXcQ",
+            "dlcutx://download?url=https://youtu.be/dQw4w9WgXcQ",
+            "dlcut://delete?url=https://youtu.be/dQw4w9WgXcQ",
+            "dlcut://download/../settings?url=https://youtu.be/dQw4w9WgXcQ",
+            "dlcut://",
+            "",
+        ] {
+            assert!(parse_deep_link(link).is_err(), "accepted {:?}", link);
+        }
+    }
+
+    #[test]
+    fn test_rejects_unsupported_urls() {
+        for url in [
+            "",
+            "https%3A%2F%2Fevil.example%2Fwatch%3Fv%3Dabc",
+            "file%3A%2F%2F%2Fetc%2Fpasswd",
+            "javascript%3Aalert(1)",
+            "--exec%3Drm",
+            "https%3A%2F%2Fyoutube.com.evil.example%2Fwatch%3Fv%3Dabc",
+        ] {
+            let link = format!("dlcut://download?url={}", url);
+            assert!(parse_deep_link(&link).is_err(), "accepted {:?}", link);
+        }
+
+        assert!(parse_deep_link("dlcut://download?start=10").is_err());
+    }
+
+    #[test]
+    fn test_rejects_smuggled_characters() {
+        for link in [
+            format!("dlcut://download?url={}%0A--exec%20rm", VIDEO),
+            format!("dlcut://download?url={}%00", VIDEO),
+            format!("dlcut://download?url={}&mode=audio%0D%0A", VIDEO),
+            format!("dlcut://download?url={}&start=%FF%FE", VIDEO),
+            format!("dlcut://download?url={} &start=1", VIDEO),
+            format!("dlcut://download?url={}\n", VIDEO),
+        ] {
+            assert!(parse_deep_link(&link).is_err(), "accepted {:?}", link);
+        }
+    }
+
+    #[test]
+    fn test_rejects_duplicate_and_unknown_parameters() {
+        let link = format!("dlcut://download?url={}&url=https://youtu.be/other", VIDEO);
+        assert!(parse_deep_link(&link).is_err());
+
+        let link = format!("dlcut://download?url={}&start=1&start=2", VIDEO);
+        assert!(parse_deep_link(&link).is_err());
+
+        let link = format!("dlcut://download?url={}&output=/etc/passwd", VIDEO);
+        assert!(parse_deep_link(&link).is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_times() {
+        for (start, end) in [
+            ("abc", "10"),
+            ("-5", "10"),
+            ("NaN", "10"),
+            ("inf", ""),
+            ("1:2:3:4", ""),
+            ("30", "10"),
+            ("10", "10"),
+            ("", "1e400"),
+        ] {
+            let link = format!("dlcut://download?url={}&start={}&end={}", VIDEO, start, end);
+            assert!(parse_deep_link(&link).is_err(), "accepted {:?}", link);
+        }
+    }
+
+    #[test]
+    fn test_rejects_invalid_mode_and_quality() {
+        for params in [
+            "mode=video_with_audio",
+            "mode=AUDIO",
+            "quality=best",
+            "quality=0",
+            "quality=99999",
+            "mode=audio&quality=720",
+            "mode=video&quality=high",
+            "quality=720;rm",
+        ] {
+            let link = format!("dlcut://download?url={}&{}", VIDEO, params);
+            assert!(parse_deep_link(&link).is_err(), "accepted {:?}", link);
+        }
+
+        // SoundCloud has no video
+        let link = "dlcut://download?url=https://soundcloud.com/artist/track&mode=video";
+        assert!(parse_deep_link(link).is_err());
+    }
+
+    #[test]
+    fn test_rejects_oversized_link() {
+        let link = format!(
+            "dlcut://download?url={}&x={}",
+            VIDEO,
+            "a".repeat(MAX_LINK_LEN)
+        );
+        assert!(parse_deep_link(&link).is_err());
+    }
+}
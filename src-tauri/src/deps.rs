@@ -1,12 +1,14 @@
 //! Dependency manager
 //!
-//! Handles automatic downloading and management of yt-dlp and ffmpeg binaries.
-//! Binaries are stored in the user's local app data directory.
+//! Handles automatic downloading and management of yt-dlp, ffmpeg/ffprobe and
+//! the optional aria2c and Deno binaries. Binaries are stored in the user's local app data directory.
 
 use crate::error::{AppError, Result};
+use crate::settings::{self, ResolutionPolicy, Settings, UpdateChannel};
 use futures_util::StreamExt;
 use std::io::Write;
-use std::path::PathBuf;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
 use tokio::process::Command;
 
 #[cfg(windows)]
@@ -49,8 +51,44 @@ pub fn get_ffmpeg_path() -> Result<PathBuf> {
     Ok(deps_dir.join(binary))
 }
 
+/// Get the path to ffprobe binary
+pub fn get_ffprobe_path() -> Result<PathBuf> {
+    let deps_dir = get_deps_dir()?;
+
+    #[cfg(windows)]
+    let binary = "ffprobe.exe";
+    #[cfg(not(windows))]
+    let binary = "ffprobe";
+
+    Ok(deps_dir.join(binary))
+}
+
+/// Get the path to aria2c binary
+pub fn get_aria2c_path() -> Result<PathBuf> {
+    let deps_dir = get_deps_dir()?;
+
+    #[cfg(windows)]
+    let binary = "aria2c.exe";
+    #[cfg(not(windows))]
+    let binary = "aria2c";
+
+    Ok(deps_dir.join(binary))
+}
+
+/// Get the path to deno binary
+pub fn get_deno_path() -> Result<PathBuf> {
+    let deps_dir = get_deps_dir()?;
+
+    #[cfg(windows)]
+    let binary = "deno.exe";
+    #[cfg(not(windows))]
+    let binary = "deno";
+
+    Ok(deps_dir.join(binary))
+}
+
 /// Check if a binary exists and is executable
-async fn check_binary(path: &PathBuf, version_arg: &str) -> bool {
+async fn check_binary(path: &Path, version_arg: &str) -> bool {
     if !path.exists() {
         return false;
     }
@@ -64,62 +102,306 @@ async fn check_binary(path: &PathBuf, version_arg: &str) -> bool {
     cmd.output().await.map(|o| o.status.success()).unwrap_or(false)
 }
 
-/// Check if yt-dlp is available (either local or system)
-pub async fn is_ytdlp_available() -> bool {
-    // First check local
-    if let Ok(path) = get_ytdlp_path() {
-        if check_binary(&path, "--version").await {
-            return true;
+/// A managed external binary
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Dependency {
+    Ytdlp,
+    Ffmpeg,
+    Ffprobe,
+    Aria2c,
+    Deno,
+}
+
+impl Dependency {
+    /// Command name on the system PATH
+    pub fn command_name(&self) -> &'static str {
+        match self {
+            Dependency::Ytdlp => "yt-dlp",
+            Dependency::Ffmpeg => "ffmpeg",
+            Dependency::Ffprobe => "ffprobe",
+            Dependency::Aria2c => "aria2c",
+            Dependency::Deno => "deno",
+        }
+    }
+
+    /// Flag that makes the binary print its version
+    fn version_arg(&self) -> &'static str {
+        match self {
+            Dependency::Ytdlp | Dependency::Aria2c | Dependency::Deno => "--version",
+            Dependency::Ffmpeg | Dependency::Ffprobe => "-version",
         }
     }
 
-    // Then check system PATH
-    let mut cmd = Command::new("yt-dlp");
-    cmd.arg("--version");
+    /// Location inside `DLCut/bin`
+    pub fn local_path(&self) -> Result<PathBuf> {
+        match self {
+            Dependency::Ytdlp => get_ytdlp_path(),
+            Dependency::Ffmpeg => get_ffmpeg_path(),
+            Dependency::Ffprobe => get_ffprobe_path(),
+            Dependency::Aria2c => get_aria2c_path(),
+            Dependency::Deno => get_deno_path(),
+        }
+    }
 
-    #[cfg(windows)]
-    cmd.creation_flags(CREATE_NO_WINDOW);
+    /// Path pinned in settings, if any
+    fn pinned_path<'a>(&self, settings: &'a Settings) -> Option<&'a str> {
+        let paths = &settings.binary_paths;
+        match self {
+            Dependency::Ytdlp => paths.ytdlp.as_deref(),
+            Dependency::Ffmpeg => paths.ffmpeg.as_deref(),
+            Dependency::Ffprobe => paths.ffprobe.as_deref(),
+            Dependency::Aria2c => paths.aria2c.as_deref(),
+            Dependency::Deno => paths.deno.as_deref(),
+        }
+    }
 
-    cmd.output().await.map(|o| o.status.success()).unwrap_or(false)
+    /// Whether version output looks like it came from this tool
+    fn matches_version_output(&self, output: &str) -> bool {
+        let first_line = output.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+        match self {
+            // yt-dlp prints a bare date-based version such as 2024.08.06
+            Dependency::Ytdlp => first_line
+                .split('.')
+                .next()
+                .is_some_and(|year| year.len() == 4 && year.chars().all(|c| c.is_ascii_digit())),
+            Dependency::Ffmpeg => first_line.starts_with("ffmpeg version"),
+            Dependency::Ffprobe => first_line.starts_with("ffprobe version"),
+            Dependency::Aria2c => first_line.starts_with("aria2 version"),
+            Dependency::Deno => first_line.starts_with("deno "),
+        }
+    }
 }
 
-/// Check if ffmpeg is available (either local or system)
-pub async fn is_ffmpeg_available() -> bool {
-    // First check local
-    if let Ok(path) = get_ffmpeg_path() {
-        if check_binary(&path, "-version").await {
-            return true;
-        }
+/// A binary picked by the resolution policy
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedBinary {
+    pub path: PathBuf,
+    pub source: DependencySource,
+}
+
+/// Pick the binary to run for `dependency` according to the user's settings
+pub fn resolve_binary(dependency: Dependency, settings: &Settings) -> Option<ResolvedBinary> {
+    choose_binary(
+        dependency.pinned_path(settings),
+        dependency.local_path().ok(),
+        find_in_path(dependency.command_name()),
+        settings.resolution_policy,
+    )
+}
+
+/// Apply the resolution rules to the candidate locations
+fn choose_binary(
+    pinned: Option<&str>,
+    local: Option<PathBuf>,
+    system: Option<PathBuf>,
+    policy: ResolutionPolicy,
+) -> Option<ResolvedBinary> {
+    // A pinned path is used as-is so a broken pin is reported instead of silently replaced
+    if let Some(path) = pinned {
+        return Some(ResolvedBinary {
+            path: PathBuf::from(path),
+            source: DependencySource::Custom,
+        });
     }
 
-    // Then check system PATH
-    let mut cmd = Command::new("ffmpeg");
-    cmd.arg("-version");
+    let as_local = |path| ResolvedBinary { path, source: DependencySource::Local };
+    let installed_local = local.clone().filter(|p| p.exists()).map(as_local);
+    let system = system.map(|path| ResolvedBinary { path, source: DependencySource::System });
 
-    #[cfg(windows)]
-    cmd.creation_flags(CREATE_NO_WINDOW);
+    match policy {
+        ResolutionPolicy::BundledFirst => installed_local.or(system),
+        ResolutionPolicy::SystemFirst => system.or(installed_local),
+        // Never fall back to PATH; a missing local binary shows up as not installed
+        ResolutionPolicy::BundledOnly => local.map(as_local),
+    }
+}
 
-    cmd.output().await.map(|o| o.status.success()).unwrap_or(false)
+/// Whether an installed local copy would be picked over a pin or a binary
+/// on PATH, i.e. whether installing into `DLCut/bin` changes the binary in use
+fn prefers_local(pinned: Option<&str>, on_path: bool, policy: ResolutionPolicy) -> bool {
+    pinned.is_none() && (policy != ResolutionPolicy::SystemFirst || !on_path)
+}
+
+/// Whether `settings` resolve `dependency` to DLCut's own copy once it is
+/// installed, so installing or updating it takes effect
+fn uses_local_copy(dependency: Dependency, settings: &Settings) -> bool {
+    prefers_local(
+        dependency.pinned_path(settings),
+        find_in_path(dependency.command_name()).is_some(),
+        settings.resolution_policy,
+    )
+}
+
+/// Look up an executable on the system PATH
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let file_name = exe_name(name);
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// Resolve the command to spawn, falling back to the bare name so spawn errors
+/// surface as "not found"
+pub fn resolve_command(dependency: Dependency, settings: &Settings) -> String {
+    resolve_binary(dependency, settings)
+        .map(|binary| binary.path.to_string_lossy().to_string())
+        .unwrap_or_else(|| dependency.command_name().to_string())
 }
 
-/// Get the command for yt-dlp (local path if available, otherwise system)
-pub async fn get_ytdlp_command() -> String {
-    if let Ok(path) = get_ytdlp_path() {
-        if path.exists() {
-            return path.to_string_lossy().to_string();
+/// JavaScript runtimes yt-dlp can use to solve YouTube challenges
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JsRuntime {
+    Deno,
+    Node,
+    QuickJs,
+}
+
+impl JsRuntime {
+    /// Name yt-dlp expects in `--js-runtimes`
+    pub fn ytdlp_name(&self) -> &'static str {
+        match self {
+            JsRuntime::Deno => "deno",
+            JsRuntime::Node => "node",
+            JsRuntime::QuickJs => "quickjs",
         }
     }
-    "yt-dlp".to_string()
+
+    /// Command name on the system PATH
+    fn command_name(&self) -> &'static str {
+        match self {
+            JsRuntime::Deno => "deno",
+            JsRuntime::Node => "node",
+            JsRuntime::QuickJs => "qjs",
+        }
+    }
+}
+
+/// Pick the JavaScript runtime handed to yt-dlp
+///
+/// Deno is preferred since it is what yt-dlp recommends and the only one DLCut
+/// installs; Node and QuickJS are only picked up from PATH.
+pub fn resolve_js_runtime(settings: &Settings) -> Option<(JsRuntime, ResolvedBinary)> {
+    if let Some(deno) = resolve_binary(Dependency::Deno, settings).filter(|b| b.path.exists()) {
+        return Some((JsRuntime::Deno, deno));
+    }
+
+    if settings.resolution_policy == ResolutionPolicy::BundledOnly {
+        return None;
+    }
+
+    [JsRuntime::Node, JsRuntime::QuickJs].into_iter().find_map(|runtime| {
+        find_in_path(runtime.command_name())
+            .map(|path| (runtime, ResolvedBinary { path, source: DependencySource::System }))
+    })
 }
 
-/// Get the command for ffmpeg (local path if available, otherwise system)
-pub async fn get_ffmpeg_command() -> String {
-    if let Ok(path) = get_ffmpeg_path() {
-        if path.exists() {
-            return path.to_string_lossy().to_string();
+/// First yt-dlp release that understands `--js-runtimes`
+const JS_RUNTIMES_MIN_YTDLP: &str = "2025.11.12";
+
+/// Whether the resolved yt-dlp accepts `--js-runtimes`
+///
+/// Older builds reject unknown options, so the flag is only passed when the
+/// version is new enough. The answer is cached per binary and modification
+/// time because starting yt-dlp just to read its version is slow.
+pub async fn ytdlp_supports_js_runtimes(settings: &Settings) -> bool {
+    static CACHE: std::sync::Mutex<Option<(PathBuf, Option<std::time::SystemTime>, bool)>> =
+        std::sync::Mutex::new(None);
+
+    let Some(binary) = resolve_binary(Dependency::Ytdlp, settings) else {
+        return false;
+    };
+    let modified = std::fs::metadata(&binary.path).and_then(|m| m.modified()).ok();
+
+    if let Some((path, mtime, supported)) = CACHE.lock().unwrap().as_ref() {
+        if *path == binary.path && *mtime == modified {
+            return *supported;
         }
     }
-    "ffmpeg".to_string()
+
+    let supported = run_version(binary.path.as_os_str(), "--version")
+        .await
+        .as_deref()
+        .and_then(parse_version_output)
+        .is_some_and(|version| !is_newer_version(JS_RUNTIMES_MIN_YTDLP, &version));
+
+    *CACHE.lock().unwrap() = Some((binary.path, modified, supported));
+    supported
+}
+
+/// Check if the yt-dlp `settings` resolve to is available
+pub async fn is_ytdlp_available(settings: &Settings) -> bool {
+    detect_dependency(Dependency::Ytdlp, settings).await.installed
+}
+
+/// Check if the ffmpeg `settings` resolve to is available
+pub async fn is_ffmpeg_available(settings: &Settings) -> bool {
+    detect_dependency(Dependency::Ffmpeg, settings).await.installed
+}
+
+/// Check if the ffprobe `settings` resolve to is available
+pub async fn is_ffprobe_available(settings: &Settings) -> bool {
+    detect_dependency(Dependency::Ffprobe, settings).await.installed
+}
+
+/// Check if the aria2c `settings` resolve to is available
+pub async fn is_aria2c_available(settings: &Settings) -> bool {
+    detect_dependency(Dependency::Aria2c, settings).await.installed
+}
+
+/// Get the command for yt-dlp according to the resolution policy
+pub async fn get_ytdlp_command(settings: &Settings) -> String {
+    resolve_command(Dependency::Ytdlp, settings)
+}
+
+/// Get the command for ffmpeg according to the resolution policy
+pub async fn get_ffmpeg_command(settings: &Settings) -> String {
+    resolve_command(Dependency::Ffmpeg, settings)
+}
+
+/// Get the command for ffprobe according to the resolution policy
+pub async fn get_ffprobe_command(settings: &Settings) -> String {
+    resolve_command(Dependency::Ffprobe, settings)
+}
+
+/// Get the command for aria2c according to the resolution policy
+pub async fn get_aria2c_command(settings: &Settings) -> String {
+    resolve_command(Dependency::Aria2c, settings)
+}
+
+/// Where a dependency binary was found
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencySource {
+    /// Installed by DLCut into `DLCut/bin`
+    Local,
+    /// Found on the system PATH
+    System,
+    /// Pinned to an explicit path in settings
+    Custom,
+}
+
+/// Installed version and origin of a single dependency
+#[derive(serde::Serialize, Clone, Debug, Default)]
+pub struct DependencyInfo {
+    pub installed: bool,
+    /// Version reported by the binary itself, if it could be parsed
+    pub version: Option<String>,
+    pub source: Option<DependencySource>,
+    /// Resolved binary path, present even when it failed to run
+    pub path: Option<String>,
+}
+
+/// JavaScript runtime that will be passed to yt-dlp
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct JsRuntimeInfo {
+    pub runtime: JsRuntime,
+    /// Version reported by the runtime; QuickJS has no version flag
+    pub version: Option<String>,
+    pub source: DependencySource,
+    pub path: String,
 }
 
 /// Status of dependencies
@@ -127,26 +409,355 @@ pub async fn get_ffmpeg_command() -> String {
 pub struct DepsStatus {
     pub ytdlp_installed: bool,
     pub ffmpeg_installed: bool,
+    pub ffprobe_installed: bool,
+    /// aria2c is optional and does not affect `ready`
+    pub aria2c_installed: bool,
+    /// Without a JS runtime yt-dlp still works, but many YouTube formats are missing
+    pub js_runtime_installed: bool,
     pub ready: bool,
+    pub ytdlp: DependencyInfo,
+    pub ffmpeg: DependencyInfo,
+    pub ffprobe: DependencyInfo,
+    pub aria2c: DependencyInfo,
+    pub js_runtime: Option<JsRuntimeInfo>,
 }
 
 /// Check status of all dependencies
 pub async fn check_deps_status() -> DepsStatus {
-    let ytdlp = is_ytdlp_available().await;
-    let ffmpeg = is_ffmpeg_available().await;
+    let settings = settings::load();
+    let ytdlp = detect_dependency(Dependency::Ytdlp, &settings).await;
+    let ffmpeg = detect_dependency(Dependency::Ffmpeg, &settings).await;
+    let ffprobe = detect_dependency(Dependency::Ffprobe, &settings).await;
+    let aria2c = detect_dependency(Dependency::Aria2c, &settings).await;
+    let js_runtime = detect_js_runtime(&settings).await;
 
     DepsStatus {
-        ytdlp_installed: ytdlp,
-        ffmpeg_installed: ffmpeg,
-        ready: ytdlp && ffmpeg,
+        ytdlp_installed: ytdlp.installed,
+        ffmpeg_installed: ffmpeg.installed,
+        ffprobe_installed: ffprobe.installed,
+        aria2c_installed: aria2c.installed,
+        js_runtime_installed: js_runtime.is_some(),
+        ready: ytdlp.installed && ffmpeg.installed && ffprobe.installed,
+        ytdlp,
+        ffmpeg,
+        ffprobe,
+        aria2c,
+        js_runtime,
+    }
+}
+
+/// Resolve the JavaScript runtime and report its version
+async fn detect_js_runtime(settings: &Settings) -> Option<JsRuntimeInfo> {
+    let (runtime, resolved) = resolve_js_runtime(settings)?;
+
+    let version = match runtime {
+        JsRuntime::Deno | JsRuntime::Node => {
+            let output = run_version(resolved.path.as_os_str(), "--version").await?;
+            parse_version_output(&output)
+        }
+        JsRuntime::QuickJs => None,
+    };
+
+    Some(JsRuntimeInfo {
+        runtime,
+        version,
+        source: resolved.source,
+        path: resolved.path.to_string_lossy().to_string(),
+    })
+}
+
+/// Resolve a dependency the same way its `get_*_command` does and report its version
+async fn detect_dependency(dependency: Dependency, settings: &Settings) -> DependencyInfo {
+    let Some(resolved) = resolve_binary(dependency, settings) else {
+        return DependencyInfo::default();
+    };
+
+    let output = run_version(resolved.path.as_os_str(), dependency.version_arg()).await;
+
+    DependencyInfo {
+        installed: output.is_some(),
+        version: output.as_deref().and_then(parse_version_output),
+        source: Some(resolved.source),
+        path: Some(resolved.path.to_string_lossy().to_string()),
+    }
+}
+
+/// Outcome of checking a configured binary path
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct BinaryValidation {
+    pub dependency: Dependency,
+    pub path: String,
+    pub valid: bool,
+    pub version: Option<String>,
+    /// Why the binary was rejected
+    pub error: Option<String>,
+}
+
+/// Validate every binary path pinned in `settings`
+pub async fn validate_pinned_binaries(settings: &Settings) -> Vec<BinaryValidation> {
+    let mut results = Vec::new();
+
+    for dependency in [
+        Dependency::Ytdlp,
+        Dependency::Ffmpeg,
+        Dependency::Ffprobe,
+        Dependency::Aria2c,
+        Dependency::Deno,
+    ] {
+        if let Some(path) = dependency.pinned_path(settings) {
+            results.push(validate_binary(dependency, Path::new(path)).await);
+        }
+    }
+
+    results
+}
+
+/// Check that `path` exists, runs, and is actually the expected tool
+pub async fn validate_binary(dependency: Dependency, path: &Path) -> BinaryValidation {
+    let rejected = |error: String| BinaryValidation {
+        dependency,
+        path: path.to_string_lossy().to_string(),
+        valid: false,
+        version: None,
+        error: Some(error),
+    };
+
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return rejected("File does not exist".into()),
+    };
+    if metadata.is_dir() {
+        return rejected("Path is a directory, not an executable".into());
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return rejected("File is not executable".into());
+        }
+    }
+
+    let mut cmd = Command::new(path);
+    cmd.arg(dependency.version_arg());
+
+    #[cfg(windows)]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = match cmd.output().await {
+        Ok(output) => output,
+        Err(e) => return rejected(format!("Failed to run: {}", e)),
+    };
+    if !output.status.success() {
+        return rejected(format!(
+            "`{}` exited with {}",
+            dependency.version_arg(),
+            output.status
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !dependency.matches_version_output(&stdout) {
+        return rejected(format!(
+            "Does not look like {} (reported: {})",
+            dependency.command_name(),
+            stdout.lines().next().unwrap_or("").trim()
+        ));
+    }
+
+    BinaryValidation {
+        dependency,
+        path: path.to_string_lossy().to_string(),
+        valid: true,
+        version: parse_version_output(&stdout),
+        error: None,
+    }
+}
+
+/// Run a binary's version flag, returning stdout if it exited successfully
+async fn run_version(program: &OsStr, version_arg: &str) -> Option<String> {
+    let mut cmd = Command::new(program);
+    cmd.arg(version_arg);
+
+    #[cfg(windows)]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let output = cmd.output().await.ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Extract the version from `--version`/`-version` output
+///
+/// yt-dlp and node print just the version, ffmpeg and aria2c print
+/// `<name> version <version> ...` and deno prints `deno <version> (...)`
+/// on the first line.
+fn parse_version_output(output: &str) -> Option<String> {
+    let first_line = output.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let mut words = first_line.split_whitespace();
+
+    if first_line.contains(" version ") {
+        words.find(|w| *w == "version")?;
+        return words.next().map(str::to_string);
     }
+
+    let first = words.next()?;
+    match words.next() {
+        Some(second) if first.chars().all(|c| c.is_ascii_alphabetic()) => Some(second.to_string()),
+        _ => Some(first.to_string()),
+    }
+}
+
+/// Result of comparing an installed dependency with its latest release
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct UpdateInfo {
+    /// Version currently resolved, if any
+    pub current: Option<String>,
+    pub source: Option<DependencySource>,
+    /// Latest release on the selected channel
+    pub latest: String,
+    pub channel: UpdateChannel,
+    pub update_available: bool,
+    /// Whether the resolved binary is DLCut's own copy, the only one an
+    /// update replaces; pinned and system binaries are updated outside DLCut
+    pub managed: bool,
+}
+
+/// Available dependency updates
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct DependencyUpdates {
+    pub ytdlp: UpdateInfo,
+}
+
+/// Compare the dependencies `settings` resolve to with their latest
+/// published releases
+///
+/// An update is only offered for binaries DLCut manages; a newer release of
+/// a pinned or system-wide yt-dlp is still reported as `latest`.
+pub async fn check_dependency_updates(settings: &Settings) -> Result<DependencyUpdates> {
+    let channel = settings.ytdlp_channel;
+    let installed = detect_dependency(Dependency::Ytdlp, settings).await;
+    let latest = fetch_latest_release_tag(channel.repo()).await?;
+
+    let managed = uses_local_copy(Dependency::Ytdlp, settings);
+    let update_available = managed
+        && installed
+            .version
+            .as_deref()
+            .is_none_or(|current| is_newer_version(&latest, current));
+
+    Ok(DependencyUpdates {
+        ytdlp: UpdateInfo {
+            current: installed.version,
+            source: installed.source,
+            latest,
+            channel,
+            update_available,
+            managed,
+        },
+    })
+}
+
+/// Get the tag of the latest GitHub release of `repo`
+async fn fetch_latest_release_tag(repo: &str) -> Result<String> {
+    #[derive(serde::Deserialize)]
+    struct Release {
+        tag_name: String,
+    }
+
+    // The GitHub API rejects requests without a User-Agent
+    let client = reqwest::Client::builder()
+        .user_agent("DLCut")
+        .build()
+        .map_err(|e| AppError::DependencyError(format!("Failed to create HTTP client: {}", e)))?;
+
+    let response = client
+        .get(mirrored_url(&format!("https://api.github.com/repos/{}/releases/latest", repo)))
+        .send()
+        .await
+        .map_err(|e| AppError::DependencyError(format!("Failed to check for updates: {}", e)))?;
+
+    if !response.status().is_success() {
+        return Err(AppError::DependencyError(format!(
+            "Update check failed with status: {}", response.status()
+        )));
+    }
+
+    let body = response.text()
+        .await
+        .map_err(|e| AppError::DependencyError(format!("Failed to read release info: {}", e)))?;
+    let release: Release = serde_json::from_str(&body)
+        .map_err(|e| AppError::DependencyError(format!("Failed to parse release info: {}", e)))?;
+
+    Ok(release.tag_name)
+}
+
+/// Whether dotted version `latest` is newer than `current`
+///
+/// yt-dlp uses `YYYY.MM.DD` for stable and `YYYY.MM.DD.HHMMSS` for
+/// nightly/master builds, so comparing numeric components in order works
+/// across channels.
+fn is_newer_version(latest: &str, current: &str) -> bool {
+    let parse = |v: &str| -> Vec<u64> {
+        v.trim_start_matches('v')
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|part| !part.is_empty())
+            .filter_map(|part| part.parse().ok())
+            .collect()
+    };
+
+    parse(latest) > parse(current)
+}
+
+/// Update the local yt-dlp from the settings' channel, rolling back if the
+/// new binary misbehaves
+///
+/// The update lands in `DLCut/bin`, so it is refused while `settings` resolve
+/// yt-dlp to a pinned path, or to a system-wide yt-dlp under the system-first
+/// policy: replacing the local copy would change nothing. Returns the newly
+/// installed version.
+pub async fn update_ytdlp<F>(settings: &Settings, on_progress: F) -> Result<String>
+where
+    F: Fn(&str, f64) + Send + Sync,
+{
+    if !uses_local_copy(Dependency::Ytdlp, settings) {
+        let in_use = resolve_command(Dependency::Ytdlp, settings);
+        return Err(AppError::DependencyError(format!(
+            "yt-dlp is used from {}, which DLCut doesn't update; update it there or change the yt-dlp binary settings",
+            in_use
+        )));
+    }
+
+    // download_ytdlp reports 0-50% as part of the full install
+    download_ytdlp(settings.ytdlp_channel, |message, progress| on_progress(message, progress * 2.0)).await?;
+
+    let target_path = get_ytdlp_path()?;
+    if let Some(version) = run_version(target_path.as_os_str(), "--version")
+        .await
+        .as_deref()
+        .and_then(parse_version_output)
+    {
+        return Ok(version);
+    }
+
+    // Smoke test failed: restore the previous binary, or remove a broken first install
+    if rollback_binary(&target_path).is_err() {
+        let _ = tokio::fs::remove_file(&target_path).await;
+        return Err(AppError::DependencyError("Updated yt-dlp failed to run".into()));
+    }
+
+    Err(AppError::DependencyError(
+        "Updated yt-dlp failed to run, previous version restored".into(),
+    ))
 }
 
 /// Progress callback type
 pub type ProgressCallback = Box<dyn Fn(&str, f64) + Send + Sync>;
 
-/// Download yt-dlp
-pub async fn download_ytdlp<F>(on_progress: F) -> Result<()>
+/// Download yt-dlp from the given release channel
+pub async fn download_ytdlp<F>(channel: UpdateChannel, on_progress: F) -> Result<()>
 where
     F: Fn(&str, f64) + Send + Sync,
 {
@@ -155,36 +766,50 @@ where
         .map_err(|e| AppError::DependencyError(format!("Failed to create deps directory: {}", e)))?;
 
     #[cfg(windows)]
-    let url = "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp.exe";
+    let asset = "yt-dlp.exe";
     #[cfg(target_os = "linux")]
-    let url = "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp";
+    let asset = "yt-dlp";
+
+    let release_url = format!("https://github.com/{}/releases/latest/download", channel.repo());
+    let url = format!("{}/{}", release_url, asset);
+    let sums_url = format!("{}/SHA2-256SUMS", release_url);
 
     let target_path = get_ytdlp_path()?;
+    let temp_path = temp_path_for(&target_path);
 
     on_progress("Downloading yt-dlp...", 0.0);
 
-    download_file(url, &target_path, |progress| {
+    download_file(&url, &temp_path, |progress| {
         on_progress("Downloading yt-dlp...", progress * 0.5); // 0-50%
     }).await?;
 
-    // Make executable on Unix
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = tokio::fs::metadata(&target_path).await
-            .map_err(|e| AppError::DependencyError(format!("Failed to get permissions: {}", e)))?
-            .permissions();
-        perms.set_mode(0o755);
-        tokio::fs::set_permissions(&target_path, perms).await
-            .map_err(|e| AppError::DependencyError(format!("Failed to set permissions: {}", e)))?;
+    on_progress("Verifying yt-dlp...", 50.0);
+
+    let result = async {
+        let expected = fetch_checksum(&sums_url, asset, ChecksumKind::Sha256).await?;
+        verify_checksum(&temp_path, &expected)?;
+        make_executable(&temp_path).await?;
+
+        if !check_binary(&temp_path, "--version").await {
+            return Err(AppError::DependencyError("Downloaded yt-dlp failed to run".into()));
+        }
+
+        replace_binary(&temp_path, &target_path)
+    }.await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
     }
+    result?;
 
     on_progress("yt-dlp ready!", 50.0);
 
     Ok(())
 }
 
-/// Download ffmpeg
+/// Download ffmpeg and ffprobe
+///
+/// Both binaries come from the same build archive and are installed in one pass.
 pub async fn download_ffmpeg<F>(on_progress: F) -> Result<()>
 where
     F: Fn(&str, f64) + Send + Sync,
@@ -195,55 +820,426 @@ where
 
     on_progress("Downloading ffmpeg...", 50.0);
 
+    let staging_dir = deps_dir.join("ffmpeg-staging");
+    let _ = tokio::fs::remove_dir_all(&staging_dir).await;
+    tokio::fs::create_dir_all(&staging_dir).await
+        .map_err(|e| AppError::DependencyError(format!("Failed to create staging directory: {}", e)))?;
+
     #[cfg(windows)]
-    {
+    let result = {
         // Download ffmpeg zip for Windows
-        let url = "https://github.com/BtbN/FFmpeg-Builds/releases/download/latest/ffmpeg-master-latest-win64-gpl.zip";
+        let asset = "ffmpeg-master-latest-win64-gpl.zip";
+        let url = format!("https://github.com/BtbN/FFmpeg-Builds/releases/download/latest/{}", asset);
+        let sums_url = "https://github.com/BtbN/FFmpeg-Builds/releases/download/latest/checksums.sha256";
         let zip_path = deps_dir.join("ffmpeg.zip");
 
-        download_file(url, &zip_path, |progress| {
-            on_progress("Downloading ffmpeg...", 50.0 + progress * 0.4); // 50-90%
-        }).await?;
+        let result = async {
+            download_file(&url, &zip_path, |progress| {
+                on_progress("Downloading ffmpeg...", 50.0 + progress * 0.4); // 50-90%
+            }).await?;
 
-        on_progress("Extracting ffmpeg...", 90.0);
+            on_progress("Verifying ffmpeg...", 90.0);
 
-        // Extract ffmpeg.exe from zip
-        extract_ffmpeg_from_zip(&zip_path, &deps_dir).await?;
+            let expected = fetch_checksum(sums_url, asset, ChecksumKind::Sha256).await?;
+            verify_checksum(&zip_path, &expected)?;
+
+            on_progress("Extracting ffmpeg...", 95.0);
+
+            // Extract ffmpeg.exe and ffprobe.exe from zip
+            extract_ffmpeg_from_zip(&zip_path, &staging_dir)?;
+            install_staged_ffmpeg(&staging_dir, &deps_dir).await
+        }.await;
 
         // Clean up zip
         let _ = tokio::fs::remove_file(&zip_path).await;
-    }
+        result
+    };
 
     #[cfg(target_os = "linux")]
-    {
+    let result = {
         // For Linux, download static build from John Van Sickle
         let url = "https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-amd64-static.tar.xz";
+        // This host only publishes MD5 sums alongside each build
+        let sums_url = "https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-amd64-static.tar.xz.md5";
         let archive_path = deps_dir.join("ffmpeg.tar.xz");
 
-        download_file(url, &archive_path, |progress| {
-            on_progress("Downloading ffmpeg...", 50.0 + progress * 0.4);
-        }).await?;
+        let result = async {
+            download_file(url, &archive_path, |progress| {
+                on_progress("Downloading ffmpeg...", 50.0 + progress * 0.4);
+            }).await?;
+
+            on_progress("Verifying ffmpeg...", 90.0);
 
-        on_progress("Extracting ffmpeg...", 90.0);
+            let expected = fetch_checksum(
+                sums_url,
+                "ffmpeg-release-amd64-static.tar.xz",
+                ChecksumKind::Md5,
+            ).await?;
+            verify_checksum(&archive_path, &expected)?;
 
-        // Extract using tar command (simpler than implementing tar.xz in Rust)
-        extract_ffmpeg_linux(&archive_path, &deps_dir).await?;
+            on_progress("Extracting ffmpeg...", 95.0);
+
+            // Extract using tar command (simpler than implementing tar.xz in Rust)
+            extract_ffmpeg_linux(&archive_path, &staging_dir).await?;
+            install_staged_ffmpeg(&staging_dir, &deps_dir).await
+        }.await;
 
         let _ = tokio::fs::remove_file(&archive_path).await;
-    }
+        result
+    };
+
+    let _ = tokio::fs::remove_dir_all(&staging_dir).await;
+    result?;
 
     on_progress("ffmpeg ready!", 100.0);
 
     Ok(())
 }
 
+/// Smoke-test the extracted ffmpeg and ffprobe, then move both into `deps_dir`
+async fn install_staged_ffmpeg(staging_dir: &Path, deps_dir: &Path) -> Result<()> {
+    let targets = [deps_dir.join(exe_name("ffmpeg")), deps_dir.join(exe_name("ffprobe"))];
+
+    // Check both before replacing either so a bad archive leaves a matched pair
+    for target in &targets {
+        let staged = staging_dir.join(target.file_name().unwrap_or_default());
+        if !staged.exists() {
+            return Err(AppError::DependencyError(format!(
+                "{} not found in archive",
+                staged.file_name().unwrap_or_default().to_string_lossy()
+            )));
+        }
+        make_executable(&staged).await?;
+        if !check_binary(&staged, "-version").await {
+            return Err(AppError::DependencyError(format!(
+                "Downloaded {} failed to run",
+                staged.file_name().unwrap_or_default().to_string_lossy()
+            )));
+        }
+    }
+
+    for target in &targets {
+        let staged = staging_dir.join(target.file_name().unwrap_or_default());
+        replace_binary(&staged, target)?;
+    }
+
+    Ok(())
+}
+
+/// Download aria2c
+pub async fn download_aria2c<F>(on_progress: F) -> Result<()>
+where
+    F: Fn(&str, f64) + Send + Sync,
+{
+    let deps_dir = get_deps_dir()?;
+    tokio::fs::create_dir_all(&deps_dir).await
+        .map_err(|e| AppError::DependencyError(format!("Failed to create deps directory: {}", e)))?;
+
+    on_progress("Downloading aria2c...", 0.0);
+
+    #[cfg(windows)]
+    let (url, entry) = (
+        "https://github.com/aria2/aria2/releases/download/release-1.37.0/aria2-1.37.0-win-64bit-build1.zip",
+        "aria2c.exe",
+    );
+    #[cfg(target_os = "linux")]
+    let (url, entry) = (
+        // Upstream only ships Windows binaries; use a fully static musl build on Linux
+        "https://github.com/abcfy2/aria2-static-build/releases/latest/download/aria2-x86_64-linux-musl_static.zip",
+        "aria2c",
+    );
+
+    let zip_path = deps_dir.join("aria2c.zip");
+
+    download_file(url, &zip_path, |progress| {
+        on_progress("Downloading aria2c...", progress * 0.9); // 0-90%
+    }).await?;
+
+    on_progress("Extracting aria2c...", 90.0);
+
+    // Neither source publishes checksums, but the binary is still staged
+    // and smoke-tested before it replaces a working copy
+    let target_path = get_aria2c_path()?;
+    let temp_path = temp_path_for(&target_path);
+
+    let result = async {
+        extract_from_zip(&zip_path, entry, &temp_path)?;
+        make_executable(&temp_path).await?;
+
+        if !check_binary(&temp_path, "--version").await {
+            return Err(AppError::DependencyError("Installed aria2c failed to run".into()));
+        }
+
+        replace_binary(&temp_path, &target_path)
+    }.await;
+
+    let _ = tokio::fs::remove_file(&zip_path).await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+    }
+    result?;
+
+    on_progress("aria2c ready!", 100.0);
+
+    Ok(())
+}
+
+/// Download a self-contained Deno for yt-dlp's JavaScript challenges
+pub async fn download_deno<F>(on_progress: F) -> Result<()>
+where
+    F: Fn(&str, f64) + Send + Sync,
+{
+    let deps_dir = get_deps_dir()?;
+    tokio::fs::create_dir_all(&deps_dir).await
+        .map_err(|e| AppError::DependencyError(format!("Failed to create deps directory: {}", e)))?;
+
+    on_progress("Downloading Deno...", 0.0);
+
+    #[cfg(windows)]
+    let asset = "deno-x86_64-pc-windows-msvc.zip";
+    #[cfg(target_os = "linux")]
+    let asset = "deno-x86_64-unknown-linux-gnu.zip";
+
+    let url = format!("https://github.com/denoland/deno/releases/latest/download/{}", asset);
+    let sums_url = format!("{}.sha256sum", url);
+    let zip_path = deps_dir.join(asset);
+
+    download_file(&url, &zip_path, |progress| {
+        on_progress("Downloading Deno...", progress * 0.9); // 0-90%
+    }).await?;
+
+    on_progress("Extracting Deno...", 90.0);
+
+    let target_path = get_deno_path()?;
+    let temp_path = temp_path_for(&target_path);
+
+    let result = async {
+        let expected = fetch_checksum(&sums_url, asset, ChecksumKind::Sha256).await?;
+        verify_checksum(&zip_path, &expected)?;
+
+        extract_from_zip(&zip_path, &exe_name("deno"), &temp_path)?;
+        make_executable(&temp_path).await?;
+
+        if !check_binary(&temp_path, "--version").await {
+            return Err(AppError::DependencyError("Installed Deno failed to run".into()));
+        }
+
+        replace_binary(&temp_path, &target_path)
+    }.await;
+
+    let _ = tokio::fs::remove_file(&zip_path).await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+    }
+    result?;
+
+    on_progress("Deno ready!", 100.0);
+
+    Ok(())
+}
+
+/// Route a public release URL through the configured mirror, if any
+fn mirrored_url(url: &str) -> String {
+    apply_mirror(url, settings::load().mirror_url.as_deref())
+}
+
+/// Rewrite `url` to `<mirror>/<host>/<path>`, or return it unchanged without a mirror
+fn apply_mirror(url: &str, mirror: Option<&str>) -> String {
+    match mirror {
+        Some(mirror) => {
+            let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+            format!("{}/{}", mirror.trim_end_matches('/'), without_scheme)
+        }
+        None => url.to_string(),
+    }
+}
+
+/// Platform file name for an executable
+fn exe_name(name: &str) -> String {
+    if cfg!(windows) {
+        format!("{}.exe", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Hash algorithm used by a published checksum file
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChecksumKind {
+    Sha256,
+    Md5,
+}
+
+/// Expected digest of a downloaded artifact
+#[derive(Debug, Clone, PartialEq)]
+struct Checksum {
+    kind: ChecksumKind,
+    /// Lowercase hex digest
+    digest: String,
+}
+
+/// Fetch a published checksum file and pick out the entry for `file_name`
+async fn fetch_checksum(sums_url: &str, file_name: &str, kind: ChecksumKind) -> Result<Checksum> {
+    let response = reqwest::get(mirrored_url(sums_url))
+        .await
+        .map_err(|e| AppError::DependencyError(format!("Failed to fetch checksums: {}", e)))?;
+
+    if !response.status().is_success() {
+        return Err(AppError::DependencyError(format!(
+            "Failed to fetch checksums with status: {}", response.status()
+        )));
+    }
+
+    let contents = response.text()
+        .await
+        .map_err(|e| AppError::DependencyError(format!("Failed to read checksums: {}", e)))?;
+
+    let digest = parse_checksum_file(&contents, file_name).ok_or_else(|| {
+        AppError::DependencyError(format!("No published checksum for {}", file_name))
+    })?;
+
+    Ok(Checksum { kind, digest })
+}
+
+/// Find the digest for `file_name` in a `sha256sum`/`md5sum` style listing
+///
+/// Lines look like `<hex>  <name>` or `<hex> *<name>` (binary mode). Files
+/// covering a single artifact (such as Deno's PowerShell `Get-FileHash`
+/// output) may not name it, so a lone digest is accepted as well.
+fn parse_checksum_file(contents: &str, file_name: &str) -> Option<String> {
+    let is_hex = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit());
+
+    let named = contents.lines().find_map(|line| {
+        let (digest, name) = line.trim().split_once(char::is_whitespace)?;
+        let name = name.trim_start().trim_start_matches('*');
+
+        (is_hex(digest) && name == file_name).then(|| digest.to_ascii_lowercase())
+    });
+    if named.is_some() {
+        return named;
+    }
+
+    let mut digests = contents
+        .split_whitespace()
+        .filter(|token| matches!(token.len(), 32 | 64) && is_hex(token));
+    match (digests.next(), digests.next()) {
+        (Some(digest), None) => Some(digest.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+/// Hash a file on disk and compare it against the expected digest
+fn verify_checksum(path: &Path, expected: &Checksum) -> Result<()> {
+    let actual = match expected.kind {
+        ChecksumKind::Sha256 => hash_file::<sha2::Sha256>(path)?,
+        ChecksumKind::Md5 => hash_file::<md5::Md5>(path)?,
+    };
+
+    if actual != expected.digest {
+        return Err(AppError::DependencyError(format!(
+            "Checksum mismatch for {}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Stream a file through a digest and return the lowercase hex result
+fn hash_file<D: sha2::Digest>(path: &Path) -> Result<String> {
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| AppError::DependencyError(format!("Failed to open file for hashing: {}", e)))?;
+
+    let mut hasher = D::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)
+            .map_err(|e| AppError::DependencyError(format!("Failed to read file for hashing: {}", e)))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Staging path a new binary is written to before it replaces `target`
+fn temp_path_for(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".download");
+    target.with_file_name(name)
+}
+
+/// Path the previous binary is kept at for rollback
+pub fn backup_path_for(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".old");
+    target.with_file_name(name)
+}
+
+/// Move a verified binary over `target`, keeping the previous one as `<name>.old`
+fn replace_binary(new: &Path, target: &Path) -> Result<()> {
+    let backup = backup_path_for(target);
+
+    if target.exists() {
+        let _ = std::fs::remove_file(&backup);
+
+        // A running executable can be renamed but not overwritten on Windows
+        #[cfg(windows)]
+        std::fs::rename(target, &backup)
+            .map_err(|e| AppError::DependencyError(format!("Failed to back up binary: {}", e)))?;
+
+        // Elsewhere keep the old binary in place so the swap below is a single atomic rename
+        #[cfg(not(windows))]
+        std::fs::hard_link(target, &backup)
+            .or_else(|_| std::fs::copy(target, &backup).map(|_| ()))
+            .map_err(|e| AppError::DependencyError(format!("Failed to back up binary: {}", e)))?;
+    }
+
+    std::fs::rename(new, target)
+        .map_err(|e| AppError::DependencyError(format!("Failed to install binary: {}", e)))
+}
+
+/// Restore the binary kept by the last `replace_binary` call
+pub fn rollback_binary(target: &Path) -> Result<()> {
+    let backup = backup_path_for(target);
+    if !backup.exists() {
+        return Err(AppError::DependencyError("No previous version to roll back to".into()));
+    }
+
+    std::fs::rename(&backup, target)
+        .map_err(|e| AppError::DependencyError(format!("Failed to roll back binary: {}", e)))
+}
+
+/// Mark a binary as executable (no-op on Windows)
+async fn make_executable(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = tokio::fs::metadata(path).await
+            .map_err(|e| AppError::DependencyError(format!("Failed to get permissions: {}", e)))?
+            .permissions();
+        perms.set_mode(0o755);
+        tokio::fs::set_permissions(path, perms).await
+            .map_err(|e| AppError::DependencyError(format!("Failed to set permissions: {}", e)))?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = path;
+
+    Ok(())
+}
+
 /// Download a file with progress reporting
-async fn download_file<F>(url: &str, target: &PathBuf, on_progress: F) -> Result<()>
+async fn download_file<F>(url: &str, target: &Path, on_progress: F) -> Result<()>
 where
     F: Fn(f64),
 {
     let client = reqwest::Client::new();
-    let response = client.get(url)
+    let response = client.get(mirrored_url(url))
         .send()
         .await
         .map_err(|e| AppError::DependencyError(format!("Download failed: {}", e)))?;
@@ -279,9 +1275,17 @@ where
     Ok(())
 }
 
-/// Extract ffmpeg.exe from the BtbN zip (Windows)
-#[cfg(windows)]
-async fn extract_ffmpeg_from_zip(zip_path: &PathBuf, target_dir: &PathBuf) -> Result<()> {
+/// Extract ffmpeg and ffprobe from a zip build such as BtbN's
+fn extract_ffmpeg_from_zip(zip_path: &Path, target_dir: &Path) -> Result<()> {
+    // Both usually live in a versioned `bin` subdirectory
+    for name in [exe_name("ffmpeg"), exe_name("ffprobe")] {
+        extract_from_zip(zip_path, &name, &target_dir.join(&name))?;
+    }
+    Ok(())
+}
+
+/// Extract the first zip entry named `entry_suffix`, or ending in `/<entry_suffix>`, to `target_path`
+fn extract_from_zip(zip_path: &Path, entry_suffix: &str, target_path: &Path) -> Result<()> {
     use std::io::Read;
 
     let file = std::fs::File::open(zip_path)
@@ -290,46 +1294,40 @@ async fn extract_ffmpeg_from_zip(zip_path: &PathBuf, target_dir: &PathBuf) -> Re
     let mut archive = zip::ZipArchive::new(file)
         .map_err(|e| AppError::DependencyError(format!("Failed to read zip: {}", e)))?;
 
-    // Find ffmpeg.exe in the archive (it's in a subdirectory)
     for i in 0..archive.len() {
         let mut file = archive.by_index(i)
             .map_err(|e| AppError::DependencyError(format!("Failed to read zip entry: {}", e)))?;
 
         let name = file.name().to_string();
 
-        if name.ends_with("bin/ffmpeg.exe") {
-            let target_path = target_dir.join("ffmpeg.exe");
-            let mut outfile = std::fs::File::create(&target_path)
-                .map_err(|e| AppError::DependencyError(format!("Failed to create ffmpeg.exe: {}", e)))?;
+        if name == entry_suffix || name.ends_with(&format!("/{}", entry_suffix)) {
+            let mut outfile = std::fs::File::create(target_path)
+                .map_err(|e| AppError::DependencyError(format!("Failed to create {}: {}", entry_suffix, e)))?;
 
             let mut contents = Vec::new();
             file.read_to_end(&mut contents)
-                .map_err(|e| AppError::DependencyError(format!("Failed to read ffmpeg.exe: {}", e)))?;
+                .map_err(|e| AppError::DependencyError(format!("Failed to read {}: {}", entry_suffix, e)))?;
 
             outfile.write_all(&contents)
-                .map_err(|e| AppError::DependencyError(format!("Failed to write ffmpeg.exe: {}", e)))?;
+                .map_err(|e| AppError::DependencyError(format!("Failed to write {}: {}", entry_suffix, e)))?;
 
             return Ok(());
         }
     }
 
-    Err(AppError::DependencyError("ffmpeg.exe not found in archive".into()))
+    Err(AppError::DependencyError(format!("{} not found in archive", entry_suffix)))
 }
 
-/// Extract ffmpeg from tar.xz (Linux)
+/// Extract ffmpeg and ffprobe from tar.xz (Linux)
 #[cfg(target_os = "linux")]
-async fn extract_ffmpeg_linux(archive_path: &PathBuf, target_dir: &PathBuf) -> Result<()> {
+async fn extract_ffmpeg_linux(archive_path: &Path, target_dir: &Path) -> Result<()> {
     // Use tar command to extract
     let output = Command::new("tar")
-        .args([
-            "-xf",
-            archive_path.to_str().unwrap(),
-            "-C",
-            target_dir.to_str().unwrap(),
-            "--wildcards",
-            "*/ffmpeg",
-            "--strip-components=1",
-        ])
+        .arg("-xf")
+        .arg(archive_path)
+        .arg("-C")
+        .arg(target_dir)
+        .args(["--wildcards", "*/ffmpeg", "*/ffprobe", "--strip-components=1"])
         .output()
         .await
         .map_err(|e| AppError::DependencyError(format!("Failed to extract: {}", e)))?;
@@ -338,21 +1336,98 @@ async fn extract_ffmpeg_linux(archive_path: &PathBuf, target_dir: &PathBuf) -> R
         return Err(AppError::DependencyError("Failed to extract ffmpeg".into()));
     }
 
-    // Make executable
-    use std::os::unix::fs::PermissionsExt;
-    let ffmpeg_path = target_dir.join("ffmpeg");
-    if ffmpeg_path.exists() {
-        let mut perms = tokio::fs::metadata(&ffmpeg_path).await
-            .map_err(|e| AppError::DependencyError(format!("Failed to get permissions: {}", e)))?
-            .permissions();
-        perms.set_mode(0o755);
-        tokio::fs::set_permissions(&ffmpeg_path, perms).await
-            .map_err(|e| AppError::DependencyError(format!("Failed to set permissions: {}", e)))?;
+    Ok(())
+}
+
+/// Install dependencies from local files instead of downloading them
+///
+/// `ytdlp_binary` is a yt-dlp executable and `ffmpeg_archive` an ffmpeg
+/// build (`.zip`, or `.tar.xz` on Linux) containing both ffmpeg and ffprobe.
+/// Nothing is fetched over the network, so this works on air-gapped machines.
+pub async fn install_dependencies_from_path<F>(
+    ytdlp_binary: Option<&Path>,
+    ffmpeg_archive: Option<&Path>,
+    on_progress: F,
+) -> Result<()>
+where
+    F: Fn(&str, f64) + Send + Sync,
+{
+    let deps_dir = get_deps_dir()?;
+    tokio::fs::create_dir_all(&deps_dir).await
+        .map_err(|e| AppError::DependencyError(format!("Failed to create deps directory: {}", e)))?;
+
+    if let Some(source) = ytdlp_binary {
+        on_progress("Installing yt-dlp...", 0.0);
+        install_ytdlp_binary(source, &deps_dir).await?;
+        on_progress("yt-dlp ready!", 50.0);
+    }
+
+    if let Some(archive) = ffmpeg_archive {
+        on_progress("Extracting ffmpeg...", 50.0);
+        install_ffmpeg_archive(archive, &deps_dir).await?;
+        on_progress("ffmpeg ready!", 100.0);
     }
 
     Ok(())
 }
 
+/// Copy a local yt-dlp executable into `deps_dir` after a smoke test
+async fn install_ytdlp_binary(source: &Path, deps_dir: &Path) -> Result<()> {
+    let target_path = deps_dir.join(exe_name("yt-dlp"));
+    let temp_path = temp_path_for(&target_path);
+
+    tokio::fs::copy(source, &temp_path).await
+        .map_err(|e| AppError::DependencyError(format!("Failed to copy yt-dlp: {}", e)))?;
+
+    let result = async {
+        make_executable(&temp_path).await?;
+
+        if !check_binary(&temp_path, "--version").await {
+            return Err(AppError::DependencyError("Provided yt-dlp failed to run".into()));
+        }
+
+        replace_binary(&temp_path, &target_path)
+    }.await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+    }
+    result
+}
+
+/// Extract ffmpeg and ffprobe from a local build archive into `deps_dir`
+async fn install_ffmpeg_archive(archive: &Path, deps_dir: &Path) -> Result<()> {
+    let staging_dir = deps_dir.join("ffmpeg-staging");
+    let _ = tokio::fs::remove_dir_all(&staging_dir).await;
+    tokio::fs::create_dir_all(&staging_dir).await
+        .map_err(|e| AppError::DependencyError(format!("Failed to create staging directory: {}", e)))?;
+
+    let result = async {
+        let name = archive.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+
+        if name.ends_with(".zip") {
+            extract_ffmpeg_from_zip(archive, &staging_dir)?;
+        } else if name.ends_with(".tar.xz") || name.ends_with(".txz") {
+            #[cfg(target_os = "linux")]
+            extract_ffmpeg_linux(archive, &staging_dir).await?;
+
+            #[cfg(not(target_os = "linux"))]
+            return Err(AppError::DependencyError(
+                "tar.xz archives are only supported on Linux, use a .zip build".into(),
+            ));
+        } else {
+            return Err(AppError::DependencyError(
+                "Unsupported ffmpeg archive, expected .zip or .tar.xz".into(),
+            ));
+        }
+
+        install_staged_ffmpeg(&staging_dir, deps_dir).await
+    }.await;
+
+    let _ = tokio::fs::remove_dir_all(&staging_dir).await;
+    result
+}
+
 /// Install all missing dependencies
 pub async fn install_dependencies<F>(on_progress: F) -> Result<()>
 where
@@ -361,12 +1436,349 @@ where
     let status = check_deps_status().await;
 
     if !status.ytdlp_installed {
-        download_ytdlp(on_progress.clone()).await?;
+        let channel = settings::load().ytdlp_channel;
+        download_ytdlp(channel, on_progress.clone()).await?;
     }
 
-    if !status.ffmpeg_installed {
+    // ffprobe ships in the same archive as ffmpeg
+    if !status.ffmpeg_installed || !status.ffprobe_installed {
         download_ffmpeg(on_progress).await?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+
+    #[test]
+    fn test_parse_version_output() {
+        assert_eq!(parse_version_output("2024.08.06\n").as_deref(), Some("2024.08.06"));
+        assert_eq!(
+            parse_version_output(
+                "ffmpeg version 6.1.1-static https://johnvansickle.com/ffmpeg/  Copyright (c) 2000-2023\nbuilt with gcc 8"
+            )
+            .as_deref(),
+            Some("6.1.1-static")
+        );
+        assert_eq!(
+            parse_version_output("\naria2 version 1.37.0\nCopyright (C) 2006, 2019 Tatsuhiro Tsujikawa")
+                .as_deref(),
+            Some("1.37.0")
+        );
+        assert_eq!(
+            parse_version_output("deno 2.1.4 (stable, release, x86_64-unknown-linux-gnu)\nv8 13.0")
+                .as_deref(),
+            Some("2.1.4")
+        );
+        assert_eq!(parse_version_output("v22.11.0\n").as_deref(), Some("v22.11.0"));
+        assert_eq!(parse_version_output(""), None);
+    }
+
+    #[test]
+    fn test_choose_binary() {
+        let dir = test_support::temp_dir("deps-resolve");
+        let local = dir.join("yt-dlp");
+        let system = PathBuf::from("/usr/bin/yt-dlp");
+        let pick = |pinned, policy| {
+            choose_binary(pinned, Some(local.clone()), Some(system.clone()), policy)
+                .map(|b| (b.path, b.source))
+        };
+
+        // Local binary not installed yet
+        assert_eq!(
+            pick(None, ResolutionPolicy::BundledFirst),
+            Some((system.clone(), DependencySource::System))
+        );
+        assert_eq!(
+            pick(None, ResolutionPolicy::BundledOnly),
+            Some((local.clone(), DependencySource::Local))
+        );
+
+        std::fs::write(&local, b"").unwrap();
+        assert_eq!(
+            pick(None, ResolutionPolicy::BundledFirst),
+            Some((local.clone(), DependencySource::Local))
+        );
+        assert_eq!(
+            pick(None, ResolutionPolicy::SystemFirst),
+            Some((system.clone(), DependencySource::System))
+        );
+        assert_eq!(
+            choose_binary(None, Some(local.clone()), None, ResolutionPolicy::SystemFirst)
+                .map(|b| b.source),
+            Some(DependencySource::Local)
+        );
+
+        // Pins win over every policy, even when the file is missing
+        assert_eq!(
+            pick(Some("/opt/patched/yt-dlp"), ResolutionPolicy::BundledOnly),
+            Some((PathBuf::from("/opt/patched/yt-dlp"), DependencySource::Custom))
+        );
+
+        assert_eq!(choose_binary(None, None, None, ResolutionPolicy::BundledFirst), None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_prefers_local() {
+        assert!(prefers_local(None, true, ResolutionPolicy::BundledFirst));
+        assert!(prefers_local(None, true, ResolutionPolicy::BundledOnly));
+        assert!(prefers_local(None, false, ResolutionPolicy::SystemFirst));
+
+        // A local copy would sit unused behind these
+        assert!(!prefers_local(None, true, ResolutionPolicy::SystemFirst));
+        assert!(!prefers_local(Some("/opt/yt-dlp"), false, ResolutionPolicy::BundledFirst));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_validate_binary_reports_reason() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = test_support::temp_dir("deps-validate");
+        let reason = |v: BinaryValidation| v.error.unwrap_or_default();
+
+        let missing = validate_binary(Dependency::Ffmpeg, &dir.join("missing")).await;
+        assert!(!missing.valid);
+        assert_eq!(reason(missing), "File does not exist");
+
+        assert!(reason(validate_binary(Dependency::Ffmpeg, &dir).await).contains("directory"));
+
+        let ffmpeg = dir.join("ffmpeg");
+        std::fs::write(&ffmpeg, fake_binary("ffmpeg version 7.0-fixture")).unwrap();
+        assert_eq!(
+            reason(validate_binary(Dependency::Ffmpeg, &ffmpeg).await),
+            "File is not executable"
+        );
+
+        std::fs::set_permissions(&ffmpeg, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let valid = validate_binary(Dependency::Ffmpeg, &ffmpeg).await;
+        assert!(valid.valid);
+        assert_eq!(valid.version.as_deref(), Some("7.0-fixture"));
+
+        // The right file name is not enough, it has to be the right tool
+        let wrong = validate_binary(Dependency::Ffprobe, &ffmpeg).await;
+        assert!(reason(wrong).starts_with("Does not look like ffprobe"));
+
+        let failing = dir.join("yt-dlp");
+        std::fs::write(&failing, b"#!/bin/sh\nexit 3\n").unwrap();
+        std::fs::set_permissions(&failing, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(reason(validate_binary(Dependency::Ytdlp, &failing).await).contains("exited with"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_is_newer_version() {
+        assert!(is_newer_version("2024.08.06", "2024.07.25"));
+        assert!(is_newer_version("2024.08.06.232850", "2024.08.06"));
+        assert!(is_newer_version("2025.01.02", "2024.12.31.120000"));
+        assert!(!is_newer_version("2024.08.06", "2024.08.06"));
+        assert!(!is_newer_version("2024.08.06", "2024.08.06.232850"));
+    }
+
+    #[test]
+    fn test_parse_checksum_file() {
+        let sums = "\
+            0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0  yt-dlp\n\
+            ABCDEF0123456789ABCDEF0123456789ABCDEF0123456789ABCDEF0123456789 *yt-dlp.exe\n\
+            1111111111111111111111111111111111111111111111111111111111111111  yt-dlp_macos\n";
+
+        assert_eq!(
+            parse_checksum_file(sums, "yt-dlp").as_deref(),
+            Some("0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0")
+        );
+        assert_eq!(
+            parse_checksum_file(sums, "yt-dlp.exe").as_deref(),
+            Some("abcdef0123456789abcdef0123456789abcdef0123456789abcdef0123456789")
+        );
+        assert_eq!(parse_checksum_file(sums, "yt-dlp_linux"), None);
+        assert_eq!(parse_checksum_file("not a checksum  yt-dlp", "yt-dlp"), None);
+    }
+
+    #[test]
+    fn test_parse_single_checksum() {
+        let digest = "0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0";
+        let powershell = format!(
+            "\r\nAlgorithm : SHA256\r\nHash      : {}\r\nPath      : D:\\a\\deno.zip\r\n",
+            digest.to_ascii_uppercase()
+        );
+
+        assert_eq!(
+            parse_checksum_file(&powershell, "deno-x86_64-pc-windows-msvc.zip").as_deref(),
+            Some(digest)
+        );
+        assert_eq!(
+            parse_checksum_file(&format!("{}  ./deno.zip\n", digest), "deno.zip").as_deref(),
+            Some(digest)
+        );
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let dir = test_support::temp_dir("deps-verify");
+        let path = dir.join("payload");
+        std::fs::write(&path, b"abc").unwrap();
+
+        let sha256 = Checksum {
+            kind: ChecksumKind::Sha256,
+            digest: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".into(),
+        };
+        assert!(verify_checksum(&path, &sha256).is_ok());
+
+        let md5 = Checksum {
+            kind: ChecksumKind::Md5,
+            digest: "900150983cd24fb0d6963f7d28e17f72".into(),
+        };
+        assert!(verify_checksum(&path, &md5).is_ok());
+
+        // A truncated download must not pass
+        std::fs::write(&path, b"ab").unwrap();
+        assert!(verify_checksum(&path, &sha256).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_extract_ffmpeg_linux_extracts_ffprobe() {
+        let dir = test_support::temp_dir("deps-extract");
+        let build = dir.join("ffmpeg-7.0.2-amd64-static");
+        std::fs::create_dir_all(&build).unwrap();
+        for name in ["ffmpeg", "ffprobe", "readme.txt"] {
+            std::fs::write(build.join(name), name).unwrap();
+        }
+
+        let archive = dir.join("ffmpeg.tar.xz");
+        let status = std::process::Command::new("tar")
+            .arg("-cJf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&dir)
+            .arg("ffmpeg-7.0.2-amd64-static")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let staging = dir.join("staging");
+        std::fs::create_dir_all(&staging).unwrap();
+        extract_ffmpeg_linux(&archive, &staging).await.unwrap();
+
+        assert_eq!(std::fs::read(staging.join("ffmpeg")).unwrap(), b"ffmpeg");
+        assert_eq!(std::fs::read(staging.join("ffprobe")).unwrap(), b"ffprobe");
+        assert!(!staging.join("readme.txt").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_apply_mirror() {
+        let url = "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp";
+        assert_eq!(apply_mirror(url, None), url);
+        assert_eq!(
+            apply_mirror(url, Some("http://mirror.internal/dlcut/")),
+            "http://mirror.internal/dlcut/github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp"
+        );
+    }
+
+    /// Write an executable shell script that prints `output` and exits successfully
+    #[cfg(unix)]
+    fn fake_binary(output: &str) -> Vec<u8> {
+        format!("#!/bin/sh\necho '{}'\n", output).into_bytes()
+    }
+
+    /// Build an ffmpeg-style zip fixture with the given `bin/` entries
+    #[cfg(unix)]
+    fn ffmpeg_zip_fixture(path: &Path, entries: &[(&str, Vec<u8>)]) {
+        use zip::write::SimpleFileOptions;
+
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        for (name, contents) in entries {
+            zip.start_file(format!("ffmpeg-n7.0-linux64-gpl/bin/{}", name), SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_install_from_local_files() {
+        let dir = test_support::temp_dir("deps-offline");
+        let deps_dir = dir.join("bin");
+        std::fs::create_dir_all(&deps_dir).unwrap();
+
+        let ytdlp = dir.join("yt-dlp-fixture");
+        std::fs::write(&ytdlp, fake_binary("2099.01.01")).unwrap();
+        install_ytdlp_binary(&ytdlp, &deps_dir).await.unwrap();
+        assert!(check_binary(&deps_dir.join("yt-dlp"), "--version").await);
+
+        let archive = dir.join("ffmpeg-fixture.zip");
+        ffmpeg_zip_fixture(
+            &archive,
+            &[
+                ("ffmpeg", fake_binary("ffmpeg version 7.0-fixture")),
+                ("ffprobe", fake_binary("ffprobe version 7.0-fixture")),
+            ],
+        );
+        install_ffmpeg_archive(&archive, &deps_dir).await.unwrap();
+        assert!(check_binary(&deps_dir.join("ffmpeg"), "-version").await);
+        assert!(check_binary(&deps_dir.join("ffprobe"), "-version").await);
+        assert!(!deps_dir.join("ffmpeg-staging").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_incomplete_archive_keeps_existing_install() {
+        let dir = test_support::temp_dir("deps-offline-bad");
+        let deps_dir = dir.join("bin");
+        std::fs::create_dir_all(&deps_dir).unwrap();
+        std::fs::write(deps_dir.join("ffmpeg"), b"existing").unwrap();
+
+        // An archive without ffprobe must not replace the working ffmpeg
+        let archive = dir.join("ffmpeg-fixture.zip");
+        ffmpeg_zip_fixture(&archive, &[("ffmpeg", fake_binary("ffmpeg version 7.0-fixture"))]);
+        assert!(install_ffmpeg_archive(&archive, &deps_dir).await.is_err());
+        assert_eq!(std::fs::read(deps_dir.join("ffmpeg")).unwrap(), b"existing");
+
+        let unsupported = dir.join("ffmpeg.7z");
+        std::fs::write(&unsupported, b"").unwrap();
+        assert!(install_ffmpeg_archive(&unsupported, &deps_dir).await.is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_replace_and_rollback_binary() {
+        let dir = test_support::temp_dir("deps-replace");
+        let target = dir.join("yt-dlp");
+        let staged = temp_path_for(&target);
+        assert_eq!(staged.file_name().unwrap(), "yt-dlp.download");
+
+        // First install has nothing to back up
+        std::fs::write(&staged, b"v1").unwrap();
+        replace_binary(&staged, &target).unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"v1");
+        assert!(!staged.exists());
+        assert!(!backup_path_for(&target).exists());
+
+        // Upgrade keeps the previous version
+        std::fs::write(&staged, b"v2").unwrap();
+        replace_binary(&staged, &target).unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"v2");
+        assert_eq!(std::fs::read(backup_path_for(&target)).unwrap(), b"v1");
+
+        rollback_binary(&target).unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"v1");
+        assert!(rollback_binary(&target).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
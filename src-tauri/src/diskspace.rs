@@ -0,0 +1,310 @@
+//! Disk space preflight
+//!
+//! Downloads and cuts are checked against the free space of the filesystem
+//! they write to before any process starts, so a full disk is reported up
+//! front instead of as a failed merge halfway through.
+//!
+//! Sizes are estimates: yt-dlp's file sizes (or bitrate × duration) scaled to
+//! the cut range, doubled for downloads because the separate streams and the
+//! merged or converted file exist side by side until yt-dlp cleans up.
+
+use crate::error::{AppError, Result};
+use crate::types::{DownloadMode, DownloadRequest, VideoInfo};
+use std::path::{Path, PathBuf};
+
+/// Space kept free on top of every estimate, for fragments, logs and
+/// estimates that come out low
+pub const SPACE_MARGIN: u64 = 64 * 1024 * 1024;
+
+/// What a job needs and what its filesystem had free when it was checked
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpaceCheck {
+    /// Directory the job writes to
+    pub dir: PathBuf,
+    /// Peak bytes the job is expected to use
+    pub needed: u64,
+    /// Free bytes, or `None` if the filesystem could not be queried
+    pub available: Option<u64>,
+}
+
+impl SpaceCheck {
+    /// Measure the free space for a job writing `needed` bytes into `dir`
+    pub fn measure(dir: &Path, needed: u64) -> Self {
+        let available = available_space(dir)
+            .inspect_err(|e| eprintln!("Could not check free space in {}: {}", dir.display(), e))
+            .ok();
+
+        Self {
+            dir: dir.to_path_buf(),
+            needed,
+            available,
+        }
+    }
+
+    /// Refuse the job if it does not fit next to `reserved` bytes held by
+    /// other jobs on the same filesystem. Unknown free space lets it through.
+    pub fn admit(&self, reserved: u64) -> Result<()> {
+        let Some(available) = self.available else {
+            return Ok(());
+        };
+
+        let available = available.saturating_sub(reserved);
+        if self.needed > available {
+            return Err(AppError::InsufficientSpace {
+                needed: self.needed,
+                available,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Directory a file is written to; a bare file name goes to the working
+/// directory
+pub fn output_dir(output_path: &str) -> PathBuf {
+    match Path::new(output_path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Estimated size of a download's output, if yt-dlp reported enough to tell
+pub fn download_size(info: &VideoInfo, request: &DownloadRequest) -> Option<u64> {
+    let full = match request.mode {
+        DownloadMode::VideoWithAudio => {
+            // yt-dlp picks the best format up to the requested height;
+            // qualities are sorted from the highest
+            let height = request.quality.parse::<u32>().ok()?;
+            info.video_qualities
+                .iter()
+                .find(|q| height == 0 || q.height <= height)
+                .or(info.video_qualities.last())?
+                .filesize?
+        }
+        DownloadMode::AudioOnly => {
+            let quality = info
+                .audio_qualities
+                .iter()
+                .find(|q| q.quality_id == request.quality)?;
+            if info.duration <= 0.0 {
+                return None;
+            }
+            (quality.bitrate as f64 * 1000.0 / 8.0 * info.duration) as u64
+        }
+    };
+
+    Some(scale_to_range(
+        full,
+        info.duration,
+        request.start_time,
+        request.end_time,
+    ))
+}
+
+/// Peak space a download uses: its streams plus the merged or converted
+/// output, with the margin. Unknown sizes only need the margin.
+pub fn download_needs(size: Option<u64>) -> u64 {
+    size.unwrap_or(0)
+        .saturating_mul(2)
+        .saturating_add(SPACE_MARGIN)
+}
+
+/// Peak space a stream-copy cut uses: the kept share of the input, with the
+/// margin
+pub fn cut_needs(input_size: u64, duration: f64, start: f64, end: f64) -> u64 {
+    scale_to_range(input_size, duration, Some(start), Some(end)).saturating_add(SPACE_MARGIN)
+}
+
+/// Share of `size` covered by the range, assuming a constant bitrate
+fn scale_to_range(size: u64, duration: f64, start: Option<f64>, end: Option<f64>) -> u64 {
+    if duration <= 0.0 || (start.is_none() && end.is_none()) {
+        return size;
+    }
+
+    let length = end.unwrap_or(duration).min(duration) - start.unwrap_or(0.0).max(0.0);
+    let share = (length / duration).clamp(0.0, 1.0);
+    (size as f64 * share).ceil() as u64
+}
+
+/// Whether two paths are on the same filesystem; unknown counts as the same,
+/// which errs towards reserving too much
+pub fn same_filesystem(a: &Path, b: &Path) -> bool {
+    match (filesystem_id(a), filesystem_id(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => true,
+    }
+}
+
+/// Nearest directory at or above `path` that exists, since output folders
+/// may only be created once the job runs
+fn existing_ancestor(path: &Path) -> &Path {
+    path.ancestors()
+        .find(|dir| !dir.as_os_str().is_empty() && dir.exists())
+        .unwrap_or(Path::new("."))
+}
+
+/// Free bytes available to the current user on the filesystem of `path`
+pub fn available_space(path: &Path) -> std::io::Result<u64> {
+    free_bytes(existing_ancestor(path))
+}
+
+#[cfg(unix)]
+#[allow(clippy::useless_conversion)] // the statvfs field types vary by platform
+fn free_bytes(dir: &Path) -> std::io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(dir.as_os_str().as_bytes())?;
+    // SAFETY: statvfs is plain data, so all zeroes is a valid value
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is a valid out pointer
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // f_bavail excludes blocks reserved for root
+    Ok(u64::from(stat.f_bavail).saturating_mul(u64::from(stat.f_frsize)))
+}
+
+#[cfg(windows)]
+fn free_bytes(dir: &Path) -> std::io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available = 0u64;
+    // SAFETY: `wide` is NUL-terminated; null pointers skip the totals we
+    // don't need
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // Quotas are already applied to the caller's figure
+    Ok(available)
+}
+
+#[cfg(unix)]
+fn filesystem_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    existing_ancestor(path).metadata().ok().map(|m| m.dev())
+}
+
+#[cfg(windows)]
+fn filesystem_id(path: &Path) -> Option<String> {
+    // The drive or UNC share, e.g. `C:` or `\\server\share`
+    match existing_ancestor(path)
+        .canonicalize()
+        .ok()?
+        .components()
+        .next()?
+    {
+        std::path::Component::Prefix(prefix) => {
+            Some(prefix.as_os_str().to_string_lossy().to_uppercase())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn request(mode: DownloadMode, quality: &str) -> DownloadRequest {
+        DownloadRequest {
+            quality: quality.to_string(),
+            mode,
+            ..test_support::download_request()
+        }
+    }
+
+    #[test]
+    fn test_download_size() {
+        let info = test_support::video_info();
+        let video = DownloadMode::VideoWithAudio;
+
+        assert_eq!(
+            download_size(&info, &request(video.clone(), "0")),
+            Some(400_000_000)
+        );
+        assert_eq!(
+            download_size(&info, &request(video.clone(), "720")),
+            Some(200_000_000)
+        );
+        // 900p isn't offered, so yt-dlp falls back to 720p
+        assert_eq!(
+            download_size(&info, &request(video.clone(), "900")),
+            Some(200_000_000)
+        );
+        assert_eq!(download_size(&info, &request(video.clone(), "480")), None);
+
+        let mut cut = request(video, "720");
+        cut.start_time = Some(25.0);
+        cut.end_time = Some(75.0);
+        assert_eq!(download_size(&info, &cut), Some(100_000_000));
+
+        // 320 kbps for 100 s
+        assert_eq!(
+            download_size(&info, &request(DownloadMode::AudioOnly, "high")),
+            Some(4_000_000)
+        );
+        assert_eq!(
+            download_size(&info, &request(DownloadMode::AudioOnly, "low")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_needs() {
+        assert_eq!(download_needs(None), SPACE_MARGIN);
+        assert_eq!(download_needs(Some(1000)), 2000 + SPACE_MARGIN);
+        assert_eq!(cut_needs(1000, 100.0, 0.0, 50.0), 500 + SPACE_MARGIN);
+        assert_eq!(cut_needs(1000, 0.0, 0.0, 50.0), 1000 + SPACE_MARGIN);
+        assert_eq!(download_needs(Some(u64::MAX)), u64::MAX);
+    }
+
+    #[test]
+    fn test_admit() {
+        let check = SpaceCheck {
+            dir: PathBuf::from("/videos"),
+            needed: 100,
+            available: Some(150),
+        };
+        assert!(check.admit(0).is_ok());
+        assert!(check.admit(50).is_ok());
+        match check.admit(80) {
+            Err(AppError::InsufficientSpace { needed, available }) => {
+                assert_eq!((needed, available), (100, 70))
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let unknown = SpaceCheck {
+            available: None,
+            ..check
+        };
+        assert!(unknown.admit(u64::MAX).is_ok());
+    }
+
+    #[test]
+    fn test_available_space_of_missing_folder() {
+        // Folders created by output rules don't exist yet at admission
+        let dir = std::env::temp_dir()
+            .join("dlcut-missing")
+            .join("a")
+            .join("b");
+        assert!(available_space(&dir).is_ok());
+        assert!(same_filesystem(&dir, &std::env::temp_dir()));
+
+        assert_eq!(output_dir("video.mp4"), PathBuf::from("."));
+        assert_eq!(output_dir("/videos/out.mp4"), PathBuf::from("/videos"));
+    }
+}
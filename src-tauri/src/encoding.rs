@@ -0,0 +1,725 @@
+//! Re-encode profiles
+//!
+//! Cuts fall back to re-encoding when stream copy fails. The encoders are
+//! picked from the output extension and the input's codecs: audio files keep
+//! their audio codec, WebM gets VP9 and Opus, Matroska keeps the source
+//! codecs, and everything else gets H.264 and AAC.
+//!
+//! Quality comes from an `EncodeQuality`: a named preset that each encoder
+//! maps to its own CRF scale and speed, with optional overrides.
+//!
+//! Exports with a target size instead get a video bitrate worked out from
+//! the duration and the audio bitrate, and are encoded in two passes with
+//! x264 or VP9. An output that still comes out too large is encoded again at
+//! a lower bitrate; see `EncodeProfile::shrink`.
+
+use crate::error::{AppError, Result};
+use crate::probe::MediaInfo;
+use crate::types::{format_bytes, format_duration, EncodeQuality, QualityPreset};
+use std::path::Path;
+
+/// Audio bitrates, in kbps, accepted as overrides
+const AUDIO_BITRATES: std::ops::RangeInclusive<u32> = 32..=640;
+
+/// Share of a target size left for container overhead and rate control
+/// missing the mark
+const SIZE_OVERHEAD: f64 = 0.04;
+
+/// Lowest video bitrate, in kbps, that still gives a watchable picture
+const MIN_VIDEO_BITRATE: u32 = 100;
+
+/// Highest audio bitrate, in kbps, an audio-only file is fit to a size at
+const MAX_SIZED_AUDIO_BITRATE: u32 = 320;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VideoEncoder {
+    X264,
+    X265,
+    Vp9,
+    Av1,
+}
+
+impl VideoEncoder {
+    fn for_codec(codec: &str) -> Option<Self> {
+        match codec {
+            "h264" => Some(Self::X264),
+            "hevc" => Some(Self::X265),
+            "vp9" => Some(Self::Vp9),
+            "av1" => Some(Self::Av1),
+            _ => None,
+        }
+    }
+
+    /// ffmpeg encoder name
+    fn encoder(self) -> &'static str {
+        match self {
+            Self::X264 => "libx264",
+            Self::X265 => "libx265",
+            Self::Vp9 => "libvpx-vp9",
+            Self::Av1 => "libaom-av1",
+        }
+    }
+
+    /// Codec name as ffprobe reports it
+    fn codec(self) -> &'static str {
+        match self {
+            Self::X264 => "h264",
+            Self::X265 => "hevc",
+            Self::Vp9 => "vp9",
+            Self::Av1 => "av1",
+        }
+    }
+
+    fn max_crf(self) -> u32 {
+        match self {
+            Self::X264 | Self::X265 => 51,
+            Self::Vp9 | Self::Av1 => 63,
+        }
+    }
+
+    /// CRF for a preset; the scales differ, so the same number means a
+    /// different quality per encoder
+    fn crf(self, preset: QualityPreset) -> u32 {
+        let (fast, balanced, high) = match self {
+            Self::X264 => (26, 23, 18),
+            Self::X265 => (30, 28, 23),
+            Self::Vp9 => (36, 32, 24),
+            Self::Av1 => (38, 32, 26),
+        };
+        match preset {
+            QualityPreset::Fast => fast,
+            QualityPreset::Balanced => balanced,
+            QualityPreset::High => high,
+        }
+    }
+
+    /// Speed arguments for a preset
+    fn speed_args(self, preset: QualityPreset) -> Vec<String> {
+        let args: &[&str] = match (self, preset) {
+            (Self::X264 | Self::X265, QualityPreset::Fast) => &["-preset", "veryfast"],
+            (Self::X264 | Self::X265, QualityPreset::Balanced) => &["-preset", "fast"],
+            (Self::X264 | Self::X265, QualityPreset::High) => &["-preset", "slow"],
+            (Self::Vp9, QualityPreset::Fast) => &["-deadline", "good", "-cpu-used", "4"],
+            (Self::Vp9, QualityPreset::Balanced) => &["-deadline", "good", "-cpu-used", "2"],
+            (Self::Vp9, QualityPreset::High) => &["-deadline", "good", "-cpu-used", "1"],
+            (Self::Av1, QualityPreset::Fast) => &["-cpu-used", "6"],
+            (Self::Av1, QualityPreset::Balanced) => &["-cpu-used", "4"],
+            (Self::Av1, QualityPreset::High) => &["-cpu-used", "3"],
+        };
+        let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        if matches!(self, Self::Vp9 | Self::Av1) {
+            args.extend(["-row-mt".to_string(), "1".to_string()]);
+        }
+        args
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AudioEncoder {
+    Aac,
+    Mp3,
+    Opus,
+    Vorbis,
+    Flac,
+    Alac,
+    Pcm,
+    Ac3,
+    Eac3,
+}
+
+impl AudioEncoder {
+    fn for_codec(codec: &str) -> Option<Self> {
+        match codec {
+            "aac" => Some(Self::Aac),
+            "mp3" => Some(Self::Mp3),
+            "opus" => Some(Self::Opus),
+            "vorbis" => Some(Self::Vorbis),
+            "flac" => Some(Self::Flac),
+            "alac" => Some(Self::Alac),
+            c if c.starts_with("pcm_") => Some(Self::Pcm),
+            "ac3" => Some(Self::Ac3),
+            "eac3" => Some(Self::Eac3),
+            _ => None,
+        }
+    }
+
+    /// ffmpeg encoder name
+    fn encoder(self) -> &'static str {
+        match self {
+            Self::Aac => "aac",
+            Self::Mp3 => "libmp3lame",
+            Self::Opus => "libopus",
+            Self::Vorbis => "libvorbis",
+            Self::Flac => "flac",
+            Self::Alac => "alac",
+            Self::Pcm => "pcm_s16le",
+            Self::Ac3 => "ac3",
+            Self::Eac3 => "eac3",
+        }
+    }
+
+    /// Codec name as ffprobe reports it
+    fn codec(self) -> &'static str {
+        match self {
+            Self::Mp3 => "mp3",
+            Self::Opus => "opus",
+            Self::Vorbis => "vorbis",
+            other => other.encoder(),
+        }
+    }
+
+    fn lossless(self) -> bool {
+        matches!(self, Self::Flac | Self::Alac | Self::Pcm)
+    }
+
+    /// Bitrate for a preset in kbps; surround codecs need more
+    fn bitrate(self, preset: QualityPreset) -> u32 {
+        let (fast, balanced, high) = match self {
+            Self::Ac3 | Self::Eac3 => (224, 384, 448),
+            _ => (96, 128, 192),
+        };
+        match preset {
+            QualityPreset::Fast => fast,
+            QualityPreset::Balanced => balanced,
+            QualityPreset::High => high,
+        }
+    }
+}
+
+/// Every audio encoder, for containers that take any of them
+const ANY_AUDIO: &[AudioEncoder] = &[
+    AudioEncoder::Aac,
+    AudioEncoder::Mp3,
+    AudioEncoder::Opus,
+    AudioEncoder::Vorbis,
+    AudioEncoder::Flac,
+    AudioEncoder::Alac,
+    AudioEncoder::Pcm,
+    AudioEncoder::Ac3,
+    AudioEncoder::Eac3,
+];
+
+/// Encoders a container takes, the first being the one used when the
+/// source's codec isn't among them; no video encoders means the video is
+/// not re-encoded
+struct Accepts {
+    video: &'static [VideoEncoder],
+    audio: &'static [AudioEncoder],
+}
+
+fn accepts(output_ext: &str) -> Accepts {
+    use AudioEncoder as A;
+    use VideoEncoder as V;
+
+    let (video, audio): (&[V], &[A]) = match output_ext.to_ascii_lowercase().as_str() {
+        "mp3" => (&[], &[A::Mp3]),
+        "m4a" => (&[], &[A::Aac, A::Alac]),
+        "aac" => (&[], &[A::Aac]),
+        "flac" => (&[], &[A::Flac]),
+        "wav" => (&[], &[A::Pcm]),
+        "ogg" => (&[], &[A::Vorbis, A::Opus, A::Flac]),
+        "opus" => (&[], &[A::Opus]),
+        "mka" => (&[], ANY_AUDIO),
+        "webm" => (&[V::Vp9, V::Av1], &[A::Opus, A::Vorbis]),
+        "mkv" => (&[V::X264, V::X265, V::Vp9, V::Av1], ANY_AUDIO),
+        // MP4, MOV and anything else get the most widely playable pair
+        _ => (&[V::X264], &[A::Aac]),
+    };
+    Accepts { video, audio }
+}
+
+/// Encoders and quality settings for re-encoding a cut
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodeProfile {
+    video: Option<VideoEncoder>,
+    audio: Option<AudioEncoder>,
+    quality: EncodeQuality,
+    /// Size the output has to fit in, in bytes
+    target_size: Option<u64>,
+}
+
+impl EncodeProfile {
+    /// Pick encoders for a cut to a file with extension `output_ext`
+    ///
+    /// `media` is what the cut keeps of the input. Without it, a video
+    /// container gets its default encoders for both video and audio.
+    pub fn new(
+        media: Option<&MediaInfo>,
+        output_ext: &str,
+        quality: &EncodeQuality,
+    ) -> Result<Self> {
+        let accepts = accepts(output_ext);
+        let (has_video, has_audio) = match media {
+            Some(media) => (media.video().is_some(), media.audio().is_some()),
+            None => (true, true),
+        };
+        let source_video = media
+            .and_then(MediaInfo::video)
+            .and_then(|s| s.codec.as_deref());
+        let source_audio = media
+            .and_then(MediaInfo::audio)
+            .and_then(|s| s.codec.as_deref());
+
+        let video = match accepts.video.first() {
+            Some(default) if has_video => Some(
+                source_video
+                    .and_then(VideoEncoder::for_codec)
+                    .filter(|encoder| accepts.video.contains(encoder))
+                    .unwrap_or(*default),
+            ),
+            _ => None,
+        };
+        let audio = match accepts.audio.first() {
+            Some(default) if has_audio => Some(
+                source_audio
+                    .and_then(AudioEncoder::for_codec)
+                    .filter(|encoder| accepts.audio.contains(encoder))
+                    .unwrap_or(*default),
+            ),
+            _ => None,
+        };
+
+        let profile = Self {
+            video,
+            audio,
+            quality: quality.clone(),
+            target_size: None,
+        };
+        profile.check()?;
+        Ok(profile)
+    }
+
+    /// Reject overrides the chosen encoders can't use
+    fn check(&self) -> Result<()> {
+        let quality = &self.quality;
+        if quality.crf.is_some() && quality.video_bitrate.is_some() {
+            return Err(AppError::EncodeError(
+                "Set either a CRF or a video bitrate, not both".to_string(),
+            ));
+        }
+        if let (Some(crf), Some(video)) = (quality.crf, self.video) {
+            if crf > video.max_crf() {
+                return Err(AppError::EncodeError(format!(
+                    "CRF {} is out of range for {}: use 0 to {}",
+                    crf,
+                    video.codec(),
+                    video.max_crf()
+                )));
+            }
+        }
+        if quality.video_bitrate == Some(0) {
+            return Err(AppError::EncodeError(
+                "The video bitrate must be above 0".to_string(),
+            ));
+        }
+        if let Some(bitrate) = quality.audio_bitrate {
+            if !AUDIO_BITRATES.contains(&bitrate) {
+                return Err(AppError::EncodeError(format!(
+                    "Audio bitrate {} kbps is out of range: use {} to {}",
+                    bitrate,
+                    AUDIO_BITRATES.start(),
+                    AUDIO_BITRATES.end()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Profile for encoding `duration` seconds into at most `target_size`
+    /// bytes, in two passes if there is video
+    ///
+    /// The bitrates come from the target, so overriding the CRF or video
+    /// bitrate is an error. Unless overridden, the audio bitrate drops below
+    /// the preset's when it would take more than a quarter of the budget.
+    pub fn for_size(
+        media: Option<&MediaInfo>,
+        output_ext: &str,
+        quality: &EncodeQuality,
+        target_size: u64,
+        duration: f64,
+    ) -> Result<Self> {
+        if quality.crf.is_some() || quality.video_bitrate.is_some() {
+            return Err(AppError::EncodeError(
+                "A target size sets the video bitrate itself; leave out the CRF and video bitrate"
+                    .to_string(),
+            ));
+        }
+        if duration <= 0.0 {
+            return Err(AppError::EncodeError(
+                "Can't fit a clip of unknown length to a size".to_string(),
+            ));
+        }
+
+        let mut profile = Self::new(media, output_ext, quality)?;
+        // Two-pass rate control is only wired up for x264 and VP9
+        profile.video = profile.video.map(|video| match video {
+            VideoEncoder::X264 | VideoEncoder::X265 => VideoEncoder::X264,
+            VideoEncoder::Vp9 | VideoEncoder::Av1 => VideoEncoder::Vp9,
+        });
+        if let Some(audio) = profile.audio.filter(|audio| audio.lossless()) {
+            profile.audio = accepts(output_ext)
+                .audio
+                .iter()
+                .copied()
+                .find(|encoder| !encoder.lossless());
+            if profile.audio.is_none() {
+                return Err(AppError::EncodeError(format!(
+                    "A .{} file can't be fit to a size: its {} audio is lossless",
+                    output_ext,
+                    audio.codec()
+                )));
+            }
+        }
+
+        let budget = target_size as f64 * 8.0 * (1.0 - SIZE_OVERHEAD) / 1000.0 / duration;
+        let budget = budget.floor() as u32;
+        match (profile.video, profile.audio) {
+            (Some(_), audio) => {
+                let audio_bitrate = audio.map_or(0, |audio| {
+                    quality.audio_bitrate.unwrap_or_else(|| {
+                        audio
+                            .bitrate(quality.preset)
+                            .min((budget / 4).max(*AUDIO_BITRATES.start()))
+                    })
+                });
+                let video_bitrate = budget.saturating_sub(audio_bitrate);
+                if video_bitrate < MIN_VIDEO_BITRATE {
+                    return Err(too_small(target_size, duration));
+                }
+                profile.quality.video_bitrate = Some(video_bitrate);
+                profile.quality.audio_bitrate = audio.map(|_| audio_bitrate);
+            }
+            (None, Some(_)) => {
+                if budget < *AUDIO_BITRATES.start() {
+                    return Err(too_small(target_size, duration));
+                }
+                profile.quality.audio_bitrate = Some(budget.min(MAX_SIZED_AUDIO_BITRATE));
+            }
+            (None, None) => {}
+        }
+
+        profile.target_size = Some(target_size);
+        Ok(profile)
+    }
+
+    /// Profile for another attempt after an encode came out at `actual`
+    /// bytes, over the target, with the bitrate lowered by the overshoot
+    pub fn shrink(&self, actual: u64) -> Result<Self> {
+        let Some(target_size) = self.target_size else {
+            return Ok(self.clone());
+        };
+
+        // Aim a little lower than the overshoot alone suggests, since rate
+        // control missed once already
+        let ratio = target_size as f64 / actual as f64 * 0.95;
+        let scale = |bitrate: u32| (bitrate as f64 * ratio).floor() as u32;
+        let missed = || {
+            AppError::EncodeError(format!(
+                "Couldn't get the output under {}: it came out at {}",
+                format_bytes(target_size),
+                format_bytes(actual)
+            ))
+        };
+        let mut profile = self.clone();
+        match (self.quality.video_bitrate, self.quality.audio_bitrate) {
+            (Some(video), _) => {
+                let video = scale(video);
+                if video < MIN_VIDEO_BITRATE {
+                    return Err(missed());
+                }
+                profile.quality.video_bitrate = Some(video);
+            }
+            (None, Some(audio)) => {
+                let audio = scale(audio);
+                if audio < *AUDIO_BITRATES.start() {
+                    return Err(missed());
+                }
+                profile.quality.audio_bitrate = Some(audio);
+            }
+            (None, None) => {}
+        }
+        Ok(profile)
+    }
+
+    /// Size in bytes the output has to fit in, for profiles made by
+    /// `for_size`
+    pub fn target_size(&self) -> Option<u64> {
+        self.target_size
+    }
+
+    /// Whether encoding takes two passes: sized exports with video
+    pub fn two_pass(&self) -> bool {
+        self.target_size.is_some() && self.video.is_some()
+    }
+
+    /// Bitrate of the video in kbps, if it is encoded at a set bitrate
+    pub fn video_bitrate(&self) -> Option<u32> {
+        self.video.and(self.quality.video_bitrate)
+    }
+
+    /// Video codec of the output as ffprobe reports it, if video is encoded
+    pub fn video_codec(&self) -> Option<&'static str> {
+        self.video.map(VideoEncoder::codec)
+    }
+
+    /// Audio codec of the output as ffprobe reports it, if audio is encoded
+    pub fn audio_codec(&self) -> Option<&'static str> {
+        self.audio.map(AudioEncoder::codec)
+    }
+
+    /// ffmpeg arguments for the video and audio encoders
+    pub fn args(&self) -> Vec<String> {
+        let mut args = self.video_args();
+        args.extend(self.audio_args());
+        args
+    }
+
+    /// ffmpeg arguments for one pass of a two-pass encode, with the pass
+    /// statistics kept at `passlog`. The first pass only analyses the video.
+    pub fn pass_args(&self, pass: u8, passlog: &Path) -> Vec<String> {
+        let mut args = self.video_args();
+        args.extend([
+            "-pass".to_string(),
+            pass.to_string(),
+            "-passlogfile".to_string(),
+            passlog.to_string_lossy().to_string(),
+        ]);
+        if pass == 1 {
+            args.extend(["-an", "-sn", "-dn", "-f", "null"].map(String::from));
+        } else {
+            args.extend(self.audio_args());
+        }
+        args
+    }
+
+    fn video_args(&self) -> Vec<String> {
+        let preset = self.quality.preset;
+        let mut args = Vec::new();
+
+        if let Some(video) = self.video {
+            args.extend(["-c:v".to_string(), video.encoder().to_string()]);
+            args.extend(video.speed_args(preset));
+            match self.quality.video_bitrate {
+                Some(bitrate) => args.extend(["-b:v".to_string(), format!("{}k", bitrate)]),
+                None => {
+                    let crf = self.quality.crf.unwrap_or_else(|| video.crf(preset));
+                    args.extend(["-crf".to_string(), crf.to_string()]);
+                    // libvpx and libaom only use the CRF as a constant
+                    // quality without a bitrate cap
+                    if matches!(video, VideoEncoder::Vp9 | VideoEncoder::Av1) {
+                        args.extend(["-b:v".to_string(), "0".to_string()]);
+                    }
+                }
+            }
+        }
+        args
+    }
+
+    fn audio_args(&self) -> Vec<String> {
+        let preset = self.quality.preset;
+        let mut args = Vec::new();
+
+        if let Some(audio) = self.audio {
+            args.extend(["-c:a".to_string(), audio.encoder().to_string()]);
+            if !audio.lossless() {
+                let bitrate = self.quality.audio_bitrate.unwrap_or(audio.bitrate(preset));
+                args.extend(["-b:a".to_string(), format!("{}k", bitrate)]);
+            }
+        }
+
+        args
+    }
+}
+
+fn too_small(target_size: u64, duration: f64) -> AppError {
+    AppError::EncodeError(format!(
+        "{} is too small for {}; shorten the clip or raise the size",
+        format_bytes(target_size),
+        format_duration(duration)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::{MediaStream, StreamKind};
+
+    fn media(video: Option<&str>, audio: Option<&str>) -> MediaInfo {
+        let stream = |index, kind, codec: &str| MediaStream {
+            index,
+            kind,
+            codec: Some(codec.to_string()),
+            ..Default::default()
+        };
+        let mut streams = Vec::new();
+        if let Some(codec) = video {
+            streams.push(stream(0, StreamKind::Video, codec));
+        }
+        if let Some(codec) = audio {
+            streams.push(stream(1, StreamKind::Audio, codec));
+        }
+        MediaInfo {
+            streams,
+            ..Default::default()
+        }
+    }
+
+    fn codecs(
+        media: Option<&MediaInfo>,
+        ext: &str,
+    ) -> (Option<&'static str>, Option<&'static str>) {
+        let profile = EncodeProfile::new(media, ext, &EncodeQuality::default()).unwrap();
+        (profile.video_codec(), profile.audio_codec())
+    }
+
+    #[test]
+    fn test_encoders_by_container() {
+        let hevc = media(Some("hevc"), Some("ac3"));
+        assert_eq!(codecs(Some(&hevc), "mp4"), (Some("h264"), Some("aac")));
+        assert_eq!(codecs(Some(&hevc), "mkv"), (Some("hevc"), Some("ac3")));
+        assert_eq!(codecs(Some(&hevc), "webm"), (Some("vp9"), Some("opus")));
+        assert_eq!(codecs(None, "mov"), (Some("h264"), Some("aac")));
+
+        // Audio files keep their codec where the container allows it
+        let song = media(None, Some("alac"));
+        assert_eq!(codecs(Some(&song), "m4a"), (None, Some("alac")));
+        assert_eq!(codecs(Some(&song), "mp3"), (None, Some("mp3")));
+        assert_eq!(codecs(Some(&song), "mp4"), (None, Some("aac")));
+        assert_eq!(codecs(None, "mp3"), (None, Some("mp3")));
+
+        let silent = media(Some("mpeg2video"), None);
+        assert_eq!(codecs(Some(&silent), "mkv"), (Some("h264"), None));
+    }
+
+    #[test]
+    fn test_quality_args() {
+        let film = media(Some("h264"), Some("aac"));
+        let args = |ext: &str, quality: EncodeQuality| {
+            EncodeProfile::new(Some(&film), ext, &quality)
+                .unwrap()
+                .args()
+                .join(" ")
+        };
+
+        assert_eq!(
+            args("mp4", EncodeQuality::default()),
+            "-c:v libx264 -preset fast -crf 23 -c:a aac -b:a 128k"
+        );
+        assert_eq!(
+            args(
+                "webm",
+                EncodeQuality {
+                    preset: QualityPreset::High,
+                    ..Default::default()
+                }
+            ),
+            "-c:v libvpx-vp9 -deadline good -cpu-used 1 -row-mt 1 -crf 24 -b:v 0 \
+             -c:a libopus -b:a 192k"
+        );
+        assert_eq!(
+            args(
+                "mp4",
+                EncodeQuality {
+                    preset: QualityPreset::Fast,
+                    video_bitrate: Some(2500),
+                    audio_bitrate: Some(160),
+                    ..Default::default()
+                }
+            ),
+            "-c:v libx264 -preset veryfast -b:v 2500k -c:a aac -b:a 160k"
+        );
+
+        // Lossless audio has no bitrate
+        let song = media(None, Some("flac"));
+        let profile = EncodeProfile::new(Some(&song), "flac", &EncodeQuality::default()).unwrap();
+        assert_eq!(profile.args().join(" "), "-c:a flac");
+    }
+
+    #[test]
+    fn test_quality_overrides_are_checked() {
+        let film = media(Some("vp9"), Some("opus"));
+        let check =
+            |ext: &str, quality: EncodeQuality| EncodeProfile::new(Some(&film), ext, &quality);
+
+        let crf = |crf| EncodeQuality {
+            crf: Some(crf),
+            ..Default::default()
+        };
+        assert!(check("webm", crf(60)).is_ok());
+        assert!(check("mp4", crf(60)).is_err());
+        assert!(check("mp4", crf(51)).is_ok());
+
+        let both = EncodeQuality {
+            crf: Some(20),
+            video_bitrate: Some(1000),
+            ..Default::default()
+        };
+        assert!(check("mp4", both).is_err());
+
+        let quiet = EncodeQuality {
+            audio_bitrate: Some(8),
+            ..Default::default()
+        };
+        assert!(check("mp4", quiet).is_err());
+    }
+
+    #[test]
+    fn test_for_size() {
+        const MB: u64 = 1024 * 1024;
+        let film = media(Some("hevc"), Some("flac"));
+        let quality = EncodeQuality::default();
+
+        // 10 MB over a minute leaves 1342 kbps, less 128 for audio
+        let profile = EncodeProfile::for_size(Some(&film), "mp4", &quality, 10 * MB, 60.0).unwrap();
+        assert!(profile.two_pass());
+        assert_eq!(profile.video_bitrate(), Some(1214));
+        assert_eq!(
+            profile.pass_args(1, Path::new("log")).join(" "),
+            "-c:v libx264 -preset fast -b:v 1214k -pass 1 -passlogfile log -an -sn -dn -f null"
+        );
+        assert_eq!(
+            profile.pass_args(2, Path::new("log")).join(" "),
+            "-c:v libx264 -preset fast -b:v 1214k -pass 2 -passlogfile log -c:a aac -b:a 128k"
+        );
+
+        // Two-pass x264 instead of x265, and lossy audio instead of flac
+        let mkv = EncodeProfile::for_size(Some(&film), "mkv", &quality, 10 * MB, 60.0).unwrap();
+        assert_eq!(
+            (mkv.video_codec(), mkv.audio_codec()),
+            (Some("h264"), Some("aac"))
+        );
+
+        // Long clips give up audio bitrate first, then fail
+        let long = EncodeProfile::for_size(Some(&film), "mp4", &quality, 10 * MB, 600.0).unwrap();
+        assert_eq!(long.video_bitrate(), Some(101));
+        assert!(EncodeProfile::for_size(Some(&film), "mp4", &quality, 10 * MB, 1200.0).is_err());
+
+        // Audio files get the whole budget in one pass
+        let song = media(None, Some("flac"));
+        let mp3 = EncodeProfile::for_size(Some(&song), "mp3", &quality, MB, 60.0).unwrap();
+        assert!(!mp3.two_pass());
+        assert_eq!(mp3.args().join(" "), "-c:a libmp3lame -b:a 134k");
+        assert!(EncodeProfile::for_size(Some(&song), "flac", &quality, MB, 60.0).is_err());
+
+        let crf = EncodeQuality {
+            crf: Some(20),
+            ..Default::default()
+        };
+        assert!(EncodeProfile::for_size(Some(&film), "mp4", &crf, 10 * MB, 60.0).is_err());
+    }
+
+    #[test]
+    fn test_shrink() {
+        let film = media(Some("h264"), Some("aac"));
+        let target = 10 * 1024 * 1024;
+        let profile =
+            EncodeProfile::for_size(Some(&film), "mp4", &Default::default(), target, 60.0).unwrap();
+
+        let retry = profile.shrink(11_000_000).unwrap();
+        assert_eq!(retry.video_bitrate(), Some(1099));
+        assert_eq!(retry.target_size(), Some(target));
+
+        // Far over the target at the lowest bitrate
+        assert!(profile.shrink(target * 20).is_err());
+    }
+}
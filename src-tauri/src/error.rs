@@ -4,6 +4,8 @@
 //! serde::Serialize to safely pass errors to the frontend without
 //! leaking sensitive internal details.
 
+use crate::timestamp::RangeError;
+use crate::types::format_bytes;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -23,6 +25,9 @@ pub enum AppError {
     #[error("Invalid timestamp: {0}")]
     InvalidTimestamp(String),
 
+    #[error("Invalid cut range: {0}")]
+    InvalidRange(#[from] RangeError),
+
     #[error("yt-dlp not found. Please ensure yt-dlp is installed and in PATH")]
     YtDlpNotFound,
 
@@ -32,6 +37,55 @@ pub enum AppError {
     #[error("Dependency error: {0}")]
     DependencyError(String),
 
+    #[error("Settings error: {0}")]
+    SettingsError(String),
+
+    #[error("History error: {0}")]
+    HistoryError(String),
+
+    #[error("Automation API error: {0}")]
+    AutomationError(String),
+
+    #[error("Native messaging error: {0}")]
+    NativeMessagingError(String),
+
+    #[error("Invalid link: {0}")]
+    InvalidDeepLink(String),
+
+    #[error("Batch import error: {0}")]
+    BatchError(String),
+
+    #[error("Invalid filename template: {0}")]
+    InvalidTemplate(String),
+
+    #[error("Output file already exists: {0}")]
+    OutputExists(String),
+
+    #[error("Failed to save output: {0}")]
+    OutputError(String),
+
+    #[error("Failed to read media: {0}")]
+    ProbeError(String),
+
+    #[error("Output failed verification: {0}")]
+    VerificationFailed(String),
+
+    #[error("Failed to encode: {0}")]
+    EncodeError(String),
+
+    #[error("Invalid reframing: {0}")]
+    InvalidReframe(String),
+
+    #[error("Invalid output folder: {0}")]
+    InvalidOutputFolder(String),
+
+    #[error(
+        "Not enough disk space: needs about {}, only {} free",
+        format_bytes(*needed),
+        format_bytes(*available)
+    )]
+    InsufficientSpace { needed: u64, available: u64 },
+
     #[error("Operation cancelled")]
     Cancelled,
 
@@ -4,8 +4,15 @@
 //! cutting doesn't suffice (e.g., for post-download trimming).
 
 use crate::deps;
+use crate::encoding::EncodeProfile;
 use crate::error::{AppError, Result};
-use crate::types::{ProgressStage, ProgressUpdate};
+use crate::probe;
+use crate::reframe;
+use crate::settings::{self, CollisionPolicy, Settings};
+use crate::staging::StagedOutput;
+use crate::streams::{self, StreamPlan};
+use crate::types::{format_bytes, CutOptions, ProgressStage, ProgressUpdate, Reframe};
+use crate::verification::{Expected, VerifiedOutput};
 use regex::Regex;
 use std::path::Path;
 use std::process::Stdio;
@@ -17,14 +24,13 @@ use tokio::sync::mpsc;
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
-/// Get the ffmpeg command (local or system)
-async fn get_ffmpeg_cmd() -> String {
-    deps::get_ffmpeg_command().await
-}
+/// Encodes to a target size tried before giving up, each at a lower
+/// bitrate than the last
+const MAX_SIZE_ATTEMPTS: u32 = 3;
 
-/// Check if ffmpeg is available
-pub async fn check_ffmpeg() -> Result<()> {
-    let ffmpeg_cmd = get_ffmpeg_cmd().await;
+/// Check if the ffmpeg `settings` resolve to is available
+pub async fn check_ffmpeg(settings: &Settings) -> Result<()> {
+    let ffmpeg_cmd = deps::get_ffmpeg_command(settings).await;
     let mut cmd = Command::new(&ffmpeg_cmd);
     cmd.arg("-version");
 
@@ -44,18 +50,67 @@ pub async fn check_ffmpeg() -> Result<()> {
 ///
 /// This function is used when we need to cut an already-downloaded video.
 /// It uses stream copy (-c copy) for fast, lossless cutting when possible.
+/// Every stream is kept, or only the input streams listed in
+/// `options.streams`, as far as the output container can carry them; see
+/// `streams::plan`. If stream copy fails, the cut is re-encoded with encoders
+/// that suit the output and `options.quality`; see `EncodeProfile`. With
+/// `options.target_size` the cut is always re-encoded, to fit that size, and
+/// likewise with `options.reframe`, through a filter graph.
+/// The cut is written to a temp file and only moved to `output_path`, as
+/// `if_exists` allows, once ffprobe confirms it has the planned streams and
+/// the requested length. Local cuts run with the saved settings.
 pub async fn cut_video(
     input_path: &str,
     output_path: &str,
     start_time: f64,
     end_time: f64,
+    options: &CutOptions,
+    if_exists: CollisionPolicy,
     progress_tx: mpsc::Sender<ProgressUpdate>,
-) -> Result<String> {
+) -> Result<VerifiedOutput> {
     let input = Path::new(input_path);
     if !input.exists() {
         return Err(AppError::CutError("Input file not found".to_string()));
     }
 
+    let settings = settings::load();
+
+    // Without a probe of the input ffmpeg picks one video and one audio
+    // stream itself
+    let input_media = probe::probe_media(input, &settings).await.ok();
+    let output_ext = Path::new(output_path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let plan = match (&input_media, options.streams.as_deref()) {
+        (Some(media), selection) => Some(streams::plan(media, selection, &output_ext)?),
+        (None, None) => None,
+        (None, Some(_)) => {
+            return Err(AppError::CutError(
+                "Could not list the input's streams to select from".to_string(),
+            ))
+        }
+    };
+    let output_media = plan
+        .as_ref()
+        .zip(input_media.as_ref())
+        .map(|(plan, media)| plan.output_media(media));
+    let mut expected = Expected::cut(output_media.as_ref(), start_time, end_time);
+    let profile = match options.target_size {
+        Some(target_size) => EncodeProfile::for_size(
+            output_media.as_ref(),
+            &output_ext,
+            &options.quality,
+            target_size,
+            end_time - start_time,
+        )?,
+        None => EncodeProfile::new(output_media.as_ref(), &output_ext, &options.quality)?,
+    };
+    let video_filter = reframe::video_filter(options.reframe.as_ref(), output_media.as_ref())?;
+
+    let staged = StagedOutput::new(Path::new(output_path), if_exists)?;
+    let temp_path = staged.temp_path().to_string_lossy().to_string();
+
     let _ = progress_tx
         .send(ProgressUpdate {
             stage: ProgressStage::Cutting,
@@ -67,29 +122,59 @@ pub async fn cut_video(
         .await;
 
     let duration = end_time - start_time;
+    let job = EncodeJob {
+        input_path,
+        output_path: &temp_path,
+        start_time,
+        duration: Some(duration),
+        plan: plan.as_ref(),
+        video_filter: video_filter.as_deref(),
+        settings: &settings,
+    };
+
+    // Sized and reframed exports skip stream copy, which can neither control
+    // the size nor apply filters
+    if profile.target_size().is_some() {
+        let passlog = staged.scratch_path("passlog");
+        let profile = encode_to_size(&job, &profile, &passlog, &progress_tx).await?;
+        expected = expected.reencoded(&profile);
+        return finish_cut(staged, &job, &expected, &progress_tx).await;
+    }
+    if job.video_filter.is_some() {
+        cut_video_reencode(&job, &profile, &progress_tx).await?;
+        expected = expected.reencoded(&profile);
+        return finish_cut(staged, &job, &expected, &progress_tx).await;
+    }
 
     // Build ffmpeg command
     // -ss before -i seeks before demuxing (faster)
     // -t specifies duration from start point
     // -c copy uses stream copy (no re-encoding, very fast)
     // -avoid_negative_ts make_zero helps with timestamp issues
-    let ffmpeg_cmd = get_ffmpeg_cmd().await;
+    let ffmpeg_cmd = deps::get_ffmpeg_command(&settings).await;
     let mut cmd = Command::new(&ffmpeg_cmd);
     cmd.args([
-        "-y", // Overwrite output
+        "-y", // Overwrite the temp file
         "-ss",
         &format!("{:.3}", start_time), // Seek to start
         "-i",
         input_path, // Input file
         "-t",
         &format!("{:.3}", duration), // Duration
-        "-c",
-        "copy", // Stream copy (no re-encode)
+    ]);
+    if let Some(plan) = &plan {
+        cmd.args(plan.map_args()); // Kept streams and chapters
+    }
+    cmd.args(["-c", "copy"]); // Stream copy (no re-encode)
+    if let Some(plan) = &plan {
+        cmd.args(plan.codec_args(false)); // Conversions for the container
+    }
+    cmd.args([
         "-avoid_negative_ts",
         "make_zero",
         "-progress",
         "pipe:1", // Progress to stdout
-        output_path,
+        &temp_path,
     ])
     .stdout(Stdio::piped())
     .stderr(Stdio::piped());
@@ -140,9 +225,31 @@ pub async fn cut_video(
         .map_err(|e| AppError::CutError(format!("Failed to wait for ffmpeg: {}", e)))?;
 
     if !status.success() {
-        // Try with re-encoding if stream copy failed
-        return cut_video_reencode(input_path, output_path, start_time, end_time, progress_tx)
-            .await;
+        // Try with re-encoding if stream copy failed, replacing the partial
+        // temp file
+        cut_video_reencode(&job, &profile, &progress_tx).await?;
+        expected = expected.reencoded(&profile);
+    }
+
+    finish_cut(staged, &job, &expected, &progress_tx).await
+}
+
+/// Move a finished cut into place, with the stream plan's warnings ahead of
+/// the verification's
+async fn finish_cut(
+    staged: StagedOutput,
+    job: &EncodeJob<'_>,
+    expected: &Expected,
+    progress_tx: &mpsc::Sender<ProgressUpdate>,
+) -> Result<VerifiedOutput> {
+    let mut output = staged
+        .commit(Path::new(job.output_path), expected, job.settings)
+        .await?;
+    if let Some(plan) = job.plan {
+        output
+            .verification
+            .warnings
+            .splice(0..0, plan.warnings.iter().cloned());
     }
 
     let _ = progress_tx
@@ -155,17 +262,85 @@ pub async fn cut_video(
         })
         .await;
 
-    Ok(output_path.to_string())
+    Ok(output)
+}
+
+/// A re-encode of (part of) an input into a temp file
+struct EncodeJob<'a> {
+    input_path: &'a str,
+    output_path: &'a str,
+    start_time: f64,
+    /// Length to encode from `start_time`; the rest of the input if `None`
+    duration: Option<f64>,
+    plan: Option<&'a StreamPlan>,
+    /// Filter graph for the main video
+    video_filter: Option<&'a str>,
+    /// Where ffmpeg and ffprobe are found
+    settings: &'a Settings,
+}
+
+impl EncodeJob<'_> {
+    /// ffmpeg command reading the job's range of the input
+    async fn command(&self) -> Command {
+        let ffmpeg_cmd = deps::get_ffmpeg_command(self.settings).await;
+        let mut cmd = Command::new(&ffmpeg_cmd);
+        cmd.args(["-y", "-nostats", "-ss", &format!("{:.3}", self.start_time)]);
+        cmd.args(["-i", self.input_path]);
+        if let Some(duration) = self.duration {
+            cmd.args(["-t", &format!("{:.3}", duration)]);
+        }
+        cmd
+    }
+
+    /// ffmpeg command writing every planned stream to the output, with video
+    /// and audio encoded by `encoder_args`
+    async fn output_command(&self, encoder_args: Vec<String>) -> Command {
+        let mut cmd = self.command().await;
+        if let Some(plan) = self.plan {
+            cmd.args(plan.map_args());
+            cmd.args(["-c", "copy"]);
+        }
+        cmd.args(encoder_args);
+        if let Some(plan) = self.plan {
+            cmd.args(plan.codec_args(true));
+        }
+        if let Some(filter) = self.video_filter {
+            // Only the main video: cover art is copied, and can't be filtered
+            match self.plan.and_then(StreamPlan::main_video) {
+                Some((output_index, _)) => cmd.arg(format!("-filter:{}", output_index)),
+                None => cmd.arg("-filter:v"),
+            };
+            cmd.arg(filter);
+        }
+        cmd.arg(self.output_path);
+        cmd
+    }
+
+    /// ffmpeg command for the first pass of a two-pass encode, which reads
+    /// only the main video
+    async fn analysis_command(&self, encoder_args: Vec<String>) -> Command {
+        let mut cmd = self.command().await;
+        if let Some((_, input_index)) = self.plan.and_then(StreamPlan::main_video) {
+            cmd.args(["-map", &format!("0:{}", input_index)]);
+        }
+        if let Some(filter) = self.video_filter {
+            cmd.args(["-filter:v", filter]);
+        }
+        cmd.args(encoder_args);
+        cmd.arg(if cfg!(windows) { "NUL" } else { "/dev/null" });
+        cmd
+    }
 }
 
 /// Cut video with re-encoding (fallback for when stream copy fails)
+///
+/// Video and audio are encoded as `profile` says; other streams are still
+/// copied or converted as planned.
 async fn cut_video_reencode(
-    input_path: &str,
-    output_path: &str,
-    start_time: f64,
-    end_time: f64,
-    progress_tx: mpsc::Sender<ProgressUpdate>,
-) -> Result<String> {
+    job: &EncodeJob<'_>,
+    profile: &EncodeProfile,
+    progress_tx: &mpsc::Sender<ProgressUpdate>,
+) -> Result<()> {
     let _ = progress_tx
         .send(ProgressUpdate {
             stage: ProgressStage::Cutting,
@@ -176,35 +351,221 @@ async fn cut_video_reencode(
         })
         .await;
 
-    let duration = end_time - start_time;
+    // Re-encode with the profile's encoders
+    let cmd = job.output_command(profile.args()).await;
+    let success = run_with_progress(cmd, job.duration, progress_tx, |percent| {
+        (percent, format!("Re-encoding... {:.0}%", percent))
+    })
+    .await?;
+
+    if !success {
+        return Err(AppError::CutError("ffmpeg encoding failed".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Encode to the profile's target size: in two passes, or one for audio,
+/// and again at a lower bitrate while the output comes out too large.
+/// Returns the profile of the encode that fit.
+async fn encode_to_size(
+    job: &EncodeJob<'_>,
+    profile: &EncodeProfile,
+    passlog: &Path,
+    progress_tx: &mpsc::Sender<ProgressUpdate>,
+) -> Result<EncodeProfile> {
+    let target_size = profile.target_size().unwrap_or(u64::MAX);
+    let mut profile = profile.clone();
+
+    // Pass 1 writes the main video alone, as output stream 0, and pass 2
+    // looks for the log of the stream at the same index
+    let plan = job.plan.map(StreamPlan::main_video_first);
+    let job = &EncodeJob {
+        plan: plan.as_ref(),
+        ..*job
+    };
+
+    // Each attempt either returns or shrinks the profile for the next one;
+    // the last returns its own outcome
+    let mut attempt = 1;
+    loop {
+        let retry = if attempt > 1 {
+            "Retrying smaller: "
+        } else {
+            ""
+        };
+        let passes: &[u8] = if profile.two_pass() { &[1, 2] } else { &[0] };
+
+        for &pass in passes {
+            let cmd = if pass == 1 {
+                // The first pass only gathers statistics
+                job.analysis_command(profile.pass_args(1, passlog)).await
+            } else if pass == 2 {
+                job.output_command(profile.pass_args(2, passlog)).await
+            } else {
+                job.output_command(profile.args()).await
+            };
+
+            let count = passes.len() as f64;
+            let done = passes.iter().position(|&p| p == pass).unwrap_or(0) as f64;
+            let success = run_with_progress(cmd, job.duration, progress_tx, |percent| {
+                let overall = (done * 100.0 + percent) / count;
+                let message = if pass == 0 {
+                    format!("{}Encoding to fit... {:.0}%", retry, percent)
+                } else {
+                    format!("{}Encoding pass {} of 2... {:.0}%", retry, pass, percent)
+                };
+                (overall, message)
+            })
+            .await?;
+
+            if !success {
+                return Err(AppError::CutError("ffmpeg encoding failed".to_string()));
+            }
+        }
+
+        let size = std::fs::metadata(job.output_path)
+            .map_err(|e| AppError::CutError(format!("Failed to read the encoded file: {}", e)))?
+            .len();
+        if size <= target_size {
+            return Ok(profile);
+        }
+        if attempt == MAX_SIZE_ATTEMPTS {
+            return Err(AppError::EncodeError(format!(
+                "Couldn't get the output under {} in {} tries: it came out at {}",
+                format_bytes(target_size),
+                MAX_SIZE_ATTEMPTS,
+                format_bytes(size)
+            )));
+        }
+        profile = profile.shrink(size)?;
+        attempt += 1;
+    }
+}
 
-    // Re-encode with libx264 and aac
-    let ffmpeg_cmd = get_ffmpeg_cmd().await;
+/// Re-encode a finished download into `output_path` so that it fits in
+/// `target_size` bytes, keeping its streams, with the ffmpeg `settings`
+/// resolve to
+///
+/// Returns `None`, leaving the download as it is, if it already fits, or
+/// the profile it was encoded with.
+pub async fn fit_to_size(
+    input: &Path,
+    output_path: &Path,
+    passlog: &Path,
+    target_size: u64,
+    settings: &Settings,
+    progress_tx: &mpsc::Sender<ProgressUpdate>,
+) -> Result<Option<EncodeProfile>> {
+    let size = std::fs::metadata(input)
+        .map_err(|e| AppError::CutError(format!("Failed to read the download: {}", e)))?
+        .len();
+    if size <= target_size {
+        return Ok(None);
+    }
+
+    let media = probe::probe_media(input, settings).await?;
+    let output_ext = output_path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let plan = streams::plan(&media, None, &output_ext)?;
+    let profile = EncodeProfile::for_size(
+        Some(&plan.output_media(&media)),
+        &output_ext,
+        &Default::default(),
+        target_size,
+        media.duration.unwrap_or(0.0),
+    )?;
+
+    let input_path = input.to_string_lossy();
+    let output_path = output_path.to_string_lossy();
+    let job = EncodeJob {
+        input_path: &input_path,
+        output_path: &output_path,
+        start_time: 0.0,
+        duration: media.duration,
+        plan: Some(&plan),
+        video_filter: None,
+        settings,
+    };
+    let _ = progress_tx
+        .send(ProgressUpdate {
+            stage: ProgressStage::Cutting,
+            percent: 0.0,
+            message: format!(
+                "Download is {}; encoding to fit {}...",
+                format_bytes(size),
+                format_bytes(target_size)
+            ),
+            speed: None,
+            eta: None,
+        })
+        .await;
+
+    encode_to_size(&job, &profile, passlog, progress_tx)
+        .await
+        .map(Some)
+}
+
+/// Render the frame of a local file at `time` to a JPEG at `output`, as
+/// `reframe` would make it if given
+pub async fn render_frame(
+    input_path: &str,
+    time: f64,
+    reframe: Option<&Reframe>,
+    output: &Path,
+) -> Result<()> {
+    let settings = settings::load();
+    let media = probe::probe_media(Path::new(input_path), &settings).await?;
+    let video = media
+        .video()
+        .ok_or_else(|| AppError::CutError("The file has no video".to_string()))?;
+    let video_filter = reframe::video_filter(reframe, Some(&media))?;
+
+    let ffmpeg_cmd = deps::get_ffmpeg_command(&settings).await;
     let mut cmd = Command::new(&ffmpeg_cmd);
-    cmd.args([
-        "-y",
-        "-ss",
-        &format!("{:.3}", start_time),
-        "-i",
-        input_path,
-        "-t",
-        &format!("{:.3}", duration),
-        "-c:v",
-        "libx264",
-        "-preset",
-        "fast",
-        "-crf",
-        "23",
-        "-c:a",
-        "aac",
-        "-b:a",
-        "128k",
-        "-progress",
-        "pipe:1",
-        output_path,
-    ])
-    .stdout(Stdio::piped())
-    .stderr(Stdio::piped());
+    cmd.args(["-y", "-v", "error", "-ss", &format!("{:.3}", time.max(0.0))]);
+    cmd.args(["-i", input_path]);
+    cmd.args(["-map", &format!("0:{}", video.index), "-frames:v", "1"]);
+    if let Some(filter) = &video_filter {
+        cmd.args(["-filter:v", filter]);
+    }
+    cmd.args(["-q:v", "3"])
+        .arg(output)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+
+    #[cfg(windows)]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    let result = cmd
+        .output()
+        .await
+        .map_err(|e| AppError::CutError(format!("Failed to start ffmpeg: {}", e)))?;
+    if !result.status.success() || !output.exists() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(AppError::CutError(format!(
+            "Failed to render the preview frame: {}",
+            stderr.lines().last().unwrap_or("no frame at that time")
+        )));
+    }
+
+    Ok(())
+}
+
+/// Run an ffmpeg command, forwarding its progress through `report`, which
+/// turns the percentage of `duration` done into the overall percentage and
+/// message. Returns whether ffmpeg succeeded.
+async fn run_with_progress(
+    mut cmd: Command,
+    duration: Option<f64>,
+    progress_tx: &mpsc::Sender<ProgressUpdate>,
+    report: impl Fn(f64) -> (f64, String),
+) -> Result<bool> {
+    cmd.args(["-progress", "pipe:1"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
 
     #[cfg(windows)]
     cmd.creation_flags(CREATE_NO_WINDOW);
@@ -217,7 +578,7 @@ async fn cut_video_reencode(
     let mut reader = BufReader::new(stdout).lines();
 
     let time_regex = Regex::new(r"out_time_ms=(\d+)").unwrap();
-    let total_us = (duration * 1_000_000.0) as u64;
+    let total_us = (duration.unwrap_or(0.0) * 1_000_000.0) as u64;
 
     while let Some(line) = reader.next_line().await.ok().flatten() {
         if let Some(caps) = time_regex.captures(&line) {
@@ -228,11 +589,12 @@ async fn cut_video_reencode(
                     0.0
                 };
 
+                let (percent, message) = report(percent);
                 let _ = progress_tx
                     .send(ProgressUpdate {
                         stage: ProgressStage::Cutting,
                         percent,
-                        message: format!("Re-encoding... {:.0}%", percent),
+                        message,
                         speed: None,
                         eta: None,
                     })
@@ -246,62 +608,14 @@ async fn cut_video_reencode(
         .await
         .map_err(|e| AppError::CutError(format!("Failed to wait for ffmpeg: {}", e)))?;
 
-    if !status.success() {
-        return Err(AppError::CutError("ffmpeg encoding failed".to_string()));
-    }
-
-    let _ = progress_tx
-        .send(ProgressUpdate {
-            stage: ProgressStage::Complete,
-            percent: 100.0,
-            message: "Cut complete!".to_string(),
-            speed: None,
-            eta: None,
-        })
-        .await;
-
-    Ok(output_path.to_string())
+    Ok(status.success())
 }
 
 /// Get video duration using ffprobe
 pub async fn get_duration(path: &str) -> Result<f64> {
-    let input = Path::new(path);
-    if !input.exists() {
-        return Err(AppError::CutError("File not found".to_string()));
-    }
-
-    // Use ffprobe to get duration
-    let ffmpeg_cmd = get_ffmpeg_cmd().await;
-    // Derive ffprobe path from ffmpeg path
-    let ffprobe_cmd = ffmpeg_cmd.replace("ffmpeg", "ffprobe");
-
-    let mut cmd = Command::new(&ffprobe_cmd);
-    cmd.args([
-        "-v", "quiet",
-        "-print_format", "json",
-        "-show_format",
-        path,
-    ]);
-
-    #[cfg(windows)]
-    cmd.creation_flags(CREATE_NO_WINDOW);
-
-    let output = cmd
-        .output()
-        .await
-        .map_err(|e| AppError::CutError(format!("Failed to run ffprobe: {}", e)))?;
-
-    if !output.status.success() {
-        return Err(AppError::CutError("ffprobe failed to read file".to_string()));
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let json: serde_json::Value = serde_json::from_str(&stdout)
-        .map_err(|e| AppError::CutError(format!("Failed to parse ffprobe output: {}", e)))?;
-
-    json["format"]["duration"]
-        .as_str()
-        .and_then(|d| d.parse::<f64>().ok())
+    probe::probe_media(Path::new(path), &settings::load())
+        .await?
+        .duration
         .ok_or_else(|| AppError::CutError("Could not determine video duration".to_string()))
 }
 
@@ -312,7 +626,7 @@ mod tests {
     #[tokio::test]
     async fn test_check_ffmpeg() {
         // This test will pass if ffmpeg is installed
-        let result = check_ffmpeg().await;
+        let result = check_ffmpeg(&settings::load()).await;
         // We don't assert success because ffmpeg might not be installed in CI
         println!("ffmpeg check result: {:?}", result);
     }
@@ -0,0 +1,649 @@
+//! Output file names
+//!
+//! File names are rendered from a template such as
+//! `{uploader} - {title} [{quality}]`, then made safe for every platform we
+//! ship on: characters Windows rejects are replaced, reserved device names
+//! (`CON`, `NUL`, `COM1`...) are escaped, trailing dots and spaces are
+//! dropped, and long names are cut on a character boundary.
+
+use crate::error::{AppError, Result};
+use crate::settings::{CollisionPolicy, Settings};
+use crate::types::{DownloadMode, Platform, VideoInfo};
+use std::path::{Path, PathBuf};
+use time::{Date, OffsetDateTime};
+
+/// Template used when none is configured
+pub const DEFAULT_TEMPLATE: &str = "{title}";
+
+/// Fields a template may use; `yyyy`, `mm` and `dd` are the date the job
+/// was created (UTC)
+pub const FIELDS: [&str; 13] = [
+    "title",
+    "uploader",
+    "id",
+    "platform",
+    "upload_date",
+    "quality",
+    "start",
+    "end",
+    "segment_label",
+    "yyyy",
+    "mm",
+    "dd",
+    "yyyy-mm",
+];
+
+/// Longest file name stem in bytes, leaving room for an extension and a
+/// collision suffix within the usual 255-byte limit
+const MAX_STEM_BYTES: usize = 200;
+
+/// Name used when a template renders to nothing
+const FALLBACK_STEM: &str = "download";
+
+/// Device names Windows reserves regardless of extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Values available to a template; missing values render as nothing
+#[derive(Debug, Clone, Default)]
+pub struct TemplateFields {
+    pub title: String,
+    pub uploader: Option<String>,
+    pub id: Option<String>,
+    pub platform: Option<Platform>,
+    /// `YYYYMMDD`, as reported by yt-dlp
+    pub upload_date: Option<String>,
+    /// Video height (`"1080"`, `"0"` for best) or audio quality id
+    pub quality: Option<String>,
+    /// Range start in seconds
+    pub start: Option<f64>,
+    /// Range end in seconds
+    pub end: Option<f64>,
+    /// User-chosen label for a cut segment
+    pub segment_label: Option<String>,
+    /// Date the job was created
+    pub date: Option<Date>,
+}
+
+impl TemplateFields {
+    /// Fields for a download of `info`
+    pub fn for_download(
+        info: &VideoInfo,
+        mode: &DownloadMode,
+        quality: &str,
+        start: Option<f64>,
+        end: Option<f64>,
+    ) -> Self {
+        let quality = match (mode, quality) {
+            (DownloadMode::VideoWithAudio, "0") => "best".to_string(),
+            (DownloadMode::VideoWithAudio, height) => format!("{}p", height),
+            (DownloadMode::AudioOnly, quality) => quality.to_string(),
+        };
+
+        Self {
+            title: info.title.clone(),
+            uploader: info.uploader.clone(),
+            id: Some(info.id.clone()),
+            platform: Some(info.platform.clone()),
+            upload_date: info.upload_date.clone(),
+            quality: Some(quality),
+            start,
+            end,
+            segment_label: None,
+            date: Some(OffsetDateTime::now_utc().date()),
+        }
+    }
+
+    fn value(&self, field: &str) -> String {
+        let has_range = self.start.is_some() || self.end.is_some();
+        match field {
+            "title" => self.title.clone(),
+            "uploader" => self.uploader.clone().unwrap_or_default(),
+            "id" => self.id.clone().unwrap_or_default(),
+            "platform" => self
+                .platform
+                .as_ref()
+                .and_then(|p| serde_json::to_value(p).ok())
+                .and_then(|v| v.as_str().map(str::to_string))
+                .unwrap_or_default(),
+            "upload_date" => self
+                .upload_date
+                .as_deref()
+                .map(format_date)
+                .unwrap_or_default(),
+            "quality" => self.quality.clone().unwrap_or_default(),
+            "start" if has_range => format_time(self.start.unwrap_or(0.0)),
+            "end" if has_range => self.end.map(format_time).unwrap_or_else(|| "end".into()),
+            "segment_label" => self.segment_label.clone().unwrap_or_default(),
+            "yyyy" | "mm" | "dd" | "yyyy-mm" => match self.date {
+                Some(date) => {
+                    let (year, month, day) = (date.year(), u8::from(date.month()), date.day());
+                    match field {
+                        "yyyy" => format!("{:04}", year),
+                        "mm" => format!("{:02}", month),
+                        "dd" => format!("{:02}", day),
+                        _ => format!("{:04}-{:02}", year, month),
+                    }
+                }
+                None => String::new(),
+            },
+            _ => String::new(),
+        }
+    }
+}
+
+/// Check a template without rendering it
+pub fn validate_template(template: &str) -> Result<()> {
+    parse(template).map(|_| ())
+}
+
+/// Render a template into a safe file name with the given extension
+pub fn render(template: &str, fields: &TemplateFields, extension: &str) -> Result<String> {
+    Ok(file_name(&expand(template, fields)?, extension))
+}
+
+/// Render a template into a single safe path component, which is empty when
+/// every field in it is
+pub fn render_component(template: &str, fields: &TemplateFields) -> Result<String> {
+    Ok(sanitize(&expand(template, fields)?))
+}
+
+/// Make a title or stem safe and append the extension
+pub fn file_name(stem: &str, extension: &str) -> String {
+    let mut name = sanitize(stem);
+    if name.is_empty() {
+        name = FALLBACK_STEM.to_string();
+    }
+
+    let extension = sanitize(extension);
+    if !extension.is_empty() {
+        name.push('.');
+        name.push_str(&extension);
+    }
+    name
+}
+
+/// Path in `dir` for a file named by the configured template, after applying
+/// the collision policy against files already on disk
+pub fn output_path(
+    dir: &Path,
+    fields: &TemplateFields,
+    extension: &str,
+    settings: &Settings,
+) -> Result<PathBuf> {
+    let name = render(&settings.filename_template, fields, extension)?;
+    resolve_collision(&dir.join(name), settings.collision_policy, Path::exists)
+}
+
+/// Pick the path to write to according to the collision policy
+///
+/// `is_taken` decides whether a path is in use, so callers can also count
+/// files that are about to be written. Returns `OutputExists` when the
+/// policy is to skip.
+pub fn resolve_collision(
+    path: &Path,
+    policy: CollisionPolicy,
+    is_taken: impl Fn(&Path) -> bool,
+) -> Result<PathBuf> {
+    if !is_taken(path) || policy == CollisionPolicy::Overwrite {
+        return Ok(path.to_path_buf());
+    }
+    if policy == CollisionPolicy::Skip {
+        return Err(AppError::OutputExists(path.to_string_lossy().to_string()));
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|ext| ext.to_string_lossy());
+    (2..10_000)
+        .map(|n| {
+            let name = match &extension {
+                Some(ext) => format!("{} ({}).{}", stem, n, ext),
+                None => format!("{} ({})", stem, n),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| !is_taken(candidate))
+        .ok_or_else(|| AppError::OutputExists(path.to_string_lossy().to_string()))
+}
+
+enum Part<'a> {
+    Text(&'a str),
+    Field(&'a str),
+}
+
+fn expand(template: &str, fields: &TemplateFields) -> Result<String> {
+    let mut pieces: Vec<Piece> = parse(template)?
+        .into_iter()
+        .map(|part| match part {
+            Part::Text(literal) => Piece {
+                text: literal.to_string(),
+                literal: true,
+            },
+            Part::Field(field) => Piece {
+                text: fields.value(field),
+                literal: false,
+            },
+        })
+        .collect();
+
+    for i in 0..pieces.len() {
+        if !pieces[i].literal && pieces[i].text.is_empty() {
+            tidy(&mut pieces, i);
+        }
+    }
+
+    Ok(pieces.into_iter().map(|piece| piece.text).collect())
+}
+
+/// Template text or a field's value
+struct Piece {
+    text: String,
+    literal: bool,
+}
+
+/// Tidy up what the empty field at `pieces[i]` leaves behind in the template
+/// text around it: brackets around nothing and dangling separators. Field
+/// values are never touched, so titles keep their own brackets and dashes.
+fn tidy(pieces: &mut [Piece], i: usize) {
+    let is_separator = |c: char| c == '-' || c == '_' || c == ' ';
+    let (before, after) = pieces.split_at_mut(i);
+    let after = &mut after[1..];
+
+    // `({upload_date})` with no date
+    if let (Some(prev), Some(next)) = (before.last_mut(), after.first_mut()) {
+        if prev.literal && next.literal {
+            for (open, close) in [('(', ')'), ('[', ']')] {
+                if prev.text.ends_with(open) && next.text.starts_with(close) {
+                    prev.text.pop();
+                    next.text.remove(0);
+                    break;
+                }
+            }
+        }
+    }
+
+    let preceding = before.iter().rev().find(|piece| !piece.text.is_empty());
+    let has_after = after.iter().any(|piece| !piece.text.is_empty());
+    match (preceding, has_after) {
+        // Leading or between two separators: drop the separators after it,
+        // so `{title} - {uploader} - {id}` keeps one dash
+        (None, _) => trim_literals(after.iter_mut(), |text| {
+            text.trim_start_matches(is_separator)
+        }),
+        (Some(prev), true) if prev.text.ends_with(is_separator) => {
+            if let Some(next) = after.first_mut().filter(|next| next.literal) {
+                next.text = next.text.trim_start_matches(is_separator).to_string();
+            }
+        }
+        // Trailing: drop the separators before it
+        (Some(_), false) => trim_literals(before.iter_mut().rev(), |text| {
+            text.trim_end_matches(is_separator)
+        }),
+        (Some(_), true) => {}
+    }
+}
+
+/// Trim template text from the empty field outwards, up to the first field
+/// value or text that is left over
+fn trim_literals<'a>(pieces: impl Iterator<Item = &'a mut Piece>, trim: impl Fn(&str) -> &str) {
+    for piece in pieces {
+        if !piece.literal && !piece.text.is_empty() {
+            break;
+        }
+        piece.text = trim(&piece.text).to_string();
+        if !piece.text.is_empty() {
+            break;
+        }
+    }
+}
+
+/// Split a template into text and `{field}`s; `{{` and `}}` are literal braces
+fn parse(template: &str) -> Result<Vec<Part<'_>>> {
+    let invalid =
+        |reason: String| AppError::InvalidTemplate(format!("{} in \"{}\"", reason, template));
+
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while !rest.is_empty() {
+        if let Some(tail) = rest.strip_prefix("{{") {
+            parts.push(Part::Text("{"));
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("}}") {
+            parts.push(Part::Text("}"));
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix('{') {
+            let (field, tail) = tail
+                .split_once('}')
+                .ok_or_else(|| invalid("Unclosed '{'".to_string()))?;
+            if !FIELDS.contains(&field) {
+                return Err(invalid(format!(
+                    "Unknown field {{{}}} (available: {})",
+                    field,
+                    FIELDS.join(", ")
+                )));
+            }
+            parts.push(Part::Field(field));
+            rest = tail;
+        } else if rest.starts_with('}') {
+            return Err(invalid("Unmatched '}'".to_string()));
+        } else {
+            let end = rest.find(['{', '}']).unwrap_or(rest.len());
+            parts.push(Part::Text(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+
+    Ok(parts)
+}
+
+/// Replace characters that are invalid in file names on any platform, escape
+/// reserved names and cut to length on a character boundary
+fn sanitize(name: &str) -> String {
+    let replaced: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let mut name = truncate(replaced.trim(), MAX_STEM_BYTES)
+        .trim_end_matches(['.', ' '])
+        .trim_start()
+        .to_string();
+
+    if name.is_empty() {
+        return name;
+    }
+
+    // `NUL.mp4` is as reserved as `NUL`
+    let base = name.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(base))
+    {
+        name.insert(base.len(), '_');
+    }
+
+    name
+}
+
+/// Cut to at most `max_bytes` without splitting a character
+fn truncate(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+
+    let end = (0..=max_bytes)
+        .rev()
+        .find(|&i| text.is_char_boundary(i))
+        .unwrap_or(0);
+    &text[..end]
+}
+
+/// `20240131` as `2024-01-31`; other values are kept as they are
+fn format_date(date: &str) -> String {
+    if date.len() == 8 && date.bytes().all(|b| b.is_ascii_digit()) {
+        format!("{}-{}-{}", &date[..4], &date[4..6], &date[6..])
+    } else {
+        date.to_string()
+    }
+}
+
+/// Seconds as `1h02m03s`, `2m05s` or `45s`, keeping milliseconds if any
+fn format_time(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    let (whole, millis) = (millis / 1000, millis % 1000);
+    let (hours, minutes, secs) = (whole / 3600, whole % 3600 / 60, whole % 60);
+
+    // Seconds are zero-padded only after a larger unit: `45s`, `1m05s`
+    let width = if whole >= 60 { 2 } else { 1 };
+    let mut secs = format!("{:0width$}", secs, width = width);
+    if millis > 0 {
+        let fraction = format!("{:03}", millis);
+        secs = format!("{}.{}", secs, fraction.trim_end_matches('0'));
+    }
+
+    match (hours, minutes) {
+        (0, 0) => format!("{}s", secs),
+        (0, _) => format!("{}m{}s", minutes, secs),
+        _ => format!("{}h{:02}m{}s", hours, minutes, secs),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> TemplateFields {
+        TemplateFields {
+            title: "Never Gonna Give You Up".to_string(),
+            uploader: Some("Rick Astley".to_string()),
+            id: Some("dQw4w9WgXcQ".to_string()),
+            platform: Some(Platform::YouTube),
+            upload_date: Some("20091025".to_string()),
+            quality: Some("1080p".to_string()),
+            start: None,
+            end: None,
+            segment_label: None,
+            date: None,
+        }
+    }
+
+    #[test]
+    fn test_render_fields() {
+        let name = render(
+            "{uploader} - {title} [{quality}] {platform} {id} {upload_date}",
+            &fields(),
+            "mp4",
+        )
+        .unwrap();
+        assert_eq!(
+            name,
+            "Rick Astley - Never Gonna Give You Up [1080p] youtube dQw4w9WgXcQ 2009-10-25.mp4"
+        );
+
+        assert_eq!(
+            render(DEFAULT_TEMPLATE, &fields(), "mp3").unwrap(),
+            "Never Gonna Give You Up.mp3"
+        );
+        assert_eq!(
+            render("{{{id}}}", &fields(), "mp4").unwrap(),
+            "{dQw4w9WgXcQ}.mp4"
+        );
+    }
+
+    #[test]
+    fn test_render_date() {
+        let mut fields = fields();
+        fields.date = Some(Date::from_calendar_date(2024, time::Month::March, 5).unwrap());
+        assert_eq!(
+            render("{yyyy-mm}/{yyyy}{mm}{dd}", &fields, "").unwrap(),
+            "2024-03_20240305"
+        );
+        assert_eq!(render_component("{yyyy}", &fields).unwrap(), "2024");
+
+        fields.date = None;
+        assert_eq!(render_component("{yyyy}", &fields).unwrap(), "");
+    }
+
+    #[test]
+    fn test_render_range() {
+        let mut fields = fields();
+        let template = "{title} {start}-{end} {segment_label}";
+        assert_eq!(
+            render(template, &fields, "mp4").unwrap(),
+            "Never Gonna Give You Up.mp4"
+        );
+
+        fields.start = Some(90.0);
+        fields.end = Some(3723.5);
+        fields.segment_label = Some("chorus".to_string());
+        assert_eq!(
+            render(template, &fields, "mp4").unwrap(),
+            "Never Gonna Give You Up 1m30s-1h02m03.5s chorus.mp4"
+        );
+
+        fields.start = None;
+        fields.end = Some(5.0);
+        assert_eq!(
+            render("{start}-{end}", &fields, "mp4").unwrap(),
+            "0s-5s.mp4"
+        );
+        fields.start = Some(5.0);
+        fields.end = None;
+        assert_eq!(
+            render("{start}-{end}", &fields, "mp4").unwrap(),
+            "5s-end.mp4"
+        );
+    }
+
+    #[test]
+    fn test_missing_fields_leave_no_debris() {
+        let fields = TemplateFields {
+            title: "Clip".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            render(
+                "{uploader} - {title} [{quality}] ({upload_date})",
+                &fields,
+                "mp4"
+            )
+            .unwrap(),
+            "Clip.mp4"
+        );
+        assert_eq!(
+            render("{uploader}", &fields, "mp4").unwrap(),
+            "download.mp4"
+        );
+    }
+
+    #[test]
+    fn test_titles_are_kept_as_they_are() {
+        let fields = |title: &str| TemplateFields {
+            title: title.to_string(),
+            ..Default::default()
+        };
+        let template = "{uploader} - {title} [{quality}] ({upload_date})";
+
+        assert_eq!(
+            render(template, &fields("Array.map() explained"), "mp4").unwrap(),
+            "Array.map() explained.mp4"
+        );
+        assert_eq!(
+            render(template, &fields("-Intro-"), "mp4").unwrap(),
+            "-Intro-.mp4"
+        );
+        assert_eq!(
+            render("{title}", &fields("[Live]  Encore ()"), "mp4").unwrap(),
+            "[Live]  Encore ().mp4"
+        );
+
+        // Separators between two fields that are there stay
+        let mut both = fields("_Intro_");
+        both.id = Some("abc".to_string());
+        assert_eq!(
+            render("{title} - {uploader} - {id}", &both, "mp4").unwrap(),
+            "_Intro_ - abc.mp4"
+        );
+        assert_eq!(
+            render("{title} ({uploader}) {id}", &both, "mp4").unwrap(),
+            "_Intro_ abc.mp4"
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        for template in ["{title", "{nope}", "title}", "{Title}", "{}"] {
+            let error = validate_template(template).unwrap_err().to_string();
+            assert!(error.contains(template), "{}: {}", template, error);
+        }
+        assert!(validate_template("{title} {{literal}}").is_ok());
+        assert!(validate_template("").is_ok());
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(
+            file_name("a/b\\c:d*e?f\"g<h>i|j", "mp4"),
+            "a_b_c_d_e_f_g_h_i_j.mp4"
+        );
+        assert_eq!(
+            file_name("tab\there\nnewline", "mp4"),
+            "tab_here_newline.mp4"
+        );
+        assert_eq!(file_name("trailing dots... ", "mp4"), "trailing dots.mp4");
+        assert_eq!(file_name("...", "mp4"), "download.mp4");
+        assert_eq!(file_name("   ", "mp4"), "download.mp4");
+        assert_eq!(file_name("../../etc/passwd", "mp4"), ".._.._etc_passwd.mp4");
+    }
+
+    #[test]
+    fn test_reserved_names() {
+        assert_eq!(file_name("CON", "mp4"), "CON_.mp4");
+        assert_eq!(file_name("nul", "mp3"), "nul_.mp3");
+        assert_eq!(file_name("com1.part", "mp4"), "com1_.part.mp4");
+        assert_eq!(file_name("LPT9 ", "mp4"), "LPT9_.mp4");
+        assert_eq!(file_name("CONSOLE", "mp4"), "CONSOLE.mp4");
+        assert_eq!(file_name("COM10", "mp4"), "COM10.mp4");
+    }
+
+    #[test]
+    fn test_unicode_truncation() {
+        // Multibyte characters straddling the limit must not panic
+        for title in [
+            "é".repeat(150),
+            "日本語".repeat(60),
+            "👍🏽".repeat(40),
+            "a".repeat(500),
+        ] {
+            let name = file_name(&title, "mp4");
+            let stem = name.strip_suffix(".mp4").unwrap();
+            assert!(stem.len() <= MAX_STEM_BYTES, "{} bytes", stem.len());
+            assert!(title.starts_with(stem));
+        }
+        assert_eq!(truncate("aé", 2), "a");
+        assert_eq!(truncate("aé", 3), "aé");
+    }
+
+    #[test]
+    fn test_collisions() {
+        let dir = Path::new("/videos");
+        let path = dir.join("clip.mp4");
+        let taken = [dir.join("clip.mp4"), dir.join("clip (2).mp4")];
+        let is_taken = |p: &Path| taken.iter().any(|t| t == p);
+
+        assert_eq!(
+            resolve_collision(&path, CollisionPolicy::Number, is_taken).unwrap(),
+            dir.join("clip (3).mp4")
+        );
+        assert_eq!(
+            resolve_collision(&path, CollisionPolicy::Overwrite, is_taken).unwrap(),
+            path
+        );
+        assert!(matches!(
+            resolve_collision(&path, CollisionPolicy::Skip, is_taken),
+            Err(AppError::OutputExists(_))
+        ));
+
+        let free = dir.join("other.mp4");
+        assert_eq!(
+            resolve_collision(&free, CollisionPolicy::Skip, is_taken).unwrap(),
+            free
+        );
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0.0), "0s");
+        assert_eq!(format_time(5.0), "5s");
+        assert_eq!(format_time(45.25), "45.25s");
+        assert_eq!(format_time(90.0), "1m30s");
+        assert_eq!(format_time(3723.0), "1h02m03s");
+        assert_eq!(format_time(0.5), "0.5s");
+    }
+}
@@ -0,0 +1,164 @@
+//! Job history
+//!
+//! Completed downloads and cuts are recorded in `DLCut/history.json`, next to
+//! the settings file, so the desktop app and the CLI share one history.
+
+use crate::error::{AppError, Result};
+use crate::types::{DownloadMode, DownloadRequest};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of entries kept; older ones are dropped first
+pub const MAX_HISTORY_ENTRIES: usize = 500;
+
+/// What produced a history entry
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryKind {
+    Download,
+    Cut,
+}
+
+/// A finished download or cut
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HistoryEntry {
+    pub kind: HistoryKind,
+    /// URL for downloads, input file for cuts
+    pub source: String,
+    pub output_path: String,
+    pub title: Option<String>,
+    /// Only set for downloads
+    pub mode: Option<DownloadMode>,
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
+    /// Unix timestamp (seconds) when the job finished
+    pub completed_at: u64,
+}
+
+impl HistoryEntry {
+    /// Entry for a finished download
+    pub fn download(request: &DownloadRequest, output_path: &str, title: Option<String>) -> Self {
+        Self {
+            kind: HistoryKind::Download,
+            source: request.url.clone(),
+            output_path: output_path.to_string(),
+            title,
+            mode: Some(request.mode.clone()),
+            start_time: request.start_time,
+            end_time: request.end_time,
+            completed_at: now(),
+        }
+    }
+
+    /// Entry for a finished cut of a local file
+    pub fn cut(input_path: &str, output_path: &str, start_time: f64, end_time: f64) -> Self {
+        Self {
+            kind: HistoryKind::Cut,
+            source: input_path.to_string(),
+            output_path: output_path.to_string(),
+            title: None,
+            mode: None,
+            start_time: Some(start_time),
+            end_time: Some(end_time),
+            completed_at: now(),
+        }
+    }
+}
+
+/// Current Unix time in seconds
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Get the path of the history file
+pub fn get_history_path() -> Result<PathBuf> {
+    let base = dirs::data_local_dir()
+        .ok_or_else(|| AppError::HistoryError("Could not find local data directory".into()))?;
+
+    Ok(base.join("DLCut").join("history.json"))
+}
+
+/// Load history, newest first; a missing or unreadable file is an empty history
+pub fn load() -> Vec<HistoryEntry> {
+    get_history_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Add a finished job to the history
+pub fn record(entry: HistoryEntry) -> Result<()> {
+    let mut entries = load();
+    push_entry(&mut entries, entry);
+    save(&entries)
+}
+
+/// Remove all history entries
+pub fn clear() -> Result<()> {
+    save(&[])
+}
+
+/// Insert `entry` at the front and drop the oldest entries past the limit
+fn push_entry(entries: &mut Vec<HistoryEntry>, entry: HistoryEntry) {
+    entries.insert(0, entry);
+    entries.truncate(MAX_HISTORY_ENTRIES);
+}
+
+fn save(entries: &[HistoryEntry]) -> Result<()> {
+    let path = get_history_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            AppError::HistoryError(format!("Failed to create history directory: {}", e))
+        })?;
+    }
+
+    let contents = serde_json::to_string_pretty(entries)
+        .map_err(|e| AppError::HistoryError(format!("Failed to serialize history: {}", e)))?;
+
+    std::fs::write(&path, contents)
+        .map_err(|e| AppError::HistoryError(format!("Failed to write history: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn test_push_entry_keeps_newest() {
+        let mut entries = Vec::new();
+        for i in 0..MAX_HISTORY_ENTRIES + 5 {
+            let mut entry = HistoryEntry::cut("in.mp4", &format!("out-{}.mp4", i), 0.0, 1.0);
+            entry.completed_at = i as u64;
+            push_entry(&mut entries, entry);
+        }
+
+        assert_eq!(entries.len(), MAX_HISTORY_ENTRIES);
+        assert_eq!(
+            entries[0].output_path,
+            format!("out-{}.mp4", MAX_HISTORY_ENTRIES + 4)
+        );
+        assert_eq!(entries.last().unwrap().completed_at, 5);
+    }
+
+    #[test]
+    fn test_download_entry_roundtrip() {
+        let request = DownloadRequest {
+            mode: DownloadMode::AudioOnly,
+            output_path: "/tmp/song.mp3".to_string(),
+            start_time: Some(90.0),
+            ..test_support::download_request()
+        };
+        let entry = HistoryEntry::download(&request, "/tmp/song.mp3", Some("Song".into()));
+
+        let json = serde_json::to_string(&entry).unwrap();
+        assert!(json.contains(r#""kind":"download""#));
+        assert!(json.contains(r#""mode":"audio_only""#));
+        assert_eq!(serde_json::from_str::<HistoryEntry>(&json).unwrap(), entry);
+    }
+}
@@ -0,0 +1,265 @@
+//! Download queue
+//!
+//! Jobs submitted from outside the main window (the automation API, batch
+//! imports) are queued here and downloaded one at a time. Every change to a
+//! job is broadcast so listeners can follow progress without polling.
+
+use crate::diskspace;
+use crate::error::Result;
+use crate::history::{self, HistoryEntry};
+use crate::settings::{self, Settings};
+use crate::types::{DownloadRequest, ProgressUpdate};
+use crate::validation;
+use crate::verification::VerificationReport;
+use crate::ytdlp;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc};
+
+/// Finished jobs kept in the list; older ones are dropped first
+const MAX_FINISHED_JOBS: usize = 100;
+
+/// Lifecycle of a queued download
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed)
+    }
+}
+
+/// A queued download and its latest state
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: u64,
+    pub request: DownloadRequest,
+    pub status: JobStatus,
+    /// Latest progress reported by yt-dlp
+    pub progress: Option<ProgressUpdate>,
+    /// Final file path once the download completed
+    pub output_path: Option<String>,
+    /// What ffprobe found in the finished file
+    pub verification: Option<VerificationReport>,
+    pub error: Option<String>,
+    /// Disk space held for the job while it is queued or running, in bytes
+    pub required_space: u64,
+}
+
+struct QueueState {
+    jobs: Vec<Job>,
+    next_id: u64,
+    /// Whether a task is currently draining the queue
+    running: bool,
+}
+
+/// Sequential download queue shared by the app and the automation API
+pub struct JobQueue {
+    state: Mutex<QueueState>,
+    events: broadcast::Sender<Job>,
+    /// Settings used for every job instead of loading them from disk; a queue
+    /// with fixed settings also leaves the on-disk history alone
+    fixed_settings: Option<Settings>,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::build(None)
+    }
+}
+
+impl JobQueue {
+    /// Queue that runs jobs with fixed settings, detached from the user's data
+    pub fn with_settings(settings: Settings) -> Self {
+        Self::build(Some(settings))
+    }
+
+    fn build(fixed_settings: Option<Settings>) -> Self {
+        let (events, _) = broadcast::channel(256);
+        Self {
+            state: Mutex::new(QueueState {
+                jobs: Vec::new(),
+                next_id: 1,
+                running: false,
+            }),
+            events,
+            fixed_settings,
+        }
+    }
+
+    /// Settings jobs run with
+    pub fn settings(&self) -> Settings {
+        self.fixed_settings.clone().unwrap_or_else(settings::load)
+    }
+
+    /// Validate a download and add it to the queue, starting the queue if
+    /// it is idle
+    ///
+    /// The job is refused when it would not fit on its disk next to the jobs
+    /// already queued or running there.
+    pub async fn enqueue(self: &Arc<Self>, request: DownloadRequest) -> Result<Job> {
+        let (request, space) = validation::check_download(request, &self.settings()).await?;
+
+        let (job, start) = {
+            let mut state = self.state.lock().unwrap();
+            let reserved = state
+                .jobs
+                .iter()
+                .filter(|job| !job.status.is_finished())
+                .filter(|job| {
+                    let dir = diskspace::output_dir(&job.request.output_path);
+                    diskspace::same_filesystem(&dir, &space.dir)
+                })
+                .map(|job| job.required_space)
+                .sum();
+            space.admit(reserved)?;
+
+            let job = Job {
+                id: state.next_id,
+                request,
+                status: JobStatus::Queued,
+                progress: None,
+                output_path: None,
+                verification: None,
+                error: None,
+                required_space: space.needed,
+            };
+            state.next_id += 1;
+            state.jobs.push(job.clone());
+
+            let start = !state.running;
+            state.running = true;
+            (job, start)
+        };
+
+        let _ = self.events.send(job.clone());
+        if start {
+            tokio::spawn(self.clone().drain());
+        }
+
+        Ok(job)
+    }
+
+    /// All jobs, oldest first
+    pub fn list(&self) -> Vec<Job> {
+        self.state.lock().unwrap().jobs.clone()
+    }
+
+    /// Look up a single job
+    pub fn get(&self, id: u64) -> Option<Job> {
+        self.state
+            .lock()
+            .unwrap()
+            .jobs
+            .iter()
+            .find(|job| job.id == id)
+            .cloned()
+    }
+
+    /// Receive a snapshot of every job whenever it changes
+    pub fn subscribe(&self) -> broadcast::Receiver<Job> {
+        self.events.subscribe()
+    }
+
+    /// Run queued jobs until none are left
+    async fn drain(self: Arc<Self>) {
+        loop {
+            let next = {
+                let mut state = self.state.lock().unwrap();
+                match state
+                    .jobs
+                    .iter()
+                    .find(|job| job.status == JobStatus::Queued)
+                {
+                    Some(job) => Some(job.id),
+                    None => {
+                        state.running = false;
+                        None
+                    }
+                }
+            };
+
+            match next {
+                Some(id) => self.run(id).await,
+                None => break,
+            }
+        }
+    }
+
+    async fn run(&self, id: u64) {
+        let Some(job) = self.update(id, |job| job.status = JobStatus::Running) else {
+            return;
+        };
+        let settings = self.settings();
+
+        let (tx, mut rx) = mpsc::channel::<ProgressUpdate>(32);
+        let forward = async {
+            while let Some(progress) = rx.recv().await {
+                self.update(id, |job| job.progress = Some(progress));
+            }
+        };
+        let (result, ()) =
+            tokio::join!(ytdlp::download_video(&job.request, &settings, tx), forward);
+
+        match result {
+            Ok(output) => {
+                if self.fixed_settings.is_none() {
+                    let _ =
+                        history::record(HistoryEntry::download(&job.request, &output.path, None));
+                }
+                self.update(id, |job| {
+                    job.status = JobStatus::Completed;
+                    job.output_path = Some(output.path);
+                    job.verification = Some(output.verification);
+                });
+            }
+            Err(e) => {
+                self.update(id, |job| {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(e.to_string());
+                });
+            }
+        }
+
+        self.prune();
+    }
+
+    /// Apply a change to a job and broadcast the new snapshot
+    fn update(&self, id: u64, change: impl FnOnce(&mut Job)) -> Option<Job> {
+        let job = {
+            let mut state = self.state.lock().unwrap();
+            let job = state.jobs.iter_mut().find(|job| job.id == id)?;
+            change(job);
+            job.clone()
+        };
+
+        let _ = self.events.send(job.clone());
+        Some(job)
+    }
+
+    /// Drop the oldest finished jobs past the limit
+    fn prune(&self) {
+        let mut state = self.state.lock().unwrap();
+        let finished = state
+            .jobs
+            .iter()
+            .filter(|job| job.status.is_finished())
+            .count();
+
+        let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+        state.jobs.retain(|job| {
+            if excess > 0 && job.status.is_finished() {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
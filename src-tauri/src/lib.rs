@@ -3,41 +3,159 @@
 //! A simple, elegant Tauri application for downloading and cutting
 //! YouTube videos using yt-dlp and ffmpeg.
 
+pub mod automation;
+pub mod batch;
 pub mod commands;
+pub mod deeplink;
 pub mod deps;
+pub mod diskspace;
+pub mod encoding;
 pub mod error;
 pub mod ffmpeg;
 pub mod fileserver;
+pub mod filename;
+pub mod history;
+pub mod jobs;
+pub mod native_messaging;
+pub mod probe;
+pub mod reframe;
+pub mod routing;
+pub mod settings;
+pub mod staging;
+pub mod streams;
+#[cfg(test)]
+mod test_support;
+pub mod timestamp;
 pub mod types;
+pub mod validation;
+pub mod verification;
 pub mod ytdlp;
 
 use commands::AppState;
 use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_deep_link::DeepLinkExt;
+
+/// Bring the main window to the front, e.g. when a link is forwarded to it
+fn focus_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Hand dlcut:// links to the frontend
+fn handle_deep_links(app: &AppHandle, links: Vec<String>) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<Arc<AppState>>().inner().clone();
+        for link in links {
+            commands::open_deep_link(&app, &state, &link).await;
+        }
+        focus_main_window(&app);
+    });
+}
 
 /// Initialize and run the Tauri application
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
+    let builder = tauri::Builder::default();
+
+    // A second launch (e.g. from a clicked link) forwards to the running
+    // window; the deep-link feature delivers its URL through on_open_url
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+        focus_main_window(app);
+    }));
+
+    builder
         // Register plugins
+        .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_fs::init())
         // Initialize application state
         .manage(Arc::new(AppState::default()))
+        .setup(|app| {
+            let state = app.state::<Arc<AppState>>().inner().clone();
+
+            // Mirror queued job updates to the frontend
+            let handle = app.handle().clone();
+            let mut job_updates = state.jobs.subscribe();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    match job_updates.recv().await {
+                        Ok(job) => {
+                            let _ = handle.emit("job-update", &job);
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // Desktop entries from the installers declare the scheme already;
+            // this covers AppImages and development builds
+            #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+            if let Err(e) = app.deep_link().register_all() {
+                eprintln!("Failed to register dlcut:// links: {}", e);
+            }
+
+            let handle = app.handle().clone();
+            app.deep_link().on_open_url(move |event| {
+                let links = event.urls().iter().map(|url| url.to_string()).collect();
+                handle_deep_links(&handle, links);
+            });
+            if let Ok(Some(urls)) = app.deep_link().get_current() {
+                let links = urls.iter().map(|url| url.to_string()).collect();
+                handle_deep_links(app.handle(), links);
+            }
+
+            if settings::load().automation_api {
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = commands::start_automation(&state).await {
+                        eprintln!("Automation API not started: {}", e);
+                    }
+                });
+            }
+
+            Ok(())
+        })
         // Register IPC commands
         .invoke_handler(tauri::generate_handler![
             commands::check_dependencies,
             commands::install_dependencies,
+            commands::install_dependencies_from_path,
+            commands::install_aria2c,
+            commands::install_js_runtime,
+            commands::validate_dependency_paths,
+            commands::check_dependency_updates,
+            commands::update_ytdlp,
             commands::fetch_video_info,
             commands::validate_timestamps,
             commands::start_download,
             commands::cancel_download,
             commands::generate_filename,
+            commands::suggest_output_path,
             commands::get_default_download_dir,
             commands::show_in_folder,
             commands::get_video_duration,
+            commands::probe_media,
+            commands::preview_reframe,
             commands::cut_local_video,
             commands::serve_local_file,
+            commands::get_settings,
+            commands::update_settings,
+            commands::get_history,
+            commands::enqueue_download,
+            commands::list_jobs,
+            commands::import_batch,
+            commands::start_automation_server,
+            commands::stop_automation_server,
+            commands::get_automation_info,
+            commands::clear_history,
+            commands::take_pending_deep_link,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
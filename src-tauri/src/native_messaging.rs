@@ -0,0 +1,546 @@
+//! Browser native messaging host
+//!
+//! Browsers start the host with the calling extension's origin (Chromium) or
+//! the path of its manifest (Firefox) as arguments, then exchange messages
+//! framed as a native-endian `u32` length followed by UTF-8 JSON.
+//!
+//! An extension sends `{url, start, end, mode, quality}`. The download is
+//! queued in the running app through the automation API when it is enabled,
+//! and otherwise run by the host itself, reporting progress as further
+//! messages.
+
+use crate::commands;
+use crate::error::{AppError, Result};
+use crate::filename::TemplateFields;
+use crate::history::{self, HistoryEntry};
+use crate::settings::{self, Settings};
+use crate::types::{DownloadMode, DownloadRequest, ProgressUpdate, VideoInfo};
+use crate::validation;
+use crate::ytdlp;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+/// Name extensions pass to `connectNative`/`sendNativeMessage`
+pub const HOST_NAME: &str = "com.dlcut.host";
+
+/// Largest message accepted from the browser; requests are tiny
+const MAX_MESSAGE_BYTES: u32 = 1024 * 1024;
+
+/// Whether the process was started by a browser as a native messaging host
+pub fn is_native_messaging_launch(args: &[String]) -> bool {
+    match args.get(1) {
+        Some(arg) if arg.starts_with("chrome-extension://") => true,
+        // Firefox passes the manifest path followed by the extension ID
+        Some(arg) => arg.ends_with(".json") && args.len() >= 3,
+        None => false,
+    }
+}
+
+/// Read one framed message, or `None` once the browser closes the pipe
+pub async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Value>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(native_error(format!("Failed to read message: {}", e))),
+    }
+
+    let len = u32::from_ne_bytes(len);
+    if len > MAX_MESSAGE_BYTES {
+        return Err(native_error(format!(
+            "Message of {} bytes is too large",
+            len
+        )));
+    }
+
+    let mut body = vec![0u8; len as usize];
+    reader
+        .read_exact(&mut body)
+        .await
+        .map_err(|e| native_error(format!("Failed to read message: {}", e)))?;
+
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| native_error(format!("Invalid JSON message: {}", e)))
+}
+
+/// Write one framed message
+pub async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, message: &Value) -> Result<()> {
+    let body = message.to_string();
+    let write = async {
+        writer.write_all(&(body.len() as u32).to_ne_bytes()).await?;
+        writer.write_all(body.as_bytes()).await?;
+        writer.flush().await
+    };
+
+    write
+        .await
+        .map_err(|e| native_error(format!("Failed to write message: {}", e)))
+}
+
+fn native_error(message: String) -> AppError {
+    AppError::NativeMessagingError(message)
+}
+
+/// Download mode as sent by extensions
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum NativeMode {
+    #[serde(alias = "video_with_audio")]
+    Video,
+    #[serde(alias = "audio_only")]
+    Audio,
+}
+
+/// A timestamp given either in seconds or as text such as `1:30`
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(untagged)]
+enum NativeTime {
+    Seconds(f64),
+    Text(String),
+}
+
+impl NativeTime {
+    fn into_text(self) -> String {
+        match self {
+            NativeTime::Seconds(seconds) => seconds.to_string(),
+            NativeTime::Text(text) => text,
+        }
+    }
+}
+
+/// Download request sent by an extension
+#[derive(Debug, Clone, Deserialize)]
+struct NativeRequest {
+    url: String,
+    #[serde(default)]
+    start: Option<NativeTime>,
+    #[serde(default)]
+    end: Option<NativeTime>,
+    #[serde(default)]
+    mode: Option<NativeMode>,
+    #[serde(default)]
+    quality: Option<String>,
+}
+
+/// Serve messages until the browser disconnects
+pub async fn run_host() -> Result<()> {
+    let mut stdin = tokio::io::stdin();
+    let mut stdout = tokio::io::stdout();
+
+    while let Some(message) = read_message(&mut stdin).await? {
+        if let Err(e) = handle_message(message, &settings::load(), &mut stdout).await {
+            write_message(
+                &mut stdout,
+                &json!({ "type": "error", "message": e.to_string() }),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate a request, then queue it in the app or download it here
+async fn handle_message<W: AsyncWrite + Unpin>(
+    message: Value,
+    settings: &Settings,
+    writer: &mut W,
+) -> Result<()> {
+    let request: NativeRequest = serde_json::from_value(message)
+        .map_err(|e| native_error(format!("Invalid request: {}", e)))?;
+    ytdlp::validate_url(&request.url)?;
+
+    // The metadata names the output file and the duration bounds the cut range
+    let info = ytdlp::fetch_video_info(&request.url, settings).await?;
+    let download = to_download_request(request, &info, settings)?;
+    let download = validation::validate_download(download, settings).await?;
+
+    if let Some(job) = forward_to_app(&download, settings).await {
+        return write_message(writer, &json!({ "type": "queued", "job": job })).await;
+    }
+
+    write_message(
+        writer,
+        &json!({ "type": "started", "output_path": download.output_path }),
+    )
+    .await?;
+
+    let (tx, mut rx) = mpsc::channel::<ProgressUpdate>(32);
+    let forward = async {
+        while let Some(progress) = rx.recv().await {
+            let mut message = json!(progress);
+            message["type"] = json!("progress");
+            // The extension may already be gone; keep downloading regardless
+            let _ = write_message(writer, &message).await;
+        }
+    };
+    let (result, ()) = tokio::join!(ytdlp::download_video(&download, settings, tx), forward);
+    let output = result?;
+
+    let _ = history::record(HistoryEntry::download(
+        &download,
+        &output.path,
+        Some(info.title),
+    ));
+    write_message(
+        writer,
+        &json!({ "type": "complete", "path": output.path, "verification": output.verification }),
+    )
+    .await
+}
+
+/// Turn an extension request into a validated `DownloadRequest`
+fn to_download_request(
+    request: NativeRequest,
+    info: &VideoInfo,
+    settings: &Settings,
+) -> Result<DownloadRequest> {
+    let (start_time, end_time) = commands::validate_timestamps(
+        request.start.map(NativeTime::into_text),
+        request.end.map(NativeTime::into_text),
+        info.duration,
+        None,
+    )?;
+
+    let mode = match request.mode.unwrap_or(NativeMode::Video) {
+        NativeMode::Video => DownloadMode::VideoWithAudio,
+        NativeMode::Audio => DownloadMode::AudioOnly,
+    };
+    let quality = request
+        .quality
+        .map(|q| q.trim().trim_end_matches('p').to_string())
+        .filter(|q| !q.is_empty())
+        .unwrap_or_else(|| mode.default_quality().to_string());
+    let fields = TemplateFields::for_download(info, &mode, &quality, start_time, end_time);
+    let output_path = commands::default_output_path(&fields, &mode, settings)?;
+
+    Ok(DownloadRequest {
+        url: request.url,
+        quality,
+        mode,
+        output_path: output_path.to_string_lossy().to_string(),
+        start_time,
+        end_time,
+        if_exists: None,
+        target_size: None,
+    })
+}
+
+/// Queue the download in the running app through its automation API
+///
+/// Returns the queued job, or `None` when the app is not reachable.
+async fn forward_to_app(request: &DownloadRequest, settings: &Settings) -> Option<Value> {
+    if !settings.automation_api {
+        return None;
+    }
+    let token = settings.automation_token.as_deref()?;
+
+    let response = reqwest::Client::new()
+        .post(format!(
+            "http://127.0.0.1:{}/api/jobs",
+            settings.automation_port
+        ))
+        .bearer_auth(token)
+        .body(json!(request).to_string())
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .ok()?;
+
+    if !response.status().is_success() {
+        return None;
+    }
+    serde_json::from_str(&response.text().await.ok()?).ok()
+}
+
+/// Host manifest for Chromium-based browsers
+fn chromium_manifest(host_path: &Path, extension_ids: &[String]) -> Value {
+    json!({
+        "name": HOST_NAME,
+        "description": "DLCut",
+        "path": host_path,
+        "type": "stdio",
+        "allowed_origins": extension_ids
+            .iter()
+            .map(|id| format!("chrome-extension://{}/", id))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Host manifest for Firefox
+fn firefox_manifest(host_path: &Path, extension_ids: &[String]) -> Value {
+    json!({
+        "name": HOST_NAME,
+        "description": "DLCut",
+        "path": host_path,
+        "type": "stdio",
+        "allowed_extensions": extension_ids,
+    })
+}
+
+/// Chromium-family configuration directories, relative to the home directory
+const CHROMIUM_CONFIG_DIRS: &[&str] = &[
+    ".config/google-chrome",
+    ".config/chromium",
+    ".config/BraveSoftware/Brave-Browser",
+    ".config/microsoft-edge",
+];
+
+/// Write the host manifests for the current user, returning the files written
+///
+/// `host_path` is the executable browsers start, normally `dlcut-cli`.
+pub fn install_manifests(
+    host_path: &Path,
+    chromium_ids: &[String],
+    firefox_ids: &[String],
+) -> Result<Vec<PathBuf>> {
+    if !cfg!(target_os = "linux") {
+        return Err(native_error(
+            "Installing host manifests is only supported on Linux".into(),
+        ));
+    }
+
+    let home =
+        dirs::home_dir().ok_or_else(|| native_error("Could not find home directory".into()))?;
+    install_manifests_in(&home, host_path, chromium_ids, firefox_ids)
+}
+
+fn install_manifests_in(
+    home: &Path,
+    host_path: &Path,
+    chromium_ids: &[String],
+    firefox_ids: &[String],
+) -> Result<Vec<PathBuf>> {
+    if chromium_ids.is_empty() && firefox_ids.is_empty() {
+        return Err(native_error("No extension IDs given".into()));
+    }
+
+    let file_name = format!("{}.json", HOST_NAME);
+    let mut targets = Vec::new();
+
+    if !chromium_ids.is_empty() {
+        let manifest = chromium_manifest(host_path, chromium_ids);
+        // Install for every Chromium-family browser the user has, or Chrome if none
+        let mut dirs: Vec<PathBuf> = CHROMIUM_CONFIG_DIRS
+            .iter()
+            .map(|dir| home.join(dir))
+            .filter(|dir| dir.is_dir())
+            .collect();
+        if dirs.is_empty() {
+            dirs.push(home.join(CHROMIUM_CONFIG_DIRS[0]));
+        }
+
+        for dir in dirs {
+            let path = dir.join("NativeMessagingHosts").join(&file_name);
+            targets.push((path, manifest.clone()));
+        }
+    }
+
+    if !firefox_ids.is_empty() {
+        let path = home
+            .join(".mozilla")
+            .join("native-messaging-hosts")
+            .join(&file_name);
+        targets.push((path, firefox_manifest(host_path, firefox_ids)));
+    }
+
+    let mut written = Vec::new();
+    for (path, manifest) in targets {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                native_error(format!("Failed to create {}: {}", parent.display(), e))
+            })?;
+        }
+
+        let contents = serde_json::to_string_pretty(&manifest)
+            .map_err(|e| native_error(format!("Failed to serialize manifest: {}", e)))?;
+        std::fs::write(&path, contents)
+            .map_err(|e| native_error(format!("Failed to write {}: {}", path.display(), e)))?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automation::AutomationServer;
+    use crate::jobs::JobQueue;
+    use crate::test_support;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_is_native_messaging_launch() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert!(is_native_messaging_launch(&args(&[
+            "dlcut-cli",
+            "chrome-extension://abcdefghijklmnopabcdefghijklmnop/"
+        ])));
+        assert!(is_native_messaging_launch(&args(&[
+            "dlcut-cli",
+            "/home/user/.mozilla/native-messaging-hosts/com.dlcut.host.json",
+            "dlcut@example.com"
+        ])));
+        assert!(!is_native_messaging_launch(&args(&[
+            "dlcut-cli",
+            "info",
+            "https://youtu.be/x"
+        ])));
+        assert!(!is_native_messaging_launch(&args(&["dlcut-cli"])));
+    }
+
+    #[tokio::test]
+    async fn test_message_framing() {
+        let mut buffer = Vec::new();
+        let message = json!({ "url": "https://youtu.be/dQw4w9WgXcQ", "start": 90 });
+        write_message(&mut buffer, &message).await.unwrap();
+        write_message(&mut buffer, &json!({ "type": "ping" }))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            &buffer[..4],
+            (message.to_string().len() as u32).to_ne_bytes()
+        );
+
+        let mut reader = buffer.as_slice();
+        assert_eq!(read_message(&mut reader).await.unwrap(), Some(message));
+        assert_eq!(
+            read_message(&mut reader).await.unwrap(),
+            Some(json!({ "type": "ping" }))
+        );
+        assert_eq!(read_message(&mut reader).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_message_rejects_oversized_and_invalid() {
+        let mut oversized = (MAX_MESSAGE_BYTES + 1).to_ne_bytes().to_vec();
+        oversized.extend_from_slice(b"{}");
+        assert!(read_message(&mut oversized.as_slice()).await.is_err());
+
+        let mut invalid = 3u32.to_ne_bytes().to_vec();
+        invalid.extend_from_slice(b"{x}");
+        assert!(read_message(&mut invalid.as_slice()).await.is_err());
+    }
+
+    #[test]
+    fn test_to_download_request() {
+        let request: NativeRequest = serde_json::from_value(json!({
+            "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "start": "0:30",
+            "end": 65,
+            "mode": "audio",
+            "quality": "medium",
+        }))
+        .unwrap();
+        let settings = Settings::default();
+        let info = VideoInfo {
+            title: "Some: title".to_string(),
+            ..test_support::video_info()
+        };
+        let download = to_download_request(request, &info, &settings).unwrap();
+
+        assert_eq!(download.start_time, Some(30.0));
+        assert_eq!(download.end_time, Some(65.0));
+        assert_eq!(download.mode, DownloadMode::AudioOnly);
+        assert_eq!(download.quality, "medium");
+        assert!(download.output_path.ends_with("Some_ title.mp3"));
+
+        let request: NativeRequest = serde_json::from_value(json!({
+            "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "quality": "720p",
+        }))
+        .unwrap();
+        let info = test_support::video_info();
+        let download = to_download_request(request, &info, &settings).unwrap();
+        assert_eq!(download.mode, DownloadMode::VideoWithAudio);
+        assert_eq!(download.quality, "720");
+
+        let request: NativeRequest = serde_json::from_value(json!({
+            "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "end": "9:00",
+        }))
+        .unwrap();
+        assert!(to_download_request(request, &info, &settings).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_forward_to_app() {
+        let mut app_settings = Settings::default();
+        app_settings.binary_paths.ytdlp = Some("/nonexistent/yt-dlp".to_string());
+        let jobs = Arc::new(JobQueue::with_settings(app_settings));
+        let server = AutomationServer::start(0, "secret".to_string(), jobs.clone())
+            .await
+            .unwrap();
+
+        let download = DownloadRequest {
+            quality: "0".to_string(),
+            output_path: "/tmp/dlcut-native.mp4".to_string(),
+            ..test_support::download_request()
+        };
+        let mut settings = Settings {
+            automation_api: true,
+            automation_port: server.info().port,
+            automation_token: Some("secret".to_string()),
+            ..Settings::default()
+        };
+
+        let job = forward_to_app(&download, &settings).await.unwrap();
+        assert_eq!(job["id"], 1);
+        assert_eq!(jobs.list().len(), 1);
+
+        // A wrong token means the app is treated as unreachable
+        settings.automation_token = Some("wrong".to_string());
+        assert!(forward_to_app(&download, &settings).await.is_none());
+
+        settings.automation_api = false;
+        assert!(forward_to_app(&download, &settings).await.is_none());
+
+        server.stop();
+    }
+
+    #[test]
+    fn test_install_manifests() {
+        let home = test_support::temp_dir("native");
+        std::fs::create_dir_all(home.join(".config/chromium")).unwrap();
+
+        let host = Path::new("/opt/dlcut/dlcut-cli");
+        let written = install_manifests_in(
+            &home,
+            host,
+            &["abcdefghijklmnopabcdefghijklmnop".to_string()],
+            &["dlcut@example.com".to_string()],
+        )
+        .unwrap();
+
+        assert_eq!(
+            written,
+            [
+                home.join(".config/chromium/NativeMessagingHosts/com.dlcut.host.json"),
+                home.join(".mozilla/native-messaging-hosts/com.dlcut.host.json"),
+            ]
+        );
+
+        let chromium: Value =
+            serde_json::from_str(&std::fs::read_to_string(&written[0]).unwrap()).unwrap();
+        assert_eq!(chromium["path"], "/opt/dlcut/dlcut-cli");
+        assert_eq!(
+            chromium["allowed_origins"][0],
+            "chrome-extension://abcdefghijklmnopabcdefghijklmnop/"
+        );
+
+        let firefox: Value =
+            serde_json::from_str(&std::fs::read_to_string(&written[1]).unwrap()).unwrap();
+        assert_eq!(firefox["allowed_extensions"][0], "dlcut@example.com");
+
+        assert!(install_manifests_in(&home, host, &[], &[]).is_err());
+        let _ = std::fs::remove_dir_all(&home);
+    }
+}
@@ -0,0 +1,787 @@
+//! Local media inspection
+//!
+//! Reads everything ffprobe knows about a local file (container, streams,
+//! chapters and tags) into a typed `MediaInfo`. The cutter shows it for the
+//! opened file, cut validation checks the input with it and output
+//! verification compares finished files against it.
+
+use crate::deps::{self, Dependency};
+use crate::error::{AppError, Result};
+use crate::settings::Settings;
+use crate::types::{format_bytes, format_duration};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tokio::process::Command;
+
+/// Windows flag to prevent console window from appearing
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// A local media file as ffprobe sees it
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct MediaInfo {
+    /// Demuxer names, e.g. "mov,mp4,m4a,3gp,3g2,mj2" or "matroska,webm"
+    pub container: String,
+    /// Readable container name, e.g. "QuickTime / MOV"
+    pub container_long_name: Option<String>,
+    /// Duration in seconds
+    pub duration: Option<f64>,
+    /// Overall bitrate in bits per second
+    pub bit_rate: Option<u64>,
+    /// File size in bytes
+    pub size: Option<u64>,
+    pub streams: Vec<MediaStream>,
+    pub chapters: Vec<Chapter>,
+    /// Container metadata such as title, artist or encoder
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamKind {
+    Video,
+    Audio,
+    Subtitle,
+    Data,
+    Attachment,
+    #[default]
+    Unknown,
+}
+
+/// One stream of a media file; video and audio fields are `None` for other
+/// kinds of streams
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct MediaStream {
+    /// Stream index as ffmpeg's `-map 0:<index>` expects it
+    pub index: u32,
+    pub kind: StreamKind,
+    /// Short codec name, e.g. "h264", "aac"
+    pub codec: Option<String>,
+    pub codec_long_name: Option<String>,
+    /// Codec profile, e.g. "High" or "LC"
+    pub profile: Option<String>,
+    /// Bitrate in bits per second
+    pub bit_rate: Option<u64>,
+    /// Duration in seconds, where the container records it per stream
+    pub duration: Option<f64>,
+    /// ISO 639-2 language code, e.g. "eng"
+    pub language: Option<String>,
+    pub title: Option<String>,
+    /// Whether players pick this stream by default
+    pub default: bool,
+    /// Cover art embedded in an audio file rather than real video
+    pub attached_pic: bool,
+
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Average frame rate
+    pub fps: Option<f64>,
+    /// Pixel format, e.g. "yuv420p" or "yuv420p10le"
+    pub pixel_format: Option<String>,
+    pub color_range: Option<String>,
+    pub color_space: Option<String>,
+    pub color_transfer: Option<String>,
+    pub color_primaries: Option<String>,
+    /// "HDR10", "HDR10+", "HLG" or "Dolby Vision"; `None` for SDR
+    pub hdr: Option<String>,
+    /// Clockwise rotation in degrees applied on playback: 0, 90, 180 or 270
+    pub rotation: u32,
+
+    pub channels: Option<u32>,
+    /// Channel layout, e.g. "stereo" or "5.1(side)"
+    pub channel_layout: Option<String>,
+    /// Sample rate in Hz
+    pub sample_rate: Option<u32>,
+
+    pub tags: BTreeMap<String, String>,
+}
+
+/// A chapter marker
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct Chapter {
+    /// Start in seconds
+    pub start: f64,
+    /// End in seconds
+    pub end: f64,
+    pub title: Option<String>,
+}
+
+impl MediaInfo {
+    /// First real video stream; cover art embedded in audio files doesn't
+    /// count
+    pub fn video(&self) -> Option<&MediaStream> {
+        self.streams
+            .iter()
+            .find(|s| s.kind == StreamKind::Video && !s.attached_pic)
+    }
+
+    /// First audio stream
+    pub fn audio(&self) -> Option<&MediaStream> {
+        self.streams.iter().find(|s| s.kind == StreamKind::Audio)
+    }
+
+    /// One-line summary of the file, e.g. "QuickTime / MOV, 03:32, 45.2 MB"
+    pub fn describe(&self) -> String {
+        let mut parts = vec![self
+            .container_long_name
+            .clone()
+            .unwrap_or_else(|| self.container.clone())];
+        if let Some(duration) = self.duration {
+            parts.push(format_duration(duration));
+        }
+        if let Some(size) = self.size {
+            parts.push(format_bytes(size));
+        }
+        if let Some(bit_rate) = self.bit_rate {
+            parts.push(format!("{} kb/s", bit_rate / 1000));
+        }
+        parts.join(", ")
+    }
+}
+
+impl MediaStream {
+    /// One-line summary, e.g. "h264 (High), 1920x1080, 29.97 fps, yuv420p"
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+
+        let codec = self.codec.as_deref().unwrap_or("unknown");
+        parts.push(match &self.profile {
+            Some(profile) => format!("{} ({})", codec, profile),
+            None => codec.to_string(),
+        });
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            parts.push(format!("{}x{}", width, height));
+        }
+        if let Some(fps) = self.fps {
+            parts.push(format!("{} fps", (fps * 100.0).round() / 100.0));
+        }
+        if let Some(pixel_format) = &self.pixel_format {
+            parts.push(pixel_format.clone());
+        }
+        if let Some(hdr) = &self.hdr {
+            parts.push(hdr.clone());
+        }
+        if self.rotation != 0 {
+            parts.push(format!("rotated {}°", self.rotation));
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            parts.push(format!("{} Hz", sample_rate));
+        }
+        match (&self.channel_layout, self.channels) {
+            (Some(layout), _) => parts.push(layout.clone()),
+            (None, Some(channels)) => parts.push(format!("{} channels", channels)),
+            (None, None) => {}
+        }
+        if let Some(bit_rate) = self.bit_rate {
+            parts.push(format!("{} kb/s", bit_rate / 1000));
+        }
+        if let Some(language) = &self.language {
+            parts.push(format!("[{}]", language));
+        }
+        if self.attached_pic {
+            parts.push("cover art".to_string());
+        }
+
+        parts.join(", ")
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct RawProbe {
+    #[serde(default)]
+    format: RawFormat,
+    #[serde(default)]
+    streams: Vec<RawStream>,
+    #[serde(default)]
+    chapters: Vec<RawChapter>,
+}
+
+#[derive(Deserialize, Default)]
+struct RawFormat {
+    format_name: Option<String>,
+    format_long_name: Option<String>,
+    duration: Option<String>,
+    size: Option<String>,
+    bit_rate: Option<String>,
+    #[serde(default)]
+    tags: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawStream {
+    index: u32,
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    codec_long_name: Option<String>,
+    profile: Option<String>,
+    bit_rate: Option<String>,
+    duration: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    avg_frame_rate: Option<String>,
+    r_frame_rate: Option<String>,
+    pix_fmt: Option<String>,
+    color_range: Option<String>,
+    color_space: Option<String>,
+    color_transfer: Option<String>,
+    color_primaries: Option<String>,
+    channels: Option<u32>,
+    channel_layout: Option<String>,
+    sample_rate: Option<String>,
+    disposition: RawDisposition,
+    tags: BTreeMap<String, serde_json::Value>,
+    side_data_list: Vec<RawSideData>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawDisposition {
+    default: u8,
+    attached_pic: u8,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawSideData {
+    side_data_type: String,
+    rotation: Option<f64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawChapter {
+    start_time: Option<String>,
+    end_time: Option<String>,
+    tags: BTreeMap<String, serde_json::Value>,
+}
+
+/// Parse the JSON printed by `ffprobe -show_format -show_streams
+/// -show_chapters -of json`
+pub fn parse_media_info(json: &[u8]) -> Result<MediaInfo> {
+    let raw: RawProbe = serde_json::from_slice(json)
+        .map_err(|e| AppError::ProbeError(format!("Unreadable ffprobe output: {}", e)))?;
+
+    Ok(MediaInfo {
+        container: raw.format.format_name.unwrap_or_default(),
+        container_long_name: raw.format.format_long_name,
+        duration: parse_number(raw.format.duration.as_deref()),
+        bit_rate: parse_number(raw.format.bit_rate.as_deref()),
+        size: parse_number(raw.format.size.as_deref()),
+        streams: raw.streams.into_iter().map(parse_stream).collect(),
+        chapters: raw
+            .chapters
+            .into_iter()
+            .map(|chapter| {
+                let tags = string_tags(chapter.tags);
+                Chapter {
+                    start: parse_number(chapter.start_time.as_deref()).unwrap_or(0.0),
+                    end: parse_number(chapter.end_time.as_deref()).unwrap_or(0.0),
+                    title: tag(&tags, "title"),
+                }
+            })
+            .collect(),
+        tags: string_tags(raw.format.tags),
+    })
+}
+
+fn parse_stream(raw: RawStream) -> MediaStream {
+    let tags = string_tags(raw.tags);
+    let kind = match raw.codec_type.as_deref() {
+        Some("video") => StreamKind::Video,
+        Some("audio") => StreamKind::Audio,
+        Some("subtitle") => StreamKind::Subtitle,
+        Some("data") => StreamKind::Data,
+        Some("attachment") => StreamKind::Attachment,
+        _ => StreamKind::Unknown,
+    };
+    let fps = parse_rate(raw.avg_frame_rate.as_deref())
+        .or_else(|| parse_rate(raw.r_frame_rate.as_deref()))
+        .filter(|_| kind == StreamKind::Video);
+    let hdr = hdr_format(raw.color_transfer.as_deref(), &raw.side_data_list);
+    let rotation = rotation(&raw.side_data_list, &tags);
+
+    MediaStream {
+        index: raw.index,
+        kind,
+        codec: raw.codec_name,
+        codec_long_name: raw.codec_long_name,
+        profile: raw.profile,
+        bit_rate: parse_number(raw.bit_rate.as_deref()),
+        duration: parse_number(raw.duration.as_deref()),
+        language: tag(&tags, "language").filter(|l| l != "und"),
+        title: tag(&tags, "title"),
+        default: raw.disposition.default != 0,
+        attached_pic: raw.disposition.attached_pic != 0,
+        width: raw.width,
+        height: raw.height,
+        fps,
+        pixel_format: raw.pix_fmt,
+        color_range: raw.color_range,
+        color_space: raw.color_space,
+        color_transfer: raw.color_transfer,
+        color_primaries: raw.color_primaries,
+        hdr,
+        rotation,
+        channels: raw.channels,
+        channel_layout: raw.channel_layout,
+        sample_rate: parse_number(raw.sample_rate.as_deref()),
+        tags,
+    }
+}
+
+/// Tag values are strings in practice; anything else is kept as its JSON text
+fn string_tags(tags: BTreeMap<String, serde_json::Value>) -> BTreeMap<String, String> {
+    tags.into_iter()
+        .map(|(key, value)| match value {
+            serde_json::Value::String(value) => (key, value),
+            value => (key, value.to_string()),
+        })
+        .collect()
+}
+
+/// Look up a tag by name; Matroska writes some tag names in upper case
+fn tag(tags: &BTreeMap<String, String>, name: &str) -> Option<String> {
+    tags.iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// ffprobe prints numbers as strings, and "N/A" when it has none
+fn parse_number<T: std::str::FromStr>(value: Option<&str>) -> Option<T> {
+    value?.trim().parse().ok()
+}
+
+/// Parse a frame rate such as "30000/1001"; "0/0" means unknown
+fn parse_rate(value: Option<&str>) -> Option<f64> {
+    let (num, den) = value?.split_once('/')?;
+    let (num, den) = (num.parse::<f64>().ok()?, den.parse::<f64>().ok()?);
+    (num > 0.0 && den > 0.0).then(|| num / den)
+}
+
+/// HDR format from the transfer characteristics and the stream's side data
+fn hdr_format(transfer: Option<&str>, side_data: &[RawSideData]) -> Option<String> {
+    let has = |name: &str| side_data.iter().any(|s| s.side_data_type.starts_with(name));
+
+    let format = if has("DOVI configuration") {
+        "Dolby Vision"
+    } else {
+        match transfer? {
+            "smpte2084" if has("HDR Dynamic Metadata SMPTE2094-40") => "HDR10+",
+            "smpte2084" => "HDR10",
+            "arib-std-b67" => "HLG",
+            _ => return None,
+        }
+    };
+    Some(format.to_string())
+}
+
+/// Clockwise playback rotation from the display matrix, or from the older
+/// `rotate` tag
+fn rotation(side_data: &[RawSideData], tags: &BTreeMap<String, String>) -> u32 {
+    // The display matrix rotation is counter-clockwise
+    let degrees = side_data
+        .iter()
+        .find_map(|s| s.rotation)
+        .map(|r| -r)
+        .or_else(|| tag(tags, "rotate").and_then(|r| r.parse::<f64>().ok()))
+        .unwrap_or(0.0);
+
+    (degrees.round() as i64).rem_euclid(360) as u32
+}
+
+/// Run the ffprobe `settings` resolve to for everything it can report about
+/// a file
+pub async fn run_ffprobe(
+    path: &Path,
+    settings: &Settings,
+) -> std::io::Result<std::process::Output> {
+    let ffprobe_cmd = deps::resolve_command(Dependency::Ffprobe, settings);
+    let mut cmd = Command::new(&ffprobe_cmd);
+    cmd.args([
+        "-v",
+        "error",
+        "-print_format",
+        "json",
+        "-show_format",
+        "-show_streams",
+        "-show_chapters",
+    ])
+    .arg(path);
+
+    #[cfg(windows)]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+
+    cmd.output().await
+}
+
+/// Inspect a local media file
+pub async fn probe_media(path: &Path, settings: &Settings) -> Result<MediaInfo> {
+    if !path.is_file() {
+        return Err(AppError::ProbeError(format!(
+            "File not found: {}",
+            path.display()
+        )));
+    }
+
+    let output = run_ffprobe(path, settings)
+        .await
+        .map_err(|e| AppError::ProbeError(format!("Failed to run ffprobe: {}", e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::ProbeError(format!(
+            "ffprobe could not read {}: {}",
+            path.display(),
+            stderr.trim()
+        )));
+    }
+
+    parse_media_info(&output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    /// ffprobe output for a rotated HDR phone clip with an mp4 container
+    const PHONE_CLIP: &str = r#"{
+        "streams": [
+            {
+                "index": 0,
+                "codec_name": "hevc",
+                "codec_long_name": "H.265 / HEVC (High Efficiency Video Coding)",
+                "profile": "Main 10",
+                "codec_type": "video",
+                "width": 3840,
+                "height": 2160,
+                "pix_fmt": "yuv420p10le",
+                "color_range": "tv",
+                "color_space": "bt2020nc",
+                "color_transfer": "arib-std-b67",
+                "color_primaries": "bt2020",
+                "r_frame_rate": "30/1",
+                "avg_frame_rate": "30000/1001",
+                "duration": "12.512500",
+                "bit_rate": "48812345",
+                "disposition": {"default": 1, "attached_pic": 0},
+                "tags": {"language": "und", "handler_name": "Core Media Video"},
+                "side_data_list": [
+                    {"side_data_type": "Display Matrix", "displaymatrix": "...", "rotation": -90}
+                ]
+            },
+            {
+                "index": 1,
+                "codec_name": "aac",
+                "profile": "LC",
+                "codec_type": "audio",
+                "sample_rate": "48000",
+                "channels": 2,
+                "channel_layout": "stereo",
+                "r_frame_rate": "0/0",
+                "avg_frame_rate": "0/0",
+                "duration": "12.501333",
+                "bit_rate": "191999",
+                "disposition": {"default": 1, "attached_pic": 0},
+                "tags": {"language": "eng"}
+            }
+        ],
+        "chapters": [],
+        "format": {
+            "filename": "IMG_0042.MOV",
+            "nb_streams": 2,
+            "format_name": "mov,mp4,m4a,3gp,3g2,mj2",
+            "format_long_name": "QuickTime / MOV",
+            "duration": "12.512500",
+            "size": "76612345",
+            "bit_rate": "48983477",
+            "tags": {"major_brand": "qt  ", "com.apple.quicktime.make": "Apple"}
+        }
+    }"#;
+
+    /// ffprobe output for a Matroska file with chapters and upper-case tags
+    const CHAPTERED: &str = r#"{
+        "streams": [
+            {
+                "index": 0,
+                "codec_name": "vp9",
+                "profile": "Profile 2",
+                "codec_type": "video",
+                "width": 1920,
+                "height": 1080,
+                "pix_fmt": "yuv420p10le",
+                "color_transfer": "smpte2084",
+                "avg_frame_rate": "24/1",
+                "disposition": {"default": 1},
+                "tags": {"DURATION": "00:10:00.000000000"},
+                "side_data_list": [
+                    {"side_data_type": "Mastering display metadata"},
+                    {"side_data_type": "HDR Dynamic Metadata SMPTE2094-40 (HDR10+)"}
+                ]
+            },
+            {
+                "index": 1,
+                "codec_name": "opus",
+                "codec_type": "audio",
+                "sample_rate": "48000",
+                "channels": 6,
+                "channel_layout": "5.1",
+                "disposition": {"default": 1},
+                "tags": {"LANGUAGE": "ger", "title": "Surround"}
+            },
+            {
+                "index": 2,
+                "codec_name": "subrip",
+                "codec_type": "subtitle",
+                "avg_frame_rate": "0/0",
+                "disposition": {"default": 0},
+                "tags": {"language": "eng"}
+            }
+        ],
+        "chapters": [
+            {"id": 1, "time_base": "1/1000000000", "start": 0, "start_time": "0.000000", "end": 90000000000, "end_time": "90.000000", "tags": {"title": "Opening"}},
+            {"id": 2, "time_base": "1/1000000000", "start": 90000000000, "start_time": "90.000000", "end": 600000000000, "end_time": "600.000000", "tags": {}}
+        ],
+        "format": {
+            "format_name": "matroska,webm",
+            "format_long_name": "Matroska / WebM",
+            "duration": "600.000000",
+            "size": "N/A",
+            "tags": {"title": "Sample", "ENCODER": "Lavf60.16.100"}
+        }
+    }"#;
+
+    fn info(json: &str) -> MediaInfo {
+        parse_media_info(json.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_phone_clip() {
+        let info = info(PHONE_CLIP);
+        assert_eq!(info.container, "mov,mp4,m4a,3gp,3g2,mj2");
+        assert_eq!(info.container_long_name.as_deref(), Some("QuickTime / MOV"));
+        assert_eq!(info.duration, Some(12.5125));
+        assert_eq!(info.size, Some(76_612_345));
+        assert_eq!(info.bit_rate, Some(48_983_477));
+        assert_eq!(info.tags["com.apple.quicktime.make"], "Apple");
+
+        let video = info.video().unwrap();
+        assert_eq!(video.codec.as_deref(), Some("hevc"));
+        assert_eq!(video.profile.as_deref(), Some("Main 10"));
+        assert_eq!((video.width, video.height), (Some(3840), Some(2160)));
+        assert!((video.fps.unwrap() - 29.97).abs() < 0.01);
+        assert_eq!(video.pixel_format.as_deref(), Some("yuv420p10le"));
+        assert_eq!(video.color_primaries.as_deref(), Some("bt2020"));
+        assert_eq!(video.hdr.as_deref(), Some("HLG"));
+        assert_eq!(video.rotation, 90);
+        // "und" is no language
+        assert_eq!(video.language, None);
+        assert!(video.default);
+
+        let audio = info.audio().unwrap();
+        assert_eq!(audio.kind, StreamKind::Audio);
+        assert_eq!(audio.sample_rate, Some(48000));
+        assert_eq!(audio.channels, Some(2));
+        assert_eq!(audio.language.as_deref(), Some("eng"));
+        assert_eq!(audio.fps, None);
+        assert_eq!(audio.rotation, 0);
+    }
+
+    #[test]
+    fn test_parse_chapters_and_tags() {
+        let info = info(CHAPTERED);
+        assert_eq!(info.size, None);
+        assert_eq!(info.tags["title"], "Sample");
+
+        assert_eq!(
+            info.chapters,
+            vec![
+                Chapter {
+                    start: 0.0,
+                    end: 90.0,
+                    title: Some("Opening".to_string()),
+                },
+                Chapter {
+                    start: 90.0,
+                    end: 600.0,
+                    title: None,
+                },
+            ]
+        );
+
+        assert_eq!(info.video().unwrap().hdr.as_deref(), Some("HDR10+"));
+        let audio = info.audio().unwrap();
+        assert_eq!(audio.language.as_deref(), Some("ger"));
+        assert_eq!(audio.title.as_deref(), Some("Surround"));
+        assert_eq!(info.streams[2].kind, StreamKind::Subtitle);
+        assert!(!info.streams[2].default);
+    }
+
+    #[test]
+    fn test_parse_helpers() {
+        assert_eq!(
+            parse_rate(Some("30000/1001")).map(|r| r.round()),
+            Some(30.0)
+        );
+        assert_eq!(parse_rate(Some("0/0")), None);
+        assert_eq!(parse_rate(Some("25")), None);
+        assert_eq!(parse_number::<u64>(Some("N/A")), None);
+
+        let hdr = |transfer, side_data: &[&str]| {
+            let side_data: Vec<RawSideData> = side_data
+                .iter()
+                .map(|name| RawSideData {
+                    side_data_type: name.to_string(),
+                    rotation: None,
+                })
+                .collect();
+            hdr_format(transfer, &side_data)
+        };
+        assert_eq!(hdr(Some("smpte2084"), &[]).as_deref(), Some("HDR10"));
+        assert_eq!(hdr(Some("bt709"), &[]), None);
+        assert_eq!(
+            hdr(Some("smpte2084"), &["DOVI configuration record"]).as_deref(),
+            Some("Dolby Vision")
+        );
+        assert_eq!(hdr(None, &[]), None);
+
+        let tags = BTreeMap::from([("rotate".to_string(), "270".to_string())]);
+        assert_eq!(rotation(&[], &tags), 270);
+        let flipped = RawSideData {
+            rotation: Some(180.0),
+            ..Default::default()
+        };
+        assert_eq!(rotation(&[flipped], &BTreeMap::new()), 180);
+
+        assert!(parse_media_info(b"not json").is_err());
+        assert_eq!(parse_media_info(b"{}").unwrap(), MediaInfo::default());
+    }
+
+    #[test]
+    fn test_describe() {
+        let info = info(PHONE_CLIP);
+        assert_eq!(
+            info.describe(),
+            format!(
+                "QuickTime / MOV, 00:12, {}, 48983 kb/s",
+                format_bytes(76_612_345)
+            )
+        );
+        assert_eq!(
+            info.streams[0].describe(),
+            "hevc (Main 10), 3840x2160, 29.97 fps, yuv420p10le, HLG, rotated 90°, 48812 kb/s"
+        );
+        assert_eq!(
+            info.streams[1].describe(),
+            "aac (LC), 48000 Hz, stereo, 191 kb/s, [eng]"
+        );
+    }
+
+    /// Generate a small fixture from `inputs` and ffmpeg's test sources
+    async fn generate(output: &Path, inputs: &[&Path], args: &str) {
+        let ffmpeg_cmd = deps::get_ffmpeg_command(&Settings::default()).await;
+        let mut cmd = Command::new(&ffmpeg_cmd);
+        cmd.args(["-y", "-v", "error"]);
+        for input in inputs {
+            cmd.arg("-i").arg(input);
+        }
+        let status = cmd.args(args.split_whitespace()).arg(output).status().await;
+
+        assert!(
+            status.as_ref().is_ok_and(|status| status.success()),
+            "Could not generate {}: {:?}",
+            output.display(),
+            status
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs ffmpeg; run with `cargo test -- --ignored`"]
+    async fn test_probe_generated_video() {
+        let dir = test_support::temp_dir("probe-video");
+        let chapters = dir.join("chapters.txt");
+        std::fs::write(
+            &chapters,
+            ";FFMETADATA1\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=1000\ntitle=Intro\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=1000\nEND=2000\ntitle=Main\n",
+        )
+        .unwrap();
+        let output = dir.join("fixture.mkv");
+
+        // The chapters file is input 0, the test sources 1 and 2
+        generate(
+            &output,
+            &[&chapters],
+            "-f lavfi -i testsrc=size=320x240:rate=25:duration=2 \
+             -f lavfi -i sine=frequency=440:sample_rate=44100:duration=2 \
+             -map 1:v -map 2:a -map_chapters 0 -c:v mpeg4 -c:a aac -ac 2 \
+             -metadata title=Fixture -metadata:s:a:0 language=eng",
+        )
+        .await;
+
+        let info = probe_media(&output, &Settings::default()).await.unwrap();
+        assert!(info.container.contains("matroska"), "{}", info.container);
+        assert!(
+            (info.duration.unwrap() - 2.0).abs() < 0.2,
+            "{:?}",
+            info.duration
+        );
+        assert_eq!(info.tags.get("title").map(String::as_str), Some("Fixture"));
+
+        let video = info.video().unwrap();
+        assert_eq!(video.codec.as_deref(), Some("mpeg4"));
+        assert_eq!((video.width, video.height), (Some(320), Some(240)));
+        assert_eq!(video.fps, Some(25.0));
+
+        let audio = info.audio().unwrap();
+        assert_eq!(audio.codec.as_deref(), Some("aac"));
+        assert_eq!(audio.sample_rate, Some(44100));
+        assert_eq!(audio.channels, Some(2));
+        assert_eq!(audio.language.as_deref(), Some("eng"));
+
+        let titles: Vec<_> = info.chapters.iter().map(|c| c.title.as_deref()).collect();
+        assert_eq!(titles, vec![Some("Intro"), Some("Main")]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs ffmpeg; run with `cargo test -- --ignored`"]
+    async fn test_probe_generated_audio() {
+        let dir = test_support::temp_dir("probe-audio");
+        let output = dir.join("fixture.wav");
+
+        generate(
+            &output,
+            &[],
+            "-f lavfi -i sine=sample_rate=8000:duration=1 -ac 1",
+        )
+        .await;
+
+        let info = probe_media(&output, &Settings::default()).await.unwrap();
+        assert!(info.video().is_none());
+        let audio = info.audio().unwrap();
+        assert_eq!(audio.codec.as_deref(), Some("pcm_s16le"));
+        assert_eq!((audio.channels, audio.sample_rate), (Some(1), Some(8000)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_probe_rejects_non_media() {
+        let dir = test_support::temp_dir("probe-invalid");
+        let text = dir.join("notes.mp4");
+        std::fs::write(&text, b"not a video").unwrap();
+
+        // Fails whether or not ffprobe is installed
+        assert!(probe_media(&text, &Settings::default()).await.is_err());
+        assert!(probe_media(&dir.join("missing.mp4"), &Settings::default())
+            .await
+            .is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,343 @@
+//! Reframing
+//!
+//! Turns a cut into another size or shape, such as a 16:9 clip into a 9:16
+//! or 1:1 one, with an ffmpeg filter graph in the re-encode path. The source
+//! is optionally cropped to a rectangle first, then fit into the output frame
+//! in one of three ways: scaled to cover it and cropped, scaled to fit with
+//! bars, or scaled to fit over a blurred copy of itself.
+
+use crate::error::{AppError, Result};
+use crate::probe::MediaInfo;
+use crate::types::{AspectPreset, FillMode, Reframe};
+
+/// Short side of the output when only an aspect ratio is given and the
+/// source's size is unknown
+const DEFAULT_SHORT_SIDE: u32 = 1080;
+
+/// Largest output side, in pixels
+const MAX_SIDE: u32 = 8192;
+
+/// Strength of the background blur for `FillMode::Blur`
+const BLUR: &str = "boxblur=20:2";
+
+impl AspectPreset {
+    /// Width and height of the ratio
+    fn ratio(self) -> (u32, u32) {
+        match self {
+            AspectPreset::Vertical => (9, 16),
+            AspectPreset::Square => (1, 1),
+            AspectPreset::Portrait => (4, 5),
+        }
+    }
+}
+
+/// Size of the first video stream as it is displayed, with rotation applied
+pub fn display_size(media: &MediaInfo) -> Option<(u32, u32)> {
+    let video = media.video()?;
+    let (width, height) = (video.width?, video.height?);
+    if video.rotation % 180 == 90 {
+        Some((height, width))
+    } else {
+        Some((width, height))
+    }
+}
+
+/// Filter graph for reframing a cut whose output keeps the streams of
+/// `output_media`, if it is known; `None` without a reframe
+pub fn video_filter(
+    reframe: Option<&Reframe>,
+    output_media: Option<&MediaInfo>,
+) -> Result<Option<String>> {
+    let Some(reframe) = reframe else {
+        return Ok(None);
+    };
+    if output_media.is_some_and(|media| media.video().is_none()) {
+        return Err(AppError::InvalidReframe(
+            "The output has no video to reframe".to_string(),
+        ));
+    }
+
+    filter_graph(reframe, output_media.and_then(display_size)).map(Some)
+}
+
+/// Output size in pixels for `reframe` of a source of size `source`,
+/// rounded to even numbers as the encoders need
+pub fn output_size(reframe: &Reframe, source: Option<(u32, u32)>) -> Result<(u32, u32)> {
+    if reframe
+        .crop
+        .is_some_and(|crop| crop.width == 0 || crop.height == 0)
+    {
+        return Err(AppError::InvalidReframe(
+            "The crop rectangle is empty".to_string(),
+        ));
+    }
+
+    // A crop is the picture everything else is computed from
+    let base = match reframe.crop {
+        Some(crop) => Some((crop.width, crop.height)),
+        None => source,
+    };
+    let scaled = |side: u32, num: u32, den: u32| (side as u64 * num as u64 / den as u64) as u32;
+
+    let (width, height) = match (reframe.width, reframe.height, reframe.aspect) {
+        (Some(width), Some(height), _) => (width, height),
+        (Some(width), None, Some(aspect)) => {
+            let (w, h) = aspect.ratio();
+            (width, scaled(width, h, w))
+        }
+        (None, Some(height), Some(aspect)) => {
+            let (w, h) = aspect.ratio();
+            (scaled(height, w, h), height)
+        }
+        (Some(width), None, None) => {
+            let (w, h) = base.ok_or_else(|| unknown_size("a height"))?;
+            (width, scaled(width, h, w))
+        }
+        (None, Some(height), None) => {
+            let (w, h) = base.ok_or_else(|| unknown_size("a width"))?;
+            (scaled(height, w, h), height)
+        }
+        (None, None, Some(aspect)) => {
+            // Keep the source's short side, so 1920×1080 becomes 1080×1920
+            let short = base.map_or(DEFAULT_SHORT_SIDE, |(w, h)| w.min(h));
+            let (w, h) = aspect.ratio();
+            if w <= h {
+                (short, scaled(short, h, w))
+            } else {
+                (scaled(short, w, h), short)
+            }
+        }
+        // Only a crop says what to make without a size or shape
+        (None, None, None) => reframe
+            .crop
+            .map(|crop| (crop.width, crop.height))
+            .ok_or_else(|| {
+                AppError::InvalidReframe("Set an aspect ratio, a size or a crop".to_string())
+            })?,
+    };
+
+    let even = |side: u32| side / 2 * 2;
+    let (width, height) = (even(width), even(height));
+    if width < 2 || height < 2 || width > MAX_SIDE || height > MAX_SIDE {
+        return Err(AppError::InvalidReframe(format!(
+            "An output of {}×{} is out of range: sides go from 2 to {} pixels",
+            width, height, MAX_SIDE
+        )));
+    }
+    Ok((width, height))
+}
+
+/// ffmpeg filter graph for the video of a source of size `source`
+pub fn filter_graph(reframe: &Reframe, source: Option<(u32, u32)>) -> Result<String> {
+    let (width, height) = output_size(reframe, source)?;
+    let mut graph = String::new();
+
+    if let Some(crop) = reframe.crop {
+        if let Some((source_width, source_height)) = source {
+            if crop.x.saturating_add(crop.width) > source_width
+                || crop.y.saturating_add(crop.height) > source_height
+            {
+                return Err(AppError::InvalidReframe(format!(
+                    "The crop rectangle reaches outside the {}×{} picture",
+                    source_width, source_height
+                )));
+            }
+        }
+        graph.push_str(&format!(
+            "crop={}:{}:{}:{},",
+            crop.width, crop.height, crop.x, crop.y
+        ));
+    }
+
+    let cover = format!(
+        "scale={}:{}:force_original_aspect_ratio=increase,crop={}:{}",
+        width, height, width, height
+    );
+    let fit = format!(
+        "scale={}:{}:force_original_aspect_ratio=decrease",
+        width, height
+    );
+    match reframe.fill {
+        FillMode::Crop => graph.push_str(&cover),
+        FillMode::Pad => {
+            let color = pad_color(reframe.pad_color.as_deref())?;
+            graph.push_str(&format!(
+                "{},pad={}:{}:(ow-iw)/2:(oh-ih)/2:color={}",
+                fit, width, height, color
+            ));
+        }
+        FillMode::Blur => graph.push_str(&format!(
+            "split=2[bg][fg];[bg]{},{}[back];[fg]{}[front];[back][front]overlay=(W-w)/2:(H-h)/2",
+            cover, BLUR, fit
+        )),
+    }
+
+    // Square pixels, so players show the frame at the size it was made
+    graph.push_str(",setsar=1");
+    Ok(graph)
+}
+
+/// Check a pad color before it goes into a filter graph: a color name or
+/// a hex value
+fn pad_color(color: Option<&str>) -> Result<&str> {
+    let color = color.map(str::trim).unwrap_or("black");
+    let hex = color
+        .strip_prefix('#')
+        .or_else(|| color.strip_prefix("0x"))
+        .map(|hex| matches!(hex.len(), 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit()));
+    let valid = match hex {
+        Some(valid) => valid,
+        None => !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic()),
+    };
+
+    if valid {
+        Ok(color)
+    } else {
+        Err(AppError::InvalidReframe(format!(
+            "`{}` is not a color name or hex color",
+            color
+        )))
+    }
+}
+
+fn unknown_size(missing: &str) -> AppError {
+    AppError::InvalidReframe(format!(
+        "The source size is unknown; set {} or an aspect ratio too",
+        missing
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe::{MediaStream, StreamKind};
+    use crate::types::CropRect;
+
+    const HD: Option<(u32, u32)> = Some((1920, 1080));
+
+    fn reframe(aspect: AspectPreset, fill: FillMode) -> Reframe {
+        Reframe {
+            aspect: Some(aspect),
+            fill,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_output_size() {
+        let vertical = reframe(AspectPreset::Vertical, FillMode::Crop);
+        assert_eq!(output_size(&vertical, HD).unwrap(), (1080, 1920));
+        assert_eq!(output_size(&vertical, None).unwrap(), (1080, 1920));
+
+        let square = reframe(AspectPreset::Square, FillMode::Crop);
+        assert_eq!(output_size(&square, Some((1280, 720))).unwrap(), (720, 720));
+
+        let portrait = Reframe {
+            width: Some(1080),
+            ..reframe(AspectPreset::Portrait, FillMode::Crop)
+        };
+        assert_eq!(output_size(&portrait, HD).unwrap(), (1080, 1350));
+
+        // One side keeps the source's shape, rounded to even
+        let half = Reframe {
+            height: Some(541),
+            ..Default::default()
+        };
+        assert_eq!(output_size(&half, HD).unwrap(), (960, 540));
+        assert!(output_size(&half, None).is_err());
+
+        // A crop alone keeps its size
+        let crop = Reframe {
+            crop: Some(CropRect {
+                x: 420,
+                y: 0,
+                width: 1080,
+                height: 1080,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(output_size(&crop, HD).unwrap(), (1080, 1080));
+
+        assert!(output_size(&Reframe::default(), HD).is_err());
+        let huge = Reframe {
+            width: Some(10_000),
+            ..Default::default()
+        };
+        assert!(output_size(&huge, HD).is_err());
+    }
+
+    #[test]
+    fn test_filter_graph() {
+        let graph = |reframe: &Reframe| filter_graph(reframe, HD).unwrap();
+
+        assert_eq!(
+            graph(&reframe(AspectPreset::Square, FillMode::Crop)),
+            "scale=1080:1080:force_original_aspect_ratio=increase,crop=1080:1080,setsar=1"
+        );
+        assert_eq!(
+            graph(&Reframe {
+                pad_color: Some("#1a1a1a".to_string()),
+                ..reframe(AspectPreset::Vertical, FillMode::Pad)
+            }),
+            "scale=1080:1920:force_original_aspect_ratio=decrease,\
+             pad=1080:1920:(ow-iw)/2:(oh-ih)/2:color=#1a1a1a,setsar=1"
+        );
+        assert_eq!(
+            graph(&reframe(AspectPreset::Vertical, FillMode::Blur)),
+            "split=2[bg][fg];\
+             [bg]scale=1080:1920:force_original_aspect_ratio=increase,crop=1080:1920,boxblur=20:2[back];\
+             [fg]scale=1080:1920:force_original_aspect_ratio=decrease[front];\
+             [back][front]overlay=(W-w)/2:(H-h)/2,setsar=1"
+        );
+
+        let cropped = Reframe {
+            crop: Some(CropRect {
+                x: 100,
+                y: 50,
+                width: 800,
+                height: 600,
+            }),
+            ..reframe(AspectPreset::Square, FillMode::Crop)
+        };
+        assert!(graph(&cropped).starts_with("crop=800:600:100:50,scale=600:600:"));
+
+        let outside = Reframe {
+            crop: Some(CropRect {
+                x: 1800,
+                y: 0,
+                width: 200,
+                height: 200,
+            }),
+            ..Default::default()
+        };
+        assert!(filter_graph(&outside, HD).is_err());
+    }
+
+    #[test]
+    fn test_pad_color() {
+        assert_eq!(pad_color(None).unwrap(), "black");
+        assert_eq!(pad_color(Some("white")).unwrap(), "white");
+        assert_eq!(pad_color(Some("0xFF0000")).unwrap(), "0xFF0000");
+        assert!(pad_color(Some("#12345")).is_err());
+        assert!(pad_color(Some("red:x=1")).is_err());
+        assert!(pad_color(Some("black;[in]")).is_err());
+    }
+
+    #[test]
+    fn test_display_size() {
+        let mut video = MediaStream {
+            kind: StreamKind::Video,
+            width: Some(1920),
+            height: Some(1080),
+            rotation: 90,
+            ..Default::default()
+        };
+        let media = |video: &MediaStream| MediaInfo {
+            streams: vec![video.clone()],
+            ..Default::default()
+        };
+        assert_eq!(display_size(&media(&video)), Some((1080, 1920)));
+        video.rotation = 180;
+        assert_eq!(display_size(&media(&video)), Some((1920, 1080)));
+    }
+}
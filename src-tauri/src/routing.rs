@@ -0,0 +1,277 @@
+//! Output folder rules
+//!
+//! Sorts outputs into subfolders of the output directory by platform,
+//! uploader, mode or date, e.g. `Music/{uploader}` for SoundCloud audio or
+//! `Clips/{platform}/{yyyy-mm}` for cuts. Folders use the fields of the
+//! filename template and are rendered one path component at a time, so a
+//! title or uploader can neither add a level nor climb out of the output
+//! directory. Paths are only computed here, so suggesting one for a save
+//! dialog leaves the disk alone; the folders are created when a job starts.
+
+use crate::error::{AppError, Result};
+use crate::filename::{self, TemplateFields};
+use crate::settings::{OutputRule, Settings};
+use crate::types::DownloadMode;
+use std::path::{Path, PathBuf};
+
+fn invalid(folder: &str, reason: &str) -> AppError {
+    AppError::InvalidOutputFolder(format!("{} in \"{}\"", reason, folder))
+}
+
+/// Check a rule's folder: relative, no `.` or `..` components and valid
+/// template fields
+pub fn validate_folder(folder: &str) -> Result<()> {
+    if folder.starts_with(['/', '\\']) || folder.get(1..2) == Some(":") {
+        return Err(invalid(folder, "Folder must be relative"));
+    }
+
+    for component in folder.split(['/', '\\']) {
+        if matches!(component.trim(), "." | "..") {
+            return Err(invalid(folder, "'.' and '..' are not allowed"));
+        }
+        filename::validate_template(component)?;
+    }
+
+    Ok(())
+}
+
+/// First rule matching an output, if any
+pub fn matching_rule<'a>(
+    rules: &'a [OutputRule],
+    fields: &TemplateFields,
+    mode: &DownloadMode,
+) -> Option<&'a OutputRule> {
+    let is_cut = fields.start.is_some() || fields.end.is_some();
+
+    rules.iter().find(|rule| {
+        rule.platform
+            .as_ref()
+            .is_none_or(|platform| fields.platform.as_ref() == Some(platform))
+            && rule.mode.as_ref().is_none_or(|m| m == mode)
+            && rule.uploader.as_deref().is_none_or(|uploader| {
+                fields
+                    .uploader
+                    .as_deref()
+                    .is_some_and(|u| u.trim().eq_ignore_ascii_case(uploader))
+            })
+            && rule.cut.is_none_or(|cut| cut == is_cut)
+    })
+}
+
+/// Render a rule's folder into a relative path; components whose fields are
+/// all empty are left out
+pub fn render_folder(folder: &str, fields: &TemplateFields) -> Result<PathBuf> {
+    validate_folder(folder)?;
+
+    let mut path = PathBuf::new();
+    for component in folder.split(['/', '\\']) {
+        let component = filename::render_component(component, fields)?;
+        if !component.is_empty() {
+            path.push(component);
+        }
+    }
+
+    Ok(path)
+}
+
+/// Directory an output goes to: `base`, or the folder of the first matching
+/// rule inside it, which may not exist yet
+pub fn output_dir(
+    base: &Path,
+    fields: &TemplateFields,
+    mode: &DownloadMode,
+    settings: &Settings,
+) -> Result<PathBuf> {
+    let Some(rule) = matching_rule(&settings.output_rules, fields, mode) else {
+        return Ok(base.to_path_buf());
+    };
+
+    let folder = render_folder(&rule.folder, fields)?;
+    if folder.as_os_str().is_empty() {
+        return Ok(base.to_path_buf());
+    }
+    inside(base, &folder)
+}
+
+/// Path for an output in `base`, routed by the rules and named by the
+/// filename template
+pub fn output_path(
+    base: &Path,
+    fields: &TemplateFields,
+    mode: &DownloadMode,
+    settings: &Settings,
+) -> Result<PathBuf> {
+    let dir = output_dir(base, fields, mode, settings)?;
+    filename::output_path(&dir, fields, mode.extension(), settings)
+}
+
+/// `base/folder`, making sure it does not lead outside `base`
+fn inside(base: &Path, folder: &Path) -> Result<PathBuf> {
+    let dir = base.join(folder);
+
+    // A symlink inside the output directory could still lead elsewhere; only
+    // the part that exists so far can hold one
+    let Ok(canonical_base) = base.canonicalize() else {
+        return Ok(dir);
+    };
+    let existing = dir.ancestors().find(|path| path.exists()).unwrap_or(base);
+    let inside = existing
+        .canonicalize()
+        .is_ok_and(|existing| existing.starts_with(&canonical_base));
+    if !inside {
+        return Err(AppError::InvalidOutputFolder(format!(
+            "{} is outside the output directory",
+            dir.display()
+        )));
+    }
+
+    Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use crate::types::Platform;
+
+    fn fields(platform: Platform, uploader: &str) -> TemplateFields {
+        TemplateFields {
+            title: "Track".to_string(),
+            uploader: Some(uploader.to_string()),
+            platform: Some(platform),
+            ..Default::default()
+        }
+    }
+
+    fn rules() -> Vec<OutputRule> {
+        vec![
+            OutputRule {
+                platform: Some(Platform::SoundCloud),
+                mode: Some(DownloadMode::AudioOnly),
+                folder: "Music/{uploader}".to_string(),
+                ..Default::default()
+            },
+            OutputRule {
+                uploader: Some("Rick Astley".to_string()),
+                folder: "Rick".to_string(),
+                ..Default::default()
+            },
+            OutputRule {
+                cut: Some(true),
+                folder: "Clips/{platform}/{yyyy-mm}".to_string(),
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_matching_rule() {
+        let rules = rules();
+        let audio = DownloadMode::AudioOnly;
+        let video = DownloadMode::VideoWithAudio;
+
+        let soundcloud = fields(Platform::SoundCloud, "artist");
+        assert_eq!(
+            matching_rule(&rules, &soundcloud, &audio).unwrap().folder,
+            "Music/{uploader}"
+        );
+
+        let youtube = fields(Platform::YouTube, "rick astley");
+        assert_eq!(
+            matching_rule(&rules, &youtube, &video).unwrap().folder,
+            "Rick"
+        );
+
+        let mut clip = fields(Platform::YouTube, "someone");
+        assert!(matching_rule(&rules, &clip, &video).is_none());
+        clip.end = Some(30.0);
+        assert_eq!(
+            matching_rule(&rules, &clip, &video).unwrap().folder,
+            "Clips/{platform}/{yyyy-mm}"
+        );
+    }
+
+    #[test]
+    fn test_render_folder() {
+        let mut fields = fields(Platform::YouTube, "Some/Channel");
+        fields.date = time::Date::from_calendar_date(2024, time::Month::July, 1).ok();
+        assert_eq!(
+            render_folder("Clips/{platform}/{yyyy-mm}", &fields).unwrap(),
+            Path::new("Clips").join("youtube").join("2024-07")
+        );
+
+        // Field values can't add levels or climb out
+        assert_eq!(
+            render_folder("Music/{uploader}", &fields).unwrap(),
+            Path::new("Music").join("Some_Channel")
+        );
+        for uploader in ["..", "../..", "..\\..", " . "] {
+            fields.uploader = Some(uploader.to_string());
+            let folder = render_folder("{uploader}", &fields).unwrap();
+            assert!(
+                folder
+                    .components()
+                    .all(|c| matches!(c, std::path::Component::Normal(_))),
+                "{:?} -> {:?}",
+                uploader,
+                folder
+            );
+        }
+
+        // Empty components are skipped
+        fields.uploader = None;
+        assert_eq!(
+            render_folder("Music/{uploader}", &fields).unwrap(),
+            Path::new("Music")
+        );
+    }
+
+    #[test]
+    fn test_validate_folder() {
+        for folder in [
+            "/abs", "\\abs", "C:/abs", "a/../b", "./a", "a/{nope}", "{title",
+        ] {
+            assert!(validate_folder(folder).is_err(), "accepted {:?}", folder);
+        }
+        for folder in ["", "Music", "Music/{uploader}", "a\\b", "{yyyy}/{mm}"] {
+            assert!(validate_folder(folder).is_ok(), "rejected {:?}", folder);
+        }
+    }
+
+    #[test]
+    fn test_output_path_leaves_disk_alone() {
+        let base = test_support::temp_dir("routing");
+        let settings = Settings {
+            output_rules: rules(),
+            ..Settings::default()
+        };
+
+        let fields = fields(Platform::SoundCloud, "Artist");
+        let path = output_path(&base, &fields, &DownloadMode::AudioOnly, &settings).unwrap();
+        assert_eq!(path, base.join("Music").join("Artist").join("Track.mp3"));
+        assert!(!base.join("Music").exists());
+
+        // Nothing matches, so the file stays in the base directory
+        let path = output_path(&base, &fields, &DownloadMode::VideoWithAudio, &settings).unwrap();
+        assert_eq!(path, base.join("Track.mp4"));
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_escape_is_rejected() {
+        let base = std::env::temp_dir().join(format!("dlcut-routing-link-{}", std::process::id()));
+        let outside = base.with_extension("outside");
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, base.join("Music")).unwrap();
+
+        let result = inside(&base, Path::new("Music/Artist"));
+        assert!(matches!(result, Err(AppError::InvalidOutputFolder(_))));
+        assert!(inside(&base, Path::new("Videos/Artist")).is_ok());
+
+        std::fs::remove_dir_all(&base).unwrap();
+        std::fs::remove_dir_all(&outside).unwrap();
+    }
+}
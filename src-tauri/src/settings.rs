@@ -0,0 +1,354 @@
+//! User settings
+//!
+//! Settings are stored as JSON next to the dependency directory
+//! (`DLCut/settings.json`) and loaded on demand, so every module sees
+//! the same values without threading state through each call.
+
+use crate::error::{AppError, Result};
+use crate::filename;
+use crate::routing;
+use crate::types::{DownloadMode, Platform};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Upper bound for parallel fragment downloads
+pub const MAX_CONCURRENT_FRAGMENTS: u32 = 16;
+
+/// Loopback port of the automation API unless configured otherwise
+pub const DEFAULT_AUTOMATION_PORT: u16 = 47815;
+
+/// yt-dlp release channel used for installs and updates
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateChannel {
+    /// Tagged releases
+    #[default]
+    Stable,
+    /// Daily builds with the latest extractor fixes
+    Nightly,
+    /// Builds of every commit to master
+    Master,
+}
+
+impl UpdateChannel {
+    /// GitHub repository publishing this channel's builds
+    pub fn repo(&self) -> &'static str {
+        match self {
+            UpdateChannel::Stable => "yt-dlp/yt-dlp",
+            UpdateChannel::Nightly => "yt-dlp/yt-dlp-nightly-builds",
+            UpdateChannel::Master => "yt-dlp/yt-dlp-master-builds",
+        }
+    }
+}
+
+/// Order in which bundled (`DLCut/bin`) and system (PATH) binaries are tried
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolutionPolicy {
+    /// Prefer binaries installed by DLCut, fall back to PATH
+    #[default]
+    BundledFirst,
+    /// Prefer binaries on PATH, fall back to those installed by DLCut
+    SystemFirst,
+    /// Only use binaries installed by DLCut
+    BundledOnly,
+}
+
+/// What to do when an output file already exists
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    /// Replace the existing file
+    Overwrite,
+    /// Leave the existing file and don't start the job
+    Skip,
+    /// Add a numbered suffix: `name (2).mp4`, `name (3).mp4`...
+    #[default]
+    Number,
+}
+
+/// Routes matching outputs into a subfolder of the output directory
+///
+/// Rules are tried in order and the first whose conditions all match wins;
+/// a rule without conditions matches everything.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct OutputRule {
+    /// Only match downloads from this platform
+    pub platform: Option<Platform>,
+    /// Only match audio or video downloads
+    pub mode: Option<DownloadMode>,
+    /// Only match this uploader (case-insensitive)
+    pub uploader: Option<String>,
+    /// Only match downloads cut to a range (`true`) or whole ones (`false`)
+    pub cut: Option<bool>,
+    /// Folder under the output directory, e.g. `Music/{uploader}`, using the
+    /// fields of the filename template
+    pub folder: String,
+}
+
+/// Explicit binary locations that take precedence over the resolution policy
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct BinaryPaths {
+    pub ytdlp: Option<String>,
+    pub ffmpeg: Option<String>,
+    pub ffprobe: Option<String>,
+    pub aria2c: Option<String>,
+    pub deno: Option<String>,
+}
+
+/// Persistent user settings
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// Number of HLS/DASH fragments yt-dlp downloads in parallel (1 = sequential)
+    pub concurrent_fragments: u32,
+    /// Use aria2c as yt-dlp's external downloader when it is available
+    pub use_aria2c: bool,
+    /// Release channel yt-dlp is installed and updated from
+    pub ytdlp_channel: UpdateChannel,
+    /// Base URL of an internal mirror used instead of the public release hosts
+    ///
+    /// The mirror serves each file under its original host and path, e.g.
+    /// `<mirror>/github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp`
+    /// (the layout `wget --force-directories` produces).
+    pub mirror_url: Option<String>,
+    /// How binaries are chosen when no explicit path is pinned
+    pub resolution_policy: ResolutionPolicy,
+    /// Pinned binary paths, used as-is even if they are broken
+    pub binary_paths: BinaryPaths,
+    /// Serve the local automation API while the app is running
+    pub automation_api: bool,
+    /// Loopback port the automation API listens on
+    pub automation_port: u16,
+    /// Bearer token required by the automation API, generated on first start
+    pub automation_token: Option<String>,
+    /// Template for output file names, see `filename::FIELDS`
+    pub filename_template: String,
+    /// What to do when an output file already exists
+    pub collision_policy: CollisionPolicy,
+    /// Subfolders outputs are sorted into, see `routing`
+    pub output_rules: Vec<OutputRule>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            concurrent_fragments: 1,
+            use_aria2c: false,
+            ytdlp_channel: UpdateChannel::Stable,
+            mirror_url: None,
+            resolution_policy: ResolutionPolicy::BundledFirst,
+            binary_paths: BinaryPaths::default(),
+            automation_api: false,
+            automation_port: DEFAULT_AUTOMATION_PORT,
+            automation_token: None,
+            filename_template: filename::DEFAULT_TEMPLATE.to_string(),
+            collision_policy: CollisionPolicy::Number,
+            output_rules: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Clamp values into their supported ranges
+    pub fn normalized(mut self) -> Self {
+        self.concurrent_fragments = self.concurrent_fragments.clamp(1, MAX_CONCURRENT_FRAGMENTS);
+        self.mirror_url = self
+            .mirror_url
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty());
+
+        let non_empty =
+            |path: Option<String>| path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+        let paths = self.binary_paths;
+        self.binary_paths = BinaryPaths {
+            ytdlp: non_empty(paths.ytdlp),
+            ffmpeg: non_empty(paths.ffmpeg),
+            ffprobe: non_empty(paths.ffprobe),
+            aria2c: non_empty(paths.aria2c),
+            deno: non_empty(paths.deno),
+        };
+
+        // Privileged ports would need elevated rights to bind
+        if self.automation_port < 1024 {
+            self.automation_port = DEFAULT_AUTOMATION_PORT;
+        }
+        self.automation_token = non_empty(self.automation_token);
+
+        // A hand-edited file may hold a template that no longer parses
+        self.filename_template = self.filename_template.trim().to_string();
+        if self.filename_template.is_empty()
+            || filename::validate_template(&self.filename_template).is_err()
+        {
+            self.filename_template = filename::DEFAULT_TEMPLATE.to_string();
+        }
+
+        // Likewise, rules whose folder would be rejected are dropped
+        self.output_rules.retain_mut(|rule| {
+            rule.uploader = non_empty(rule.uploader.take());
+            rule.folder = rule.folder.trim().to_string();
+            routing::validate_folder(&rule.folder).is_ok()
+        });
+        self
+    }
+}
+
+/// Get the path of the settings file
+pub fn get_settings_path() -> Result<PathBuf> {
+    let base = dirs::data_local_dir()
+        .ok_or_else(|| AppError::SettingsError("Could not find local data directory".into()))?;
+
+    Ok(base.join("DLCut").join("settings.json"))
+}
+
+/// Load settings from disk, falling back to defaults if missing or unreadable
+pub fn load() -> Settings {
+    get_settings_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str::<Settings>(&contents).ok())
+        .unwrap_or_default()
+        .normalized()
+}
+
+/// Save settings to disk
+pub fn save(settings: &Settings) -> Result<()> {
+    let path = get_settings_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            AppError::SettingsError(format!("Failed to create settings directory: {}", e))
+        })?;
+    }
+
+    let contents = serde_json::to_string_pretty(settings)
+        .map_err(|e| AppError::SettingsError(format!("Failed to serialize settings: {}", e)))?;
+
+    std::fs::write(&path, contents)
+        .map_err(|e| AppError::SettingsError(format!("Failed to write settings: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let settings: Settings = serde_json::from_str("{}").unwrap();
+        assert_eq!(settings, Settings::default());
+
+        let settings: Settings = serde_json::from_str(r#"{"use_aria2c": true}"#).unwrap();
+        assert!(settings.use_aria2c);
+        assert_eq!(settings.concurrent_fragments, 1);
+
+        let settings: Settings = serde_json::from_str(r#"{"ytdlp_channel": "nightly"}"#).unwrap();
+        assert_eq!(settings.ytdlp_channel, UpdateChannel::Nightly);
+    }
+
+    #[test]
+    fn test_normalized_mirror_url() {
+        let settings = Settings {
+            mirror_url: Some(" http://mirror.internal/dlcut/ ".to_string()),
+            ..Settings::default()
+        };
+        assert_eq!(
+            settings.normalized().mirror_url.as_deref(),
+            Some("http://mirror.internal/dlcut")
+        );
+
+        let settings = Settings {
+            mirror_url: Some("   ".to_string()),
+            ..Settings::default()
+        };
+        assert_eq!(settings.normalized().mirror_url, None);
+    }
+
+    #[test]
+    fn test_normalized_binary_paths() {
+        let settings: Settings = serde_json::from_str(
+            r#"{"resolution_policy": "system_first", "binary_paths": {"ffmpeg": " /usr/bin/ffmpeg ", "ytdlp": ""}}"#,
+        )
+        .unwrap();
+        let settings = settings.normalized();
+
+        assert_eq!(settings.resolution_policy, ResolutionPolicy::SystemFirst);
+        assert_eq!(
+            settings.binary_paths.ffmpeg.as_deref(),
+            Some("/usr/bin/ffmpeg")
+        );
+        assert_eq!(settings.binary_paths.ytdlp, None);
+        assert_eq!(settings.binary_paths.aria2c, None);
+    }
+
+    #[test]
+    fn test_normalized_clamps_fragments() {
+        let settings = Settings {
+            concurrent_fragments: 0,
+            ..Settings::default()
+        };
+        assert_eq!(settings.normalized().concurrent_fragments, 1);
+
+        let settings = Settings {
+            concurrent_fragments: 64,
+            ..Settings::default()
+        };
+        assert_eq!(
+            settings.normalized().concurrent_fragments,
+            MAX_CONCURRENT_FRAGMENTS
+        );
+    }
+
+    #[test]
+    fn test_normalized_automation() {
+        let settings = Settings {
+            automation_port: 80,
+            automation_token: Some(" ".to_string()),
+            ..Settings::default()
+        };
+        let settings = settings.normalized();
+        assert_eq!(settings.automation_port, DEFAULT_AUTOMATION_PORT);
+        assert_eq!(settings.automation_token, None);
+    }
+
+    #[test]
+    fn test_normalized_filename_template() {
+        let settings: Settings = serde_json::from_str(
+            r#"{"filename_template": " {uploader} - {title} ", "collision_policy": "skip"}"#,
+        )
+        .unwrap();
+        let settings = settings.normalized();
+        assert_eq!(settings.filename_template, "{uploader} - {title}");
+        assert_eq!(settings.collision_policy, CollisionPolicy::Skip);
+
+        for template in ["", "  ", "{title", "{unknown}"] {
+            let settings = Settings {
+                filename_template: template.to_string(),
+                ..Settings::default()
+            };
+            assert_eq!(
+                settings.normalized().filename_template,
+                filename::DEFAULT_TEMPLATE
+            );
+        }
+    }
+
+    #[test]
+    fn test_normalized_output_rules() {
+        let settings: Settings = serde_json::from_str(
+            r#"{"output_rules": [
+                {"platform": "soundcloud", "mode": "audio_only", "uploader": " ", "folder": " Music/{uploader} "},
+                {"folder": "../outside"},
+                {"cut": true, "folder": "Clips/{nope}"}
+            ]}"#,
+        )
+        .unwrap();
+        let rules = settings.normalized().output_rules;
+
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].platform, Some(Platform::SoundCloud));
+        assert_eq!(rules[0].mode, Some(DownloadMode::AudioOnly));
+        assert_eq!(rules[0].uploader, None);
+        assert_eq!(rules[0].folder, "Music/{uploader}");
+    }
+}
@@ -0,0 +1,303 @@
+//! Atomic outputs
+//!
+//! yt-dlp and ffmpeg never write to the destination directly. Each job
+//! writes to a hidden, job-scoped temp file next to it
+//! (`.<name>.dlcut-<id>.<ext>`), which is verified with ffprobe and only
+//! then renamed into place. A failed or cancelled job leaves neither a truncated
+//! file nor a damaged copy of an existing one, and what happens to an
+//! existing file is decided by an explicit `CollisionPolicy`.
+
+use crate::error::{AppError, Result};
+use crate::filename;
+use crate::settings::{CollisionPolicy, Settings};
+use crate::verification::{self, Expected, VerifiedOutput};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// An output being written to a temp path in its destination directory
+///
+/// Dropping it without `commit` removes the temp file and anything the
+/// writer left next to it (yt-dlp's `.part` and per-stream files).
+#[derive(Debug)]
+pub struct StagedOutput {
+    destination: PathBuf,
+    temp_path: PathBuf,
+    /// File name prefix shared by the temp file and the writer's leftovers,
+    /// ending in a dot so job 1 never matches job 12's files
+    prefix: String,
+    policy: CollisionPolicy,
+}
+
+impl StagedOutput {
+    /// Stage an output for `destination`, failing early if the policy is to
+    /// skip an existing file, and create its directory if needed
+    pub fn new(destination: &Path, policy: CollisionPolicy) -> Result<Self> {
+        if policy == CollisionPolicy::Skip && destination.exists() {
+            return Err(AppError::OutputExists(destination.display().to_string()));
+        }
+
+        let dir = match destination.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        // Folders of output rules are only made once a job writes to them
+        std::fs::create_dir_all(dir).map_err(|e| {
+            AppError::OutputError(format!("Failed to create {}: {}", dir.display(), e))
+        })?;
+        let stem = destination
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let prefix = format!(
+            ".{}.dlcut-{}-{}.",
+            stem,
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        );
+
+        // Keep the extension: ffmpeg picks the container from it
+        let temp_name = match destination.extension() {
+            Some(ext) => format!("{}{}", prefix, ext.to_string_lossy()),
+            None => prefix.trim_end_matches('.').to_string(),
+        };
+
+        Ok(Self {
+            destination: destination.to_path_buf(),
+            temp_path: dir.join(temp_name),
+            prefix,
+            policy,
+        })
+    }
+
+    /// Path the writer should produce
+    pub fn temp_path(&self) -> &Path {
+        &self.temp_path
+    }
+
+    /// Path for an intermediate file of this job, e.g. a re-encoded copy or
+    /// pass statistics, removed along with the temp file
+    pub fn scratch_path(&self, name: &str) -> PathBuf {
+        self.temp_path
+            .with_file_name(format!("{}{}", self.prefix, name))
+    }
+
+    /// Verify what the writer produced against what was expected and move it
+    /// into place, returning the final path with the verification report
+    ///
+    /// `produced` is usually `temp_path`, but yt-dlp may have changed the
+    /// extension; the destination takes the produced file's extension.
+    pub async fn commit(
+        self,
+        produced: &Path,
+        expected: &Expected,
+        settings: &Settings,
+    ) -> Result<VerifiedOutput> {
+        let verification = verification::verify(produced, expected, settings).await?;
+
+        let destination = match produced.extension() {
+            Some(ext) => self.destination.with_extension(ext),
+            None => self.destination.clone(),
+        };
+        let destination = match self.policy {
+            CollisionPolicy::Overwrite => {
+                // Replaces an existing file in one step on every platform
+                std::fs::rename(produced, &destination).map_err(|e| save_error(&destination, e))?;
+                destination
+            }
+            policy => {
+                let destination = filename::resolve_collision(&destination, policy, Path::exists)?;
+                move_new(produced, &destination)?;
+                destination
+            }
+        };
+
+        Ok(VerifiedOutput {
+            path: destination.to_string_lossy().to_string(),
+            verification,
+        })
+    }
+
+    /// Files in the destination directory written for this job
+    fn leftovers(&self) -> Vec<PathBuf> {
+        let Some(dir) = self.temp_path.parent() else {
+            return Vec::new();
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path == &self.temp_path
+                    || path
+                        .file_name()
+                        .is_some_and(|name| name.to_string_lossy().starts_with(&self.prefix))
+            })
+            .collect()
+    }
+}
+
+impl Drop for StagedOutput {
+    fn drop(&mut self) {
+        for path in self.leftovers() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Move `from` to `to` without replacing a file that appeared at `to` in the
+/// meantime
+fn move_new(from: &Path, to: &Path) -> Result<()> {
+    // A hard link fails if the target exists, which makes the check and the
+    // move one step. Filesystems without hard links (FAT) fall back to a
+    // plain rename.
+    match std::fs::hard_link(from, to) {
+        Ok(()) => {
+            let _ = std::fs::remove_file(from);
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            Err(AppError::OutputExists(to.display().to_string()))
+        }
+        Err(_) if !to.exists() => std::fs::rename(from, to).map_err(|e| save_error(to, e)),
+        Err(_) => Err(AppError::OutputExists(to.display().to_string())),
+    }
+}
+
+fn save_error(path: &Path, error: std::io::Error) -> AppError {
+    AppError::OutputError(format!("Failed to save {}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn test_temp_path_is_hidden_and_unique() {
+        let dir = test_support::temp_dir("staging-unique");
+        let destination = dir.join("Clip.mp4");
+        let a = StagedOutput::new(&destination, CollisionPolicy::Overwrite).unwrap();
+        let b = StagedOutput::new(&destination, CollisionPolicy::Overwrite).unwrap();
+
+        assert_ne!(a.temp_path(), b.temp_path());
+        assert_eq!(a.temp_path().parent(), Some(dir.as_path()));
+        assert_eq!(a.temp_path().extension().unwrap(), "mp4");
+        let name = a
+            .temp_path()
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        assert!(name.starts_with(".Clip.dlcut-"), "{}", name);
+
+        let bare = StagedOutput::new(Path::new("Clip.mp4"), CollisionPolicy::Overwrite).unwrap();
+        assert_eq!(bare.temp_path().parent(), Some(Path::new(".")));
+
+        let scratch = a.scratch_path("passlog");
+        assert_eq!(scratch.parent(), Some(dir.as_path()));
+        assert!(scratch
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with(name.trim_end_matches(".mp4")));
+
+        // A routed folder is created once the job starts
+        let routed = dir.join("Music").join("Artist");
+        StagedOutput::new(&routed.join("Track.mp3"), CollisionPolicy::Skip).unwrap();
+        assert!(routed.is_dir());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_skip_fails_early() {
+        let dir = test_support::temp_dir("staging-skip");
+        let existing = dir.join("Existing.mp4");
+        std::fs::write(&existing, b"keep me").unwrap();
+
+        let result = StagedOutput::new(&existing, CollisionPolicy::Skip);
+        assert!(matches!(result, Err(AppError::OutputExists(_))));
+        assert!(StagedOutput::new(&dir.join("New.mp4"), CollisionPolicy::Skip).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_drop_removes_leftovers() {
+        let dir = test_support::temp_dir("staging-drop");
+        let existing = dir.join("Clip.mp4");
+        std::fs::write(&existing, b"original").unwrap();
+
+        let staged = StagedOutput::new(&existing, CollisionPolicy::Overwrite).unwrap();
+        let temp = staged.temp_path().to_path_buf();
+        let part = temp.with_extension("f137.mp4.part");
+        std::fs::write(&temp, b"trunc").unwrap();
+        std::fs::write(&part, b"fragment").unwrap();
+        drop(staged);
+
+        // The failed job is gone and the existing file is untouched
+        assert!(!temp.exists());
+        assert!(!part.exists());
+        assert_eq!(std::fs::read(&existing).unwrap(), b"original");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_drop_keeps_other_jobs() {
+        let dir = test_support::temp_dir("staging-jobs");
+        let destination = dir.join("Clip.mp4");
+        let staged = |id: u64| StagedOutput {
+            destination: destination.clone(),
+            temp_path: dir.join(format!(".Clip.dlcut-1-{}.mp4", id)),
+            prefix: format!(".Clip.dlcut-1-{}.", id),
+            policy: CollisionPolicy::Overwrite,
+        };
+        let (first, tenth) = (staged(1), staged(10));
+        let tenth_files = [
+            tenth.temp_path().to_path_buf(),
+            tenth.scratch_path("passlog-0.log"),
+        ];
+        std::fs::write(first.temp_path(), b"data").unwrap();
+        for path in &tenth_files {
+            std::fs::write(path, b"data").unwrap();
+        }
+
+        // Job 1's prefix is a prefix of job 10's name without the dot
+        drop(first);
+        assert!(!dir.join(".Clip.dlcut-1-1.mp4").exists());
+        assert!(tenth_files.iter().all(|path| path.exists()));
+
+        drop(tenth);
+        assert!(tenth_files.iter().all(|path| !path.exists()));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_move_new() {
+        let dir = test_support::temp_dir("staging-move");
+        let from = dir.join("from.mp4");
+        let to = dir.join("to.mp4");
+
+        std::fs::write(&from, b"new").unwrap();
+        move_new(&from, &to).unwrap();
+        assert!(!from.exists());
+        assert_eq!(std::fs::read(&to).unwrap(), b"new");
+
+        // Never replaces a file that appeared in the meantime
+        std::fs::write(&from, b"newer").unwrap();
+        assert!(matches!(
+            move_new(&from, &to),
+            Err(AppError::OutputExists(_))
+        ));
+        assert_eq!(std::fs::read(&to).unwrap(), b"new");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
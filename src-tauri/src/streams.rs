@@ -0,0 +1,561 @@
+//! Stream selection for cuts
+//!
+//! ffmpeg keeps only one video and one audio stream unless told otherwise,
+//! which silently drops commentary tracks, subtitles, attachments and
+//! chapters. Cuts map every stream they keep explicitly: all of them, or the
+//! ones picked by index from `probe_media`.
+//!
+//! Not every container can carry every stream. What the output container
+//! can't hold is converted (text subtitles, some audio codecs) or dropped,
+//! and either way the cut reports a warning.
+
+use crate::error::{AppError, Result};
+use crate::probe::{MediaInfo, MediaStream, StreamKind};
+
+/// Subtitle codecs that are plain or styled text and can be converted
+const TEXT_SUBTITLES: &[&str] = &["subrip", "srt", "ass", "ssa", "webvtt", "mov_text", "text"];
+
+/// What happens to a kept stream
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamAction {
+    Copy,
+    /// Re-encode with the named ffmpeg encoder
+    Convert(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedStream {
+    /// Index in the input file
+    pub index: u32,
+    pub kind: StreamKind,
+    pub action: StreamAction,
+    /// Cover art, which is copied even when the video is re-encoded
+    pub attached_pic: bool,
+}
+
+/// Which input streams a cut writes and how
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamPlan {
+    pub streams: Vec<PlannedStream>,
+    /// Streams converted or dropped for the output container
+    pub warnings: Vec<String>,
+}
+
+/// Output containers, by what they can carry
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Container {
+    Mp4,
+    Mov,
+    Matroska,
+    WebM,
+    /// Audio files, which hold no video or subtitles; some take only one
+    /// audio track, and some no cover art
+    Audio {
+        single_track: bool,
+        cover_art: bool,
+    },
+    /// Anything else: video and audio only
+    Other,
+}
+
+impl Container {
+    fn from_extension(ext: &str) -> Self {
+        match ext.to_ascii_lowercase().as_str() {
+            "mp4" | "m4v" => Container::Mp4,
+            "mov" => Container::Mov,
+            "mkv" | "mka" => Container::Matroska,
+            "webm" => Container::WebM,
+            "m4a" => Container::Audio {
+                single_track: false,
+                cover_art: true,
+            },
+            "mp3" | "flac" => Container::Audio {
+                single_track: true,
+                cover_art: true,
+            },
+            "aac" | "wav" => Container::Audio {
+                single_track: true,
+                cover_art: false,
+            },
+            "ogg" | "opus" => Container::Audio {
+                single_track: false,
+                cover_art: false,
+            },
+            _ => Container::Other,
+        }
+    }
+
+    fn single_track(self) -> bool {
+        matches!(
+            self,
+            Container::Audio {
+                single_track: true,
+                ..
+            }
+        )
+    }
+}
+
+/// Check a stream selection against the input before any work starts
+pub fn check_selection(media: &MediaInfo, selection: Option<&[u32]>) -> Result<()> {
+    let Some(selection) = selection else {
+        return Ok(());
+    };
+
+    if selection.is_empty() {
+        return Err(AppError::CutError("No streams selected".to_string()));
+    }
+    for index in selection {
+        if !media.streams.iter().any(|s| s.index == *index) {
+            return Err(AppError::CutError(format!(
+                "The input has no stream #{}",
+                index
+            )));
+        }
+    }
+
+    let has_media = media
+        .streams
+        .iter()
+        .any(|s| selection.contains(&s.index) && is_media(s));
+    if !has_media {
+        return Err(AppError::CutError(
+            "Select at least one video or audio stream".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Plan the streams of a cut: every stream, or the selected ones, as far as
+/// a container with extension `output_ext` can carry them
+pub fn plan(media: &MediaInfo, selection: Option<&[u32]>, output_ext: &str) -> Result<StreamPlan> {
+    check_selection(media, selection)?;
+
+    let container = Container::from_extension(output_ext);
+    let mut plan = StreamPlan::default();
+    for stream in &media.streams {
+        if selection.is_some_and(|selection| !selection.contains(&stream.index)) {
+            continue;
+        }
+
+        let name = describe(stream);
+        let has_audio = plan.streams.iter().any(|s| s.kind == StreamKind::Audio);
+        if stream.kind == StreamKind::Audio && has_audio && container.single_track() {
+            plan.warnings.push(format!(
+                "Dropped {}: .{} files hold one audio track",
+                name, output_ext
+            ));
+            continue;
+        }
+
+        match place(stream, container) {
+            Placement::Keep => plan.streams.push(PlannedStream {
+                index: stream.index,
+                kind: stream.kind,
+                action: StreamAction::Copy,
+                attached_pic: stream.attached_pic,
+            }),
+            Placement::Convert(codec, note) => {
+                plan.warnings.push(format!(
+                    "Converted {} to {} for .{}{}",
+                    name,
+                    codec.trim_start_matches("lib"),
+                    output_ext,
+                    note
+                ));
+                plan.streams.push(PlannedStream {
+                    index: stream.index,
+                    kind: stream.kind,
+                    action: StreamAction::Convert(codec),
+                    attached_pic: stream.attached_pic,
+                });
+            }
+            Placement::Drop => plan.warnings.push(format!(
+                "Dropped {}: .{} files can't carry it",
+                name, output_ext
+            )),
+        }
+    }
+
+    if !plan.streams.iter().any(|planned| {
+        media
+            .streams
+            .iter()
+            .any(|s| s.index == planned.index && is_media(s))
+    }) {
+        return Err(AppError::CutError(format!(
+            "None of the selected streams fit in a .{} file",
+            output_ext
+        )));
+    }
+
+    Ok(plan)
+}
+
+enum Placement {
+    Keep,
+    /// Encoder and a note on what the conversion loses
+    Convert(&'static str, &'static str),
+    Drop,
+}
+
+/// Whether a stream can go into the container as it is, converted, or not
+/// at all
+fn place(stream: &MediaStream, container: Container) -> Placement {
+    let codec = stream.codec.as_deref().unwrap_or_default();
+    let is_text = TEXT_SUBTITLES.contains(&codec);
+    let styled = matches!(codec, "ass" | "ssa");
+
+    match (stream.kind, container) {
+        // Cover art travels with audio files and containers that have a
+        // place for it
+        (StreamKind::Video, _) if stream.attached_pic => match container {
+            Container::WebM
+            | Container::Other
+            | Container::Audio {
+                cover_art: false, ..
+            } => Placement::Drop,
+            _ => Placement::Keep,
+        },
+        (StreamKind::Video, Container::Audio { .. }) => Placement::Drop,
+        (StreamKind::Video, Container::WebM) if !matches!(codec, "vp8" | "vp9" | "av1") => {
+            Placement::Drop
+        }
+        (StreamKind::Video, _) => Placement::Keep,
+
+        (StreamKind::Audio, Container::Mp4) => match codec {
+            "aac" | "mp3" | "ac3" | "eac3" | "opus" | "flac" | "alac" | "mp2" | "dts" => {
+                Placement::Keep
+            }
+            _ => Placement::Convert("aac", ""),
+        },
+        (StreamKind::Audio, Container::Mov) => match codec {
+            "aac" | "mp3" | "ac3" | "eac3" | "alac" | "mp2" => Placement::Keep,
+            c if c.starts_with("pcm_") => Placement::Keep,
+            _ => Placement::Convert("aac", ""),
+        },
+        (StreamKind::Audio, Container::WebM) => match codec {
+            "opus" | "vorbis" => Placement::Keep,
+            _ => Placement::Convert("libopus", ""),
+        },
+        (StreamKind::Audio, _) => Placement::Keep,
+
+        (StreamKind::Subtitle, Container::Mp4 | Container::Mov) => match codec {
+            "mov_text" => Placement::Keep,
+            _ if styled => Placement::Convert("mov_text", "; styling is lost"),
+            _ if is_text => Placement::Convert("mov_text", ""),
+            _ => Placement::Drop,
+        },
+        (StreamKind::Subtitle, Container::Matroska) => match codec {
+            // Matroska has no mov_text
+            "mov_text" => Placement::Convert("subrip", ""),
+            _ => Placement::Keep,
+        },
+        (StreamKind::Subtitle, Container::WebM) => match codec {
+            "webvtt" => Placement::Keep,
+            _ if styled => Placement::Convert("webvtt", "; styling is lost"),
+            _ if is_text => Placement::Convert("webvtt", ""),
+            _ => Placement::Drop,
+        },
+        (StreamKind::Subtitle, _) => Placement::Drop,
+
+        // Fonts for styled subtitles
+        (StreamKind::Attachment, Container::Matroska) => Placement::Keep,
+
+        // Timecode, GPS and other data tracks rarely survive a remux
+        _ => Placement::Drop,
+    }
+}
+
+fn is_media(stream: &MediaStream) -> bool {
+    match stream.kind {
+        StreamKind::Video => !stream.attached_pic,
+        StreamKind::Audio => true,
+        _ => false,
+    }
+}
+
+/// e.g. "subtitle #3 (ass, eng)"
+fn describe(stream: &MediaStream) -> String {
+    let kind = match stream.kind {
+        StreamKind::Video if stream.attached_pic => "cover art",
+        StreamKind::Video => "video",
+        StreamKind::Audio => "audio",
+        StreamKind::Subtitle => "subtitle",
+        StreamKind::Data => "data",
+        StreamKind::Attachment => "attachment",
+        StreamKind::Unknown => "stream",
+    };
+    let details: Vec<&str> = [stream.codec.as_deref(), stream.language.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect();
+
+    if details.is_empty() {
+        format!("{} #{}", kind, stream.index)
+    } else {
+        format!("{} #{} ({})", kind, stream.index, details.join(", "))
+    }
+}
+
+impl StreamPlan {
+    /// `-map` arguments for the kept streams, plus the input's chapters
+    pub fn map_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for stream in &self.streams {
+            args.push("-map".to_string());
+            args.push(format!("0:{}", stream.index));
+        }
+        args.push("-map_chapters".to_string());
+        args.push("0".to_string());
+        args
+    }
+
+    /// Output and input index of the main video stream, the one a filter
+    /// graph applies to
+    pub fn main_video(&self) -> Option<(usize, u32)> {
+        self.streams
+            .iter()
+            .position(|s| s.kind == StreamKind::Video && !s.attached_pic)
+            .map(|output_index| (output_index, self.streams[output_index].index))
+    }
+
+    /// The same plan with the main video as the first output stream
+    ///
+    /// ffmpeg names two-pass logs by output stream index, and the first pass
+    /// writes only the main video, so the second must put it first too.
+    pub fn main_video_first(&self) -> StreamPlan {
+        let mut plan = self.clone();
+        if let Some((output_index, _)) = self.main_video() {
+            let video = plan.streams.remove(output_index);
+            plan.streams.insert(0, video);
+        }
+        plan
+    }
+
+    /// Per-stream codec arguments, to follow a general `-c copy` or the
+    /// re-encoding codecs
+    ///
+    /// When `reencoding`, cover art is still copied rather than turned into
+    /// a video track.
+    pub fn codec_args(&self, reencoding: bool) -> Vec<String> {
+        let mut args = Vec::new();
+        for (output_index, planned) in self.streams.iter().enumerate() {
+            let codec = match &planned.action {
+                StreamAction::Convert(codec) => *codec,
+                StreamAction::Copy if reencoding && planned.attached_pic => "copy",
+                StreamAction::Copy => continue,
+            };
+            args.push(format!("-c:{}", output_index));
+            args.push(codec.to_string());
+        }
+        args
+    }
+
+    /// The input as the cut should come out: only the kept streams, with
+    /// converted codecs, for verifying the result
+    pub fn output_media(&self, media: &MediaInfo) -> MediaInfo {
+        let streams = self
+            .streams
+            .iter()
+            .filter_map(|planned| {
+                let mut stream = media
+                    .streams
+                    .iter()
+                    .find(|s| s.index == planned.index)?
+                    .clone();
+                if let StreamAction::Convert(codec) = planned.action {
+                    stream.codec = Some(codec.trim_start_matches("lib").to_string());
+                }
+                Some(stream)
+            })
+            .collect();
+
+        MediaInfo {
+            streams,
+            ..media.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(index: u32, kind: StreamKind, codec: &str) -> MediaStream {
+        MediaStream {
+            index,
+            kind,
+            codec: Some(codec.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// A film with commentary, styled and bitmap subtitles and a font
+    fn film() -> MediaInfo {
+        let mut cover = stream(6, StreamKind::Video, "mjpeg");
+        cover.attached_pic = true;
+        let mut commentary = stream(2, StreamKind::Audio, "opus");
+        commentary.language = Some("eng".to_string());
+
+        MediaInfo {
+            container: "matroska,webm".to_string(),
+            duration: Some(5400.0),
+            streams: vec![
+                stream(0, StreamKind::Video, "h264"),
+                stream(1, StreamKind::Audio, "aac"),
+                commentary,
+                stream(3, StreamKind::Subtitle, "ass"),
+                stream(4, StreamKind::Subtitle, "hdmv_pgs_subtitle"),
+                stream(5, StreamKind::Attachment, "ttf"),
+                cover,
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn indices(plan: &StreamPlan) -> Vec<u32> {
+        plan.streams.iter().map(|s| s.index).collect()
+    }
+
+    #[test]
+    fn test_plan_keeps_everything_in_matroska() {
+        let plan = plan(&film(), None, "mkv").unwrap();
+        assert_eq!(indices(&plan), vec![0, 1, 2, 3, 4, 5, 6]);
+        assert!(plan.streams.iter().all(|s| s.action == StreamAction::Copy));
+        assert!(plan.warnings.is_empty(), "{:?}", plan.warnings);
+
+        assert_eq!(
+            plan.map_args().join(" "),
+            "-map 0:0 -map 0:1 -map 0:2 -map 0:3 -map 0:4 -map 0:5 -map 0:6 -map_chapters 0"
+        );
+    }
+
+    #[test]
+    fn test_plan_for_mp4() {
+        let film = film();
+        let plan = plan(&film, None, "mp4").unwrap();
+
+        // Bitmap subtitles and the font don't fit; the styled subtitles lose
+        // their styling
+        assert_eq!(indices(&plan), vec![0, 1, 2, 3, 6]);
+        assert_eq!(plan.streams[3].action, StreamAction::Convert("mov_text"));
+        assert_eq!(
+            plan.warnings,
+            vec![
+                "Converted subtitle #3 (ass) to mov_text for .mp4; styling is lost",
+                "Dropped subtitle #4 (hdmv_pgs_subtitle): .mp4 files can't carry it",
+                "Dropped attachment #5 (ttf): .mp4 files can't carry it",
+            ]
+        );
+
+        // Output index 3 is the converted subtitle track
+        assert_eq!(plan.codec_args(false).join(" "), "-c:3 mov_text");
+        // Re-encoding leaves the cover art alone
+        assert_eq!(plan.codec_args(true).join(" "), "-c:3 mov_text -c:4 copy");
+        // Filters go to the film, not the cover art
+        assert_eq!(plan.main_video(), Some((0, 0)));
+
+        let output = plan.output_media(&film);
+        assert_eq!(output.streams.len(), 5);
+        assert_eq!(output.streams[3].codec.as_deref(), Some("mov_text"));
+    }
+
+    #[test]
+    fn test_plan_selection() {
+        let film = film();
+        let plan = plan(&film, Some(&[0, 2]), "mp4").unwrap();
+        assert_eq!(indices(&plan), vec![0, 2]);
+        assert!(plan.warnings.is_empty());
+
+        // The commentary track is opus, which webm keeps; h264 it can't
+        let error = super::plan(&film, Some(&[0, 3]), "webm").unwrap_err();
+        assert!(error.to_string().contains("None of the selected streams"));
+        let webm = super::plan(&film, Some(&[0, 2, 3]), "webm").unwrap();
+        assert_eq!(indices(&webm), vec![2, 3]);
+        assert_eq!(webm.streams[1].action, StreamAction::Convert("webvtt"));
+        assert_eq!(webm.warnings.len(), 2, "{:?}", webm.warnings);
+        assert_eq!(webm.main_video(), None);
+
+        // Audio gets converted where the container needs it
+        let mut wav = film.clone();
+        wav.streams[1].codec = Some("pcm_s16le".to_string());
+        let mp4 = super::plan(&wav, Some(&[1]), "mp4").unwrap();
+        assert_eq!(mp4.streams[0].action, StreamAction::Convert("aac"));
+        let mov = super::plan(&wav, Some(&[1]), "mov").unwrap();
+        assert_eq!(mov.streams[0].action, StreamAction::Copy);
+    }
+
+    #[test]
+    fn test_plan_for_audio_files() {
+        let film = film();
+
+        // mp3 takes one audio track and the cover art
+        let mp3 = plan(&film, None, "mp3").unwrap();
+        assert_eq!(indices(&mp3), vec![1, 6]);
+        assert!(mp3.warnings.contains(
+            &"Dropped audio #2 (opus, eng): .mp3 files hold one audio track".to_string()
+        ));
+
+        // Of the selected tracks, the first in the file is kept
+        assert_eq!(
+            indices(&plan(&film, Some(&[2, 1]), "wav").unwrap()),
+            vec![1]
+        );
+        assert_eq!(
+            indices(&plan(&film, Some(&[2, 6]), "flac").unwrap()),
+            vec![2, 6]
+        );
+
+        // wav, adts and ogg have no place for cover art
+        for ext in ["wav", "aac", "opus", "ogg"] {
+            let audio = plan(&film, None, ext).unwrap();
+            assert!(!indices(&audio).contains(&6), "{}", ext);
+        }
+        assert_eq!(indices(&plan(&film, None, "opus").unwrap()), vec![1, 2]);
+        assert_eq!(indices(&plan(&film, None, "m4a").unwrap()), vec![1, 2, 6]);
+    }
+
+    #[test]
+    fn test_main_video_first() {
+        // Transport streams and remuxes often list audio before video
+        let mut cover = stream(0, StreamKind::Video, "mjpeg");
+        cover.attached_pic = true;
+        let media = MediaInfo {
+            streams: vec![
+                cover,
+                stream(1, StreamKind::Audio, "flac"),
+                stream(2, StreamKind::Video, "h264"),
+            ],
+            ..Default::default()
+        };
+        let plan = plan(&media, None, "mkv").unwrap();
+        assert_eq!(plan.main_video(), Some((2, 2)));
+
+        let reordered = plan.main_video_first();
+        assert_eq!(indices(&reordered), vec![2, 0, 1]);
+        assert_eq!(reordered.main_video(), Some((0, 2)));
+        assert_eq!(
+            reordered.map_args().join(" "),
+            "-map 0:2 -map 0:0 -map 0:1 -map_chapters 0"
+        );
+        // The cover art's codec follows it to its new output index
+        assert_eq!(reordered.codec_args(true).join(" "), "-c:1 copy");
+
+        let audio = super::plan(&media, Some(&[1]), "mkv").unwrap();
+        assert_eq!(audio.main_video_first(), audio);
+    }
+
+    #[test]
+    fn test_check_selection() {
+        let film = film();
+        assert!(check_selection(&film, None).is_ok());
+        assert!(check_selection(&film, Some(&[1, 3])).is_ok());
+        assert!(check_selection(&film, Some(&[])).is_err());
+        assert!(check_selection(&film, Some(&[9])).is_err());
+        // Subtitles alone are not a cut
+        assert!(check_selection(&film, Some(&[3, 4])).is_err());
+        assert!(check_selection(&film, Some(&[6])).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+//! Fixtures shared by the unit tests
+//!
+//! Tests take these and override only the fields they care about, so a new
+//! field on a request or on video info is added here once.
+
+use crate::types::{
+    AudioQuality, DownloadMode, DownloadRequest, Platform, VideoInfo, VideoQuality,
+};
+use std::path::PathBuf;
+
+/// Fresh scratch directory for filesystem tests; `name` keeps tests that run
+/// in parallel apart
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("dlcut-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Info of a 1:40 YouTube video with audio: 1080p (400 MB), 720p (200 MB),
+/// 480p (size unknown) and one "high" 320 kbps audio quality
+pub fn video_info() -> VideoInfo {
+    let quality = |height: u32, filesize: Option<u64>| VideoQuality {
+        height,
+        label: format!("{}p", height),
+        filesize_approx: None,
+        filesize,
+    };
+
+    VideoInfo {
+        id: "dQw4w9WgXcQ".to_string(),
+        title: "Video".to_string(),
+        duration: 100.0,
+        duration_string: "1:40".to_string(),
+        thumbnail: None,
+        uploader: None,
+        upload_date: None,
+        formats: Vec::new(),
+        video_qualities: vec![
+            quality(1080, Some(400_000_000)),
+            quality(720, Some(200_000_000)),
+            quality(480, None),
+        ],
+        audio_qualities: vec![AudioQuality {
+            quality_id: "high".to_string(),
+            label: "High".to_string(),
+            bitrate: 320,
+        }],
+        platform: Platform::YouTube,
+        start_time: None,
+        end_time: None,
+        has_audio: Some(true),
+    }
+}
+
+/// A 720p download of the whole of that video
+pub fn download_request() -> DownloadRequest {
+    DownloadRequest {
+        url: "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+        quality: "720".to_string(),
+        mode: DownloadMode::VideoWithAudio,
+        output_path: "video.mp4".to_string(),
+        start_time: None,
+        end_time: None,
+        if_exists: None,
+        target_size: None,
+    }
+}
@@ -0,0 +1,529 @@
+//! Timestamp parsing
+//!
+//! Accepts the forms people type into cut fields:
+//! - `SS`, `MM:SS` and `HH:MM:SS`, each with optional `.mmm`
+//! - SMPTE `HH:MM:SS:FF` when the frame rate is known
+//! - `1h2m3.5s` as used in YouTube and Twitch links
+//! - for end times, `+45` (45 s after the start) and `-10` (10 s before the
+//!   end of the video); `-10` also works for start times
+//!
+//! Minutes and seconds after a larger unit must be below 60, so typos like
+//! `99:99` are rejected instead of silently becoming 1:40:39.
+
+use crate::error::{AppError, Result};
+use serde::Serialize;
+use std::fmt;
+use thiserror::Error;
+
+/// Longest time accepted (100 hours)
+pub const MAX_SECONDS: f64 = 100.0 * 3600.0;
+
+const FORMS: &str = "use SS, MM:SS, HH:MM:SS, HH:MM:SS.mmm or 1h2m3s";
+
+/// A parsed time, possibly relative to the range it belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timestamp {
+    /// Seconds from the start of the video
+    Absolute(f64),
+    /// Seconds after the start time (`+45`)
+    AfterStart(f64),
+    /// Seconds before the end of the video (`-10`)
+    BeforeEnd(f64),
+}
+
+/// Parse a time, including relative forms
+///
+/// `fps` enables SMPTE `HH:MM:SS:FF` timecodes.
+pub fn parse(input: &str, fps: Option<f64>) -> Result<Timestamp> {
+    parse_with_reason(input, fps)
+        .map_err(|reason| AppError::InvalidTimestamp(format!("'{}': {}", input.trim(), reason)))
+}
+
+/// Parse a time that must be absolute, returning seconds
+pub fn parse_seconds(input: &str, fps: Option<f64>) -> Result<f64> {
+    match parse(input, fps)? {
+        Timestamp::Absolute(seconds) => Ok(seconds),
+        _ => Err(AppError::InvalidTimestamp(format!(
+            "'{}': relative times are not allowed here",
+            input.trim()
+        ))),
+    }
+}
+
+/// Parse optional start and end times and resolve them into a valid range
+///
+/// Blank values mean "from the beginning" and "to the end". `duration` is
+/// needed to resolve `-N` and to check the range fits inside the video.
+pub fn resolve_range(
+    start: Option<&str>,
+    end: Option<&str>,
+    duration: Option<f64>,
+    fps: Option<f64>,
+) -> Result<(Option<f64>, Option<f64>)> {
+    let start = start.map(str::trim).filter(|s| !s.is_empty());
+    let end = end.map(str::trim).filter(|e| !e.is_empty());
+
+    let start_secs = match start {
+        None => None,
+        Some(input) => Some(match parse_field("start", input, fps)? {
+            Timestamp::Absolute(seconds) => seconds,
+            Timestamp::AfterStart(_) => {
+                return Err(AppError::InvalidTimestamp(format!(
+                    "Invalid start time '{}': '+' offsets are only allowed for the end time",
+                    input
+                )))
+            }
+            Timestamp::BeforeEnd(offset) => before_end("start", input, offset, duration)?,
+        }),
+    };
+
+    let end_secs = match end {
+        None => None,
+        Some(input) => Some(match parse_field("end", input, fps)? {
+            Timestamp::Absolute(seconds) => seconds,
+            Timestamp::AfterStart(offset) => start_secs.unwrap_or(0.0) + offset,
+            Timestamp::BeforeEnd(offset) => before_end("end", input, offset, duration)?,
+        }),
+    };
+
+    check_range(start_secs, end_secs, duration)?;
+    Ok((start_secs, end_secs))
+}
+
+/// Which end of a cut range an error refers to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RangeBound {
+    Start,
+    End,
+}
+
+impl fmt::Display for RangeBound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeBound::Start => write!(f, "Start"),
+            RangeBound::End => write!(f, "End"),
+        }
+    }
+}
+
+/// Why a resolved cut range was rejected
+///
+/// Serialized with a `reason` tag so API clients can react without parsing
+/// the message.
+#[derive(Debug, Clone, PartialEq, Error, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum RangeError {
+    #[error("{bound} time is not a number")]
+    NotANumber { bound: RangeBound },
+
+    #[error("{bound} time cannot be negative")]
+    Negative { bound: RangeBound },
+
+    #[error("End time must be positive")]
+    EmptyEnd,
+
+    #[error("{bound} time {} is past the end of the video ({})", display(*.time), display(*.duration))]
+    PastEnd {
+        bound: RangeBound,
+        time: f64,
+        duration: f64,
+    },
+
+    #[error("Start time {} must be before end time {}", display(*.start), display(*.end))]
+    Reversed { start: f64, end: f64 },
+}
+
+/// Check that resolved start and end times form a valid range within the
+/// video, when its duration is known
+pub fn check_range(start: Option<f64>, end: Option<f64>, duration: Option<f64>) -> Result<()> {
+    let duration = duration.filter(|d| d.is_finite());
+
+    for (bound, time) in [(RangeBound::Start, start), (RangeBound::End, end)] {
+        let Some(time) = time else {
+            continue;
+        };
+
+        if !time.is_finite() {
+            return Err(RangeError::NotANumber { bound }.into());
+        }
+        if time < 0.0 {
+            return Err(RangeError::Negative { bound }.into());
+        }
+        if bound == RangeBound::End && time == 0.0 {
+            return Err(RangeError::EmptyEnd.into());
+        }
+        if let Some(duration) = duration {
+            let past_end = match bound {
+                RangeBound::Start => time >= duration,
+                RangeBound::End => time > duration,
+            };
+            if past_end {
+                return Err(RangeError::PastEnd {
+                    bound,
+                    time,
+                    duration,
+                }
+                .into());
+            }
+        }
+    }
+
+    if let (Some(start), Some(end)) = (start, end) {
+        if start >= end {
+            return Err(RangeError::Reversed { start, end }.into());
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_field(field: &str, input: &str, fps: Option<f64>) -> Result<Timestamp> {
+    parse_with_reason(input, fps).map_err(|reason| {
+        AppError::InvalidTimestamp(format!("Invalid {} time '{}': {}", field, input, reason))
+    })
+}
+
+fn before_end(field: &str, input: &str, offset: f64, duration: Option<f64>) -> Result<f64> {
+    let duration = duration.filter(|d| d.is_finite() && *d > 0.0).ok_or_else(|| {
+        AppError::InvalidTimestamp(format!(
+            "Invalid {} time '{}': the video length is unknown, so it cannot be counted from the end",
+            field, input
+        ))
+    })?;
+
+    if offset > duration {
+        return Err(AppError::InvalidTimestamp(format!(
+            "Invalid {} time '{}': the video is only {} long",
+            field,
+            input,
+            display(duration)
+        )));
+    }
+
+    Ok(duration - offset)
+}
+
+fn parse_with_reason(input: &str, fps: Option<f64>) -> std::result::Result<Timestamp, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("time is empty".to_string());
+    }
+
+    let (wrap, body): (fn(f64) -> Timestamp, &str) = if let Some(body) = input.strip_prefix('+') {
+        (Timestamp::AfterStart, body)
+    } else if let Some(body) = input.strip_prefix('-') {
+        (Timestamp::BeforeEnd, body)
+    } else {
+        (Timestamp::Absolute, input)
+    };
+
+    let seconds = if body.ends_with(['h', 'm', 's']) {
+        parse_units(body)?
+    } else {
+        parse_clock(body, fps)?
+    };
+
+    if seconds > MAX_SECONDS {
+        return Err("longer than 100 hours".to_string());
+    }
+
+    Ok(wrap(seconds))
+}
+
+/// Parse `SS`, `MM:SS`, `HH:MM:SS` (each with optional fraction) or
+/// SMPTE `HH:MM:SS:FF`
+fn parse_clock(body: &str, fps: Option<f64>) -> std::result::Result<f64, String> {
+    let parts: Vec<&str> = body.split(':').collect();
+
+    match parts.as_slice() {
+        [secs] => parse_number(secs, "seconds", true),
+        [mins, secs] => {
+            let mins = parse_number(mins, "minutes", false)?;
+            let secs = parse_below_60(secs, "seconds", true)?;
+            Ok(mins * 60.0 + secs)
+        }
+        [hours, mins, secs] => {
+            let hours = parse_number(hours, "hours", false)?;
+            let mins = parse_below_60(mins, "minutes", false)?;
+            let secs = parse_below_60(secs, "seconds", true)?;
+            Ok(hours * 3600.0 + mins * 60.0 + secs)
+        }
+        [hours, mins, secs, frames] => {
+            let fps = fps
+                .filter(|fps| fps.is_finite() && *fps > 0.0)
+                .ok_or("frame timecodes (HH:MM:SS:FF) need a known frame rate")?;
+            let hours = parse_number(hours, "hours", false)?;
+            let mins = parse_below_60(mins, "minutes", false)?;
+            let secs = parse_below_60(secs, "seconds", false)?;
+            let frames = parse_number(frames, "frames", false)?;
+            if frames >= fps.ceil() {
+                return Err(format!(
+                    "frame {} does not exist at {} fps",
+                    frames,
+                    display_fps(fps)
+                ));
+            }
+            Ok(hours * 3600.0 + mins * 60.0 + secs + frames / fps)
+        }
+        _ => Err(format!("not a time ({})", FORMS)),
+    }
+}
+
+/// Parse `1h2m3.5s`; each unit is optional but they must come in order and
+/// only the last may have a fraction
+fn parse_units(body: &str) -> std::result::Result<f64, String> {
+    let mut rest = body;
+    let mut total = 0.0;
+    let mut leading = true;
+
+    for (unit, name, scale) in [
+        ('h', "hours", 3600.0),
+        ('m', "minutes", 60.0),
+        ('s', "seconds", 1.0),
+    ] {
+        let Some((value, tail)) = rest.split_once(unit) else {
+            continue;
+        };
+        let fraction = tail.is_empty();
+        let value = if leading {
+            parse_number(value, name, fraction)?
+        } else {
+            parse_below_60(value, name, fraction)?
+        };
+
+        total += value * scale;
+        rest = tail;
+        leading = false;
+    }
+
+    if leading || !rest.is_empty() {
+        return Err(format!("not a time ({})", FORMS));
+    }
+
+    Ok(total)
+}
+
+/// Parse a non-negative decimal made only of digits and, if allowed, one
+/// fractional part; rejects signs, exponents, `inf` and `NaN`
+fn parse_number(value: &str, name: &str, fraction: bool) -> std::result::Result<f64, String> {
+    let (whole, frac) = match value.split_once('.') {
+        Some((whole, frac)) if fraction => (whole, Some(frac)),
+        Some(_) => return Err(format!("{} must be a whole number", name)),
+        None => (value, None),
+    };
+
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let valid = match frac {
+        Some(frac) => (whole.is_empty() || digits(whole)) && digits(frac),
+        None => digits(whole),
+    };
+    if !valid {
+        return Err(if value.is_empty() {
+            format!("{} are missing", name)
+        } else {
+            format!("'{}' is not a valid number of {}", value, name)
+        });
+    }
+
+    value
+        .parse::<f64>()
+        .map_err(|_| format!("'{}' is not a valid number of {}", value, name))
+}
+
+fn parse_below_60(value: &str, name: &str, fraction: bool) -> std::result::Result<f64, String> {
+    let number = parse_number(value, name, fraction)?;
+    if number >= 60.0 {
+        return Err(format!("{} must be below 60, got {}", name, value));
+    }
+    Ok(number)
+}
+
+/// Seconds as `MM:SS` or `HH:MM:SS`, keeping milliseconds when present
+fn display(seconds: f64) -> String {
+    let millis = ((seconds.fract() * 1000.0).round() as u32).min(999);
+    let whole = crate::types::format_duration(seconds);
+    if millis > 0 {
+        format!("{}.{:03}", whole, millis)
+    } else {
+        whole
+    }
+}
+
+fn display_fps(fps: f64) -> String {
+    if fps.fract() == 0.0 {
+        format!("{}", fps as u32)
+    } else {
+        format!("{:.3}", fps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::format_duration;
+    use proptest::prelude::*;
+
+    fn seconds(input: &str) -> f64 {
+        parse_seconds(input, None).unwrap()
+    }
+
+    fn error(result: Result<impl std::fmt::Debug>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_clock_forms() {
+        assert_eq!(seconds("45"), 45.0);
+        assert_eq!(seconds("90.25"), 90.25);
+        assert_eq!(seconds("1:30"), 90.0);
+        assert_eq!(seconds("90:00"), 5400.0);
+        assert_eq!(seconds("01:02:03"), 3723.0);
+        assert_eq!(seconds("00:01:02.500"), 62.5);
+        assert_eq!(seconds(" 1:05.1 "), 65.1);
+        assert_eq!(seconds(".5"), 0.5);
+    }
+
+    #[test]
+    fn test_unit_forms() {
+        assert_eq!(seconds("1h2m3.5s"), 3723.5);
+        assert_eq!(seconds("90s"), 90.0);
+        assert_eq!(seconds("90m"), 5400.0);
+        assert_eq!(seconds("1h"), 3600.0);
+        assert!(error(parse("1h90m", None)).contains("minutes must be below 60"));
+        assert!(error(parse("1.5h2m", None)).contains("whole number"));
+        assert!(parse("2m1h", None).is_err());
+        assert!(parse("1h1h", None).is_err());
+    }
+
+    #[test]
+    fn test_smpte() {
+        assert_eq!(parse_seconds("00:00:01:12", Some(24.0)).unwrap(), 1.5);
+        assert_eq!(
+            parse_seconds("01:00:00:29", Some(29.97)).unwrap(),
+            3600.0 + 29.0 / 29.97
+        );
+        assert!(error(parse("00:00:01:12", None)).contains("frame rate"));
+        assert!(
+            error(parse("00:00:01:24", Some(24.0))).contains("frame 24 does not exist at 24 fps")
+        );
+        assert!(parse("00:00:01.5:00", Some(24.0)).is_err());
+    }
+
+    #[test]
+    fn test_relative() {
+        assert_eq!(parse("+45", None).unwrap(), Timestamp::AfterStart(45.0));
+        assert_eq!(parse("-1:30", None).unwrap(), Timestamp::BeforeEnd(90.0));
+        assert!(error(parse_seconds("+45", None)).contains("relative"));
+
+        let range = resolve_range(Some("1:00"), Some("+45"), Some(600.0), None).unwrap();
+        assert_eq!(range, (Some(60.0), Some(105.0)));
+
+        let range = resolve_range(None, Some("+45"), None, None).unwrap();
+        assert_eq!(range, (None, Some(45.0)));
+
+        let range = resolve_range(Some("-10"), None, Some(600.0), None).unwrap();
+        assert_eq!(range, (Some(590.0), None));
+
+        let range = resolve_range(Some("1:00"), Some("-10"), Some(600.0), None).unwrap();
+        assert_eq!(range, (Some(60.0), Some(590.0)));
+
+        assert!(
+            error(resolve_range(None, Some("-10"), None, None)).contains("video length is unknown")
+        );
+        assert!(error(resolve_range(Some("+10"), None, Some(600.0), None))
+            .contains("only allowed for the end"));
+        assert!(
+            error(resolve_range(None, Some("-700"), Some(600.0), None)).contains("only 10:00 long")
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed() {
+        for input in [
+            "",
+            "abc",
+            "1e3",
+            "NaN",
+            "inf",
+            "-inf",
+            "1:2:3:4:5",
+            "1::2",
+            ":30",
+            "1:",
+            "1 30",
+            "+-5",
+            "--5",
+            "0x10",
+            "1,5",
+            "1:30.",
+            "1.2.3",
+            "٣٠",
+            "1h30",
+        ] {
+            assert!(parse(input, Some(30.0)).is_err(), "accepted {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_range_errors() {
+        assert!(error(parse("99:99", None)).contains("seconds must be below 60, got 99"));
+        assert!(error(parse("1:60:00", None)).contains("minutes must be below 60"));
+        assert!(error(parse("101:00:00", None)).contains("longer than 100 hours"));
+
+        assert!(
+            error(resolve_range(Some("2:00"), Some("1:00"), Some(600.0), None))
+                .contains("Start time 02:00 must be before end time 01:00")
+        );
+        assert!(
+            error(resolve_range(Some("10:00"), None, Some(600.0), None)).contains("past the end")
+        );
+        assert!(
+            error(resolve_range(None, Some("10:00.5"), Some(600.0), None))
+                .contains("End time 10:00.500 is past the end of the video (10:00)")
+        );
+        assert!(error(resolve_range(None, Some("0"), None, None)).contains("must be positive"));
+        assert!(check_range(Some(f64::NAN), None, None).is_err());
+        assert!(check_range(None, Some(f64::INFINITY), None).is_err());
+        assert!(check_range(Some(-1.0), None, None).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_parses_format_duration(secs in 0u64..(MAX_SECONDS as u64)) {
+            prop_assert_eq!(seconds(&format_duration(secs as f64)), secs as f64);
+        }
+
+        #[test]
+        fn prop_parses_milliseconds(secs in 0u64..(MAX_SECONDS as u64), millis in 0u32..1000) {
+            let input = format!("{}.{:03}", format_duration(secs as f64), millis);
+            let expected = secs as f64 + millis as f64 / 1000.0;
+            prop_assert!((seconds(&input) - expected).abs() < 1e-6);
+            prop_assert_eq!(display(seconds(&input)), if millis > 0 {
+                input.clone()
+            } else {
+                format_duration(secs as f64)
+            });
+        }
+
+        #[test]
+        fn prop_units_match_clock(secs in 0u64..(MAX_SECONDS as u64)) {
+            let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+            let units = format!("{}h{}m{}s", h, m, s);
+            prop_assert_eq!(seconds(&units), seconds(&format_duration(secs as f64)));
+        }
+
+        #[test]
+        fn prop_relative_end_after_start(start in 0u64..3600, offset in 1u64..3600) {
+            let start_text = format_duration(start as f64);
+            let offset_text = format!("+{}", format_duration(offset as f64));
+            let range = resolve_range(Some(&start_text), Some(&offset_text), None, None).unwrap();
+            prop_assert_eq!(range, (Some(start as f64), Some((start + offset) as f64)));
+        }
+
+        #[test]
+        fn prop_never_panics(input in "\\PC{0,24}") {
+            let _ = parse(&input, Some(25.0));
+        }
+    }
+}
@@ -3,6 +3,7 @@
 //! These structures are used for IPC between frontend and backend.
 //! All fields are validated before use.
 
+use crate::settings::CollisionPolicy;
 use serde::{Deserialize, Serialize};
 
 /// Supported platform detected from URL
@@ -20,6 +21,8 @@ pub enum Platform {
     Reddit,
     #[serde(rename = "soundcloud")]
     SoundCloud,
+    #[serde(rename = "twitch")]
+    Twitch,
 }
 
 impl Platform {
@@ -39,6 +42,24 @@ pub enum DownloadMode {
     AudioOnly,
 }
 
+impl DownloadMode {
+    /// Extension of the file this mode produces
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DownloadMode::VideoWithAudio => "mp4",
+            DownloadMode::AudioOnly => "mp3",
+        }
+    }
+
+    /// Quality used when none is chosen: best video, or high-quality audio
+    pub fn default_quality(&self) -> &'static str {
+        match self {
+            DownloadMode::VideoWithAudio => "0",
+            DownloadMode::AudioOnly => "high",
+        }
+    }
+}
+
 /// Quality option for video downloads
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoQuality {
@@ -48,6 +69,8 @@ pub struct VideoQuality {
     pub label: String,
     /// Estimated file size (if available)
     pub filesize_approx: Option<String>,
+    /// Estimated file size in bytes (if available)
+    pub filesize: Option<u64>,
 }
 
 /// Quality option for audio downloads
@@ -93,6 +116,8 @@ pub struct VideoInfo {
     pub duration_string: String,
     pub thumbnail: Option<String>,
     pub uploader: Option<String>,
+    /// Upload date as `YYYYMMDD`
+    pub upload_date: Option<String>,
     /// Legacy formats (kept for compatibility)
     pub formats: Vec<VideoFormat>,
     /// Available video quality options
@@ -101,10 +126,17 @@ pub struct VideoInfo {
     pub audio_qualities: Vec<AudioQuality>,
     /// Detected platform
     pub platform: Platform,
+    /// Start of the range embedded in the link (e.g. `?t=90`), in seconds
+    pub start_time: Option<f64>,
+    /// End of the range embedded in the link (e.g. a YouTube clip), in seconds
+    pub end_time: Option<f64>,
+    /// Whether any format has an audio track; `None` if yt-dlp didn't say
+    #[serde(default)]
+    pub has_audio: Option<bool>,
 }
 
 /// Download request from frontend
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownloadRequest {
     pub url: String,
     /// For VideoWithAudio: height as string (e.g., "1080")
@@ -117,6 +149,120 @@ pub struct DownloadRequest {
     pub start_time: Option<f64>,
     /// End time in seconds (optional, for cutting)
     pub end_time: Option<f64>,
+    /// What to do if the output file exists once the download is done;
+    /// `None` follows the settings
+    #[serde(default)]
+    pub if_exists: Option<CollisionPolicy>,
+    /// Size in bytes the download has to fit in; a larger download is
+    /// re-encoded to fit
+    #[serde(default)]
+    pub target_size: Option<u64>,
+}
+
+/// Named quality levels for re-encoding; each encoder maps them to its own
+/// CRF, speed and audio bitrate
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum QualityPreset {
+    /// Quick encodes at a lower quality
+    Fast,
+    #[default]
+    Balanced,
+    /// Slow encodes that are close to the source
+    High,
+}
+
+/// Quality of a re-encode: a preset, optionally with its settings overridden
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EncodeQuality {
+    #[serde(default)]
+    pub preset: QualityPreset,
+    /// Constant rate factor instead of the preset's; lower is better
+    #[serde(default)]
+    pub crf: Option<u32>,
+    /// Target video bitrate in kbps instead of a constant quality
+    #[serde(default)]
+    pub video_bitrate: Option<u32>,
+    /// Audio bitrate in kbps instead of the preset's; lossless codecs
+    /// ignore it
+    #[serde(default)]
+    pub audio_bitrate: Option<u32>,
+}
+
+/// Output shapes for reframing
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum AspectPreset {
+    /// 9:16, for stories and shorts
+    #[serde(rename = "9:16")]
+    Vertical,
+    /// 1:1
+    #[serde(rename = "1:1")]
+    Square,
+    /// 4:5, for feed posts
+    #[serde(rename = "4:5")]
+    Portrait,
+}
+
+/// How the picture fills a frame of another shape
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FillMode {
+    /// Scale to cover the frame and crop what sticks out
+    #[default]
+    Crop,
+    /// Scale to fit inside the frame and fill the rest with a color
+    Pad,
+    /// Scale to fit over a blurred copy that covers the frame
+    Blur,
+}
+
+/// A rectangle of the source picture, in pixels
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Resizing, cropping and reshaping of a cut's video
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Reframe {
+    /// Shape of the output; an explicit width and height take precedence
+    #[serde(default)]
+    pub aspect: Option<AspectPreset>,
+    /// Output width in pixels
+    #[serde(default)]
+    pub width: Option<u32>,
+    /// Output height in pixels
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// Part of the source to keep before it is fit into the output
+    #[serde(default)]
+    pub crop: Option<CropRect>,
+    #[serde(default)]
+    pub fill: FillMode,
+    /// Color of the bars with `FillMode::Pad`, e.g. "black" or "#1a1a1a"
+    #[serde(default)]
+    pub pad_color: Option<String>,
+}
+
+/// How a local file is cut, beyond its range
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CutOptions {
+    /// Input streams to keep by index; `None` keeps all of them
+    #[serde(default)]
+    pub streams: Option<Vec<u32>>,
+    /// Quality if the cut falls back to re-encoding
+    #[serde(default)]
+    pub quality: EncodeQuality,
+    /// Size in bytes the output has to fit in; the cut is then always
+    /// re-encoded, in two passes
+    #[serde(default)]
+    pub target_size: Option<u64>,
+    /// New size or shape for the video; the cut is then always re-encoded
+    #[serde(default)]
+    pub reframe: Option<Reframe>,
 }
 
 /// Progress update sent to frontend
@@ -176,23 +322,33 @@ pub fn format_duration(seconds: f64) -> String {
     }
 }
 
-/// Parse a timestamp string (HH:MM:SS or MM:SS or SS) to seconds
+/// Parse an absolute timestamp (HH:MM:SS, MM:SS, SS, with optional
+/// milliseconds, or 1h2m3s) to seconds
+///
+/// See `timestamp::parse` for relative times and error details.
 pub fn parse_timestamp(timestamp: &str) -> Option<f64> {
-    let parts: Vec<&str> = timestamp.trim().split(':').collect();
-
-    match parts.len() {
-        1 => parts[0].parse::<f64>().ok(),
-        2 => {
-            let mins = parts[0].parse::<f64>().ok()?;
-            let secs = parts[1].parse::<f64>().ok()?;
-            Some(mins * 60.0 + secs)
-        }
-        3 => {
-            let hours = parts[0].parse::<f64>().ok()?;
-            let mins = parts[1].parse::<f64>().ok()?;
-            let secs = parts[2].parse::<f64>().ok()?;
-            Some(hours * 3600.0 + mins * 60.0 + secs)
-        }
+    crate::timestamp::parse_seconds(timestamp, None).ok()
+}
+
+/// Highest video height accepted as a quality (8K)
+const MAX_VIDEO_HEIGHT: u32 = 4320;
+
+/// Normalize a user-supplied quality: a video height (`720`, `720p`) or an
+/// audio quality id (`high`, `medium`, `low`). Without a mode either kind is
+/// accepted.
+pub fn normalize_quality(quality: &str, mode: Option<&DownloadMode>) -> Option<String> {
+    let audio = matches!(quality, "high" | "medium" | "low");
+    let digits = quality.strip_suffix('p').unwrap_or(quality);
+    let height = digits
+        .bytes()
+        .all(|b| b.is_ascii_digit())
+        .then(|| digits.parse::<u32>().ok())
+        .flatten()
+        .filter(|h| (1..=MAX_VIDEO_HEIGHT).contains(h));
+
+    match (mode, height) {
+        (Some(DownloadMode::AudioOnly), _) | (None, None) if audio => Some(quality.to_string()),
+        (Some(DownloadMode::VideoWithAudio) | None, Some(height)) => Some(height.to_string()),
         _ => None,
     }
 }
@@ -234,6 +390,22 @@ mod tests {
         assert_eq!(parse_timestamp("invalid"), None);
     }
 
+    #[test]
+    fn test_parse_unit_timestamp() {
+        assert_eq!(parse_timestamp("90s"), Some(90.0));
+        assert_eq!(parse_timestamp("1m30s"), Some(90.0));
+        assert_eq!(parse_timestamp("1h2m3s"), Some(3723.0));
+        assert_eq!(parse_timestamp("2h"), Some(7200.0));
+        assert_eq!(parse_timestamp("1h5s"), Some(3605.0));
+        assert_eq!(parse_timestamp("1.5m"), Some(90.0));
+        assert_eq!(parse_timestamp("1s2m"), None);
+        assert_eq!(parse_timestamp("1h1h"), None);
+        assert_eq!(parse_timestamp("h"), None);
+        assert_eq!(parse_timestamp("-1m"), None);
+        assert_eq!(parse_timestamp("1h30"), None);
+        assert_eq!(parse_timestamp("ms"), None);
+    }
+
     #[test]
     fn test_format_bytes() {
         assert_eq!(format_bytes(500), "500 B");
@@ -0,0 +1,329 @@
+//! Job validation
+//!
+//! Every entry point that starts a download or a cut (the app, the queue,
+//! the CLI and the native messaging host) checks its request here before any
+//! process is spawned, so bad ranges never reach yt-dlp or ffmpeg and jobs
+//! that would fill the disk are refused up front.
+
+use crate::diskspace::{self, SpaceCheck};
+use crate::encoding::EncodeProfile;
+use crate::error::{AppError, Result};
+use crate::probe;
+use crate::reframe;
+use crate::settings::{self, Settings};
+use crate::streams;
+use crate::timestamp;
+use crate::types::{CutOptions, DownloadMode, DownloadRequest, VideoInfo};
+use crate::ytdlp;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+
+/// Fetched video info kept for validation; the cache is cleared when full
+const MAX_CACHED_INFOS: usize = 256;
+
+/// How far an end time may overshoot the duration before it is rejected.
+/// Players and yt-dlp round durations differently, so a range ending "at the
+/// end" can land slightly past it.
+const DURATION_TOLERANCE: f64 = 0.5;
+
+static INFOS: LazyLock<Mutex<HashMap<String, VideoInfo>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Remember a video's fetched info for its duration and size estimates
+pub fn remember(url: &str, info: &VideoInfo) {
+    let mut infos = INFOS.lock().unwrap();
+    if infos.len() >= MAX_CACHED_INFOS {
+        infos.clear();
+    }
+    infos.insert(url.trim().to_string(), info.clone());
+}
+
+/// Duration of a remote video, if its info was fetched before
+pub fn known_duration(url: &str) -> Option<f64> {
+    INFOS
+        .lock()
+        .unwrap()
+        .get(url.trim())
+        .map(|info| info.duration)
+        .filter(|d| *d > 0.0)
+}
+
+/// Whether a remote video has audio, if its info was fetched before and
+/// yt-dlp listed its formats' codecs
+pub fn known_audio(url: &str) -> Option<bool> {
+    INFOS
+        .lock()
+        .unwrap()
+        .get(url.trim())
+        .and_then(|info| info.has_audio)
+}
+
+/// Info of a remote video from the cache, or by asking yt-dlp when `fetch`
+/// is set
+async fn video_info(url: &str, settings: &Settings, fetch: bool) -> Result<Option<VideoInfo>> {
+    let cached = INFOS.lock().unwrap().get(url.trim()).cloned();
+    match cached {
+        Some(info) => Ok(Some(info)),
+        None if fetch => ytdlp::fetch_video_info(url, settings).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Validate a download before it starts, returning it with the range
+/// snapped to the video's end where it overshoots by a rounding error
+pub async fn validate_download(
+    request: DownloadRequest,
+    settings: &Settings,
+) -> Result<DownloadRequest> {
+    let (request, space) = check_download(request, settings).await?;
+    space.admit(0)?;
+    Ok(request)
+}
+
+/// Validate a download and measure the space it needs, leaving admission to
+/// the caller so a queue can count space held by the jobs ahead of it
+///
+/// The size is estimated from info that is already known; a download
+/// without a range is not worth a yt-dlp call just for the estimate.
+pub async fn check_download(
+    mut request: DownloadRequest,
+    settings: &Settings,
+) -> Result<(DownloadRequest, SpaceCheck)> {
+    ytdlp::validate_url(&request.url)?;
+    let has_range = request.start_time.is_some() || request.end_time.is_some();
+
+    // Malformed ranges fail before spending a yt-dlp call on the duration
+    if has_range {
+        timestamp::check_range(request.start_time, request.end_time, None)?;
+    }
+
+    let info = video_info(&request.url, settings, has_range).await?;
+    let duration = info.as_ref().map(|info| info.duration).filter(|d| *d > 0.0);
+    if let (true, Some(duration)) = (has_range, duration) {
+        request.end_time = request.end_time.map(|end| snap_to_end(end, duration));
+        timestamp::check_range(request.start_time, request.end_time, Some(duration))?;
+    }
+    if let (Some(target_size), Some(duration)) = (request.target_size, duration) {
+        // Whether the clip could fit at all, before downloading it
+        let length = request.end_time.unwrap_or(duration) - request.start_time.unwrap_or(0.0);
+        let ext = match request.mode {
+            DownloadMode::VideoWithAudio => "mp4",
+            DownloadMode::AudioOnly => "mp3",
+        };
+        EncodeProfile::for_size(None, ext, &Default::default(), target_size, length)?;
+    }
+
+    let size = info
+        .as_ref()
+        .and_then(|info| diskspace::download_size(info, &request));
+    let space = SpaceCheck::measure(
+        &diskspace::output_dir(&request.output_path),
+        diskspace::download_needs(size),
+    );
+    Ok((request, space))
+}
+
+/// Validate a cut of a local file, its stream selection, re-encode quality
+/// and reframing before ffmpeg runs, returning the range snapped to the file's end
+/// where it overshoots by a rounding error
+pub async fn validate_cut(
+    input_path: &str,
+    output_path: &str,
+    start_time: f64,
+    end_time: f64,
+    options: &CutOptions,
+) -> Result<(f64, f64)> {
+    timestamp::check_range(Some(start_time), Some(end_time), None)?;
+
+    let input = Path::new(input_path);
+    if !input.is_file() {
+        return Err(AppError::CutError("Input file not found".to_string()));
+    }
+    if output_path.trim().is_empty() {
+        return Err(AppError::CutError("No output file chosen".to_string()));
+    }
+    if is_same_file(input, Path::new(output_path)) {
+        return Err(AppError::CutError(
+            "The output file would overwrite the input".to_string(),
+        ));
+    }
+
+    let media = probe::probe_media(input, &settings::load()).await?;
+    if media.video().is_none() && media.audio().is_none() {
+        return Err(AppError::CutError(
+            "The input has no video or audio to cut".to_string(),
+        ));
+    }
+    let output_ext = Path::new(output_path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let plan = streams::plan(&media, options.streams.as_deref(), &output_ext)?;
+
+    let duration = media.duration.unwrap_or(0.0);
+    let end_time = if duration > 0.0 {
+        let end_time = snap_to_end(end_time, duration);
+        timestamp::check_range(Some(start_time), Some(end_time), Some(duration))?;
+        end_time
+    } else {
+        end_time
+    };
+
+    // The encoders a re-encode would use must take the quality settings
+    let output_media = plan.output_media(&media);
+    match options.target_size {
+        Some(target_size) => EncodeProfile::for_size(
+            Some(&output_media),
+            &output_ext,
+            &options.quality,
+            target_size,
+            end_time - start_time,
+        )?,
+        None => EncodeProfile::new(Some(&output_media), &output_ext, &options.quality)?,
+    };
+    reframe::video_filter(options.reframe.as_ref(), Some(&output_media))?;
+
+    // Without a duration the whole input counts
+    let input_size = input.metadata().map(|m| m.len()).unwrap_or(0);
+    SpaceCheck::measure(
+        &diskspace::output_dir(output_path),
+        diskspace::cut_needs(input_size, duration, start_time, end_time),
+    )
+    .admit(0)?;
+
+    Ok((start_time, end_time))
+}
+
+fn snap_to_end(end: f64, duration: f64) -> f64 {
+    if end > duration && end - duration <= DURATION_TOLERANCE {
+        duration
+    } else {
+        end
+    }
+}
+
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use crate::timestamp::{RangeBound, RangeError};
+
+    fn request(url: &str, start_time: Option<f64>, end_time: Option<f64>) -> DownloadRequest {
+        DownloadRequest {
+            url: url.to_string(),
+            start_time,
+            end_time,
+            ..test_support::download_request()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_download_against_cached_duration() {
+        // Cached, so no yt-dlp is needed
+        let url = "https://www.youtube.com/watch?v=validation1";
+        remember(url, &test_support::video_info());
+        let settings = Settings::default();
+
+        let ok = validate_download(request(url, Some(10.0), Some(100.3)), &settings)
+            .await
+            .unwrap();
+        assert_eq!(ok.end_time, Some(100.0));
+
+        match validate_download(request(url, None, Some(150.0)), &settings).await {
+            Err(AppError::InvalidRange(RangeError::PastEnd { bound, .. })) => {
+                assert_eq!(bound, RangeBound::End)
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        for (start, end) in [
+            (Some(f64::NAN), None),
+            (Some(-1.0), Some(10.0)),
+            (Some(50.0), Some(20.0)),
+            (Some(100.0), None),
+        ] {
+            let result = validate_download(request(url, start, end), &settings).await;
+            assert!(
+                matches!(result, Err(AppError::InvalidRange(_))),
+                "accepted {:?}-{:?}",
+                start,
+                end
+            );
+        }
+
+        // Bad ranges and URLs are rejected without looking up the duration
+        let uncached = "https://www.youtube.com/watch?v=validation2";
+        let result = validate_download(request(uncached, Some(30.0), Some(10.0)), &settings).await;
+        assert!(matches!(
+            result,
+            Err(AppError::InvalidRange(RangeError::Reversed { .. }))
+        ));
+        let result =
+            validate_download(request("https://example.com/video", None, None), &settings).await;
+        assert!(matches!(result, Err(AppError::InvalidUrl)));
+    }
+
+    #[tokio::test]
+    async fn test_check_download_space() {
+        let url = "https://www.youtube.com/watch?v=validation3";
+        remember(url, &test_support::video_info());
+        let settings = Settings::default();
+
+        // Half of the 720p estimate, twice over for streams and merge
+        let (_, space) = check_download(request(url, Some(0.0), Some(50.0)), &settings)
+            .await
+            .unwrap();
+        assert_eq!(space.needed, 200_000_000 + diskspace::SPACE_MARGIN);
+        assert_eq!(space.dir, std::path::PathBuf::from("."));
+
+        // Jobs ahead of it may hold the rest of the disk
+        if space.available.is_some() {
+            assert!(matches!(
+                space.admit(u64::MAX),
+                Err(AppError::InsufficientSpace { .. })
+            ));
+        }
+
+        // Uncached and without a range: no lookup, only the margin
+        let uncached = "https://www.youtube.com/watch?v=validation4";
+        let (_, space) = check_download(request(uncached, None, None), &settings)
+            .await
+            .unwrap();
+        assert_eq!(space.needed, diskspace::SPACE_MARGIN);
+    }
+
+    #[tokio::test]
+    async fn test_validate_cut_rejects_before_ffmpeg() {
+        let dir = test_support::temp_dir("validation");
+        let input = dir.join("input.mp4");
+        std::fs::write(&input, b"not really a video").unwrap();
+        let input = input.to_string_lossy().to_string();
+
+        let result = validate_cut(&input, "out.mp4", 20.0, 10.0, &CutOptions::default()).await;
+        assert!(matches!(result, Err(AppError::InvalidRange(_))));
+
+        let missing = dir.join("missing.mp4").to_string_lossy().to_string();
+        let result = validate_cut(&missing, "out.mp4", 0.0, 10.0, &CutOptions::default()).await;
+        assert!(matches!(result, Err(AppError::CutError(_))));
+
+        let result = validate_cut(&input, &input, 0.0, 10.0, &CutOptions::default()).await;
+        assert!(matches!(result, Err(AppError::CutError(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_snap_to_end() {
+        assert_eq!(snap_to_end(100.4, 100.0), 100.0);
+        assert_eq!(snap_to_end(101.0, 100.0), 101.0);
+        assert_eq!(snap_to_end(50.0, 100.0), 50.0);
+    }
+}
@@ -0,0 +1,471 @@
+//! Output verification
+//!
+//! A clean exit from yt-dlp or ffmpeg doesn't guarantee a playable file:
+//! merges come out truncated and audio tracks come out empty. Every finished
+//! job is probed with ffprobe before it is moved into place, and the result
+//! travels with the completion event as a `VerificationReport`.
+//!
+//! Missing streams, empty tracks and outputs far shorter than requested fail
+//! the job. A duration that is only somewhat off, a codec other than the
+//! expected one or a missing audio track the source may not have had is kept
+//! as a warning in the report.
+
+use crate::encoding::EncodeProfile;
+use crate::error::{AppError, Result};
+use crate::probe::{self, MediaInfo};
+use crate::settings::Settings;
+use crate::types::{DownloadMode, DownloadRequest};
+use serde::Serialize;
+use std::path::Path;
+
+/// How far, in seconds, a duration may be off before it is flagged, on top
+/// of `DURATION_TOLERANCE_SHARE` of the expected duration
+const DURATION_TOLERANCE: f64 = 1.0;
+const DURATION_TOLERANCE_SHARE: f64 = 0.02;
+
+/// Stream-copy cuts start at the keyframe before the requested start, so
+/// they may run this much longer
+const KEYFRAME_TOLERANCE: f64 = 10.0;
+
+/// An output shorter than this share of the expected duration is truncated
+const TRUNCATED_SHARE: f64 = 0.5;
+
+/// Streams shorter than this, in seconds, are empty
+const EMPTY_STREAM: f64 = 0.05;
+
+/// What a finished job should contain
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Expected {
+    pub video: bool,
+    pub audio: bool,
+    /// A missing audio track only warns: the source may have been silent
+    pub audio_optional: bool,
+    /// Length of the requested range, or of the whole media
+    pub duration: Option<f64>,
+    /// Extra seconds the output may run longer than `duration`
+    pub slack: f64,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+}
+
+impl Expected {
+    /// What a download should produce; `full_duration` is the video's length
+    /// and `source_audio` whether it has audio, if they are known
+    ///
+    /// Sites such as X and Reddit serve silent videos, which yt-dlp's
+    /// fallback format downloads as they are, so audio is only required when
+    /// the source listed it.
+    pub fn download(
+        request: &DownloadRequest,
+        full_duration: Option<f64>,
+        source_audio: Option<bool>,
+    ) -> Self {
+        let duration = match (request.start_time, request.end_time) {
+            (None, None) => full_duration,
+            (start, Some(end)) => Some(end - start.unwrap_or(0.0)),
+            (Some(start), None) => full_duration.map(|duration| duration - start),
+        };
+
+        match request.mode {
+            DownloadMode::VideoWithAudio => Self {
+                video: true,
+                audio: source_audio != Some(false),
+                audio_optional: source_audio.is_none(),
+                duration,
+                ..Default::default()
+            },
+            DownloadMode::AudioOnly => Self {
+                audio: true,
+                duration,
+                audio_codec: Some("mp3".to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// What a stream-copy cut should produce: the input's streams and codecs
+    /// for the range. Without a probe of the input only the length is known.
+    pub fn cut(input: Option<&MediaInfo>, start_time: f64, end_time: f64) -> Self {
+        let duration = Some(end_time - start_time);
+        let Some(input) = input else {
+            return Self {
+                duration,
+                slack: KEYFRAME_TOLERANCE,
+                ..Default::default()
+            };
+        };
+
+        let video = input.video();
+        let audio = input.audio();
+        Self {
+            video: video.is_some(),
+            audio: audio.is_some(),
+            duration,
+            slack: KEYFRAME_TOLERANCE,
+            video_codec: video.and_then(|s| s.codec.clone()),
+            audio_codec: audio.and_then(|s| s.codec.clone()),
+            ..Default::default()
+        }
+    }
+
+    /// The same output after falling back to re-encoding with `profile`,
+    /// which cuts exactly
+    pub fn reencoded(self, profile: &EncodeProfile) -> Self {
+        Self {
+            slack: 0.0,
+            video_codec: profile
+                .video_codec()
+                .filter(|_| self.video)
+                .map(str::to_string),
+            audio_codec: profile
+                .audio_codec()
+                .filter(|_| self.audio)
+                .map(str::to_string),
+            ..self
+        }
+    }
+}
+
+/// Result of checking a finished output, sent with its completion event
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct VerificationReport {
+    /// Whether ffprobe checked the file; false if it couldn't be run
+    pub verified: bool,
+    /// Duration in seconds
+    pub duration: Option<f64>,
+    pub expected_duration: Option<f64>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    /// Problems that didn't fail the job
+    pub warnings: Vec<String>,
+}
+
+/// A finished download or cut
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct VerifiedOutput {
+    pub path: String,
+    pub verification: VerificationReport,
+}
+
+/// Check a finished output against what the job should have produced, with
+/// the ffprobe `settings` resolve to
+///
+/// When ffprobe itself can't be started the report says so instead of
+/// throwing away a finished download.
+pub async fn verify(
+    path: &Path,
+    expected: &Expected,
+    settings: &Settings,
+) -> Result<VerificationReport> {
+    let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if size == 0 {
+        return Err(AppError::VerificationFailed(
+            "The file is missing or empty".to_string(),
+        ));
+    }
+
+    let output = match probe::run_ffprobe(path, settings).await {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("Skipping verification of {}: {}", path.display(), e);
+            return Ok(VerificationReport {
+                verified: false,
+                duration: None,
+                expected_duration: expected.duration,
+                video_codec: None,
+                audio_codec: None,
+                warnings: vec![format!("Not verified: ffprobe could not be run ({})", e)],
+            });
+        }
+    };
+    if !output.status.success() {
+        return Err(AppError::VerificationFailed(
+            "ffprobe could not read the file".to_string(),
+        ));
+    }
+
+    check(&probe::parse_media_info(&output.stdout)?, expected)
+}
+
+/// Compare what ffprobe found with the expected output
+fn check(media: &MediaInfo, expected: &Expected) -> Result<VerificationReport> {
+    let fail = |reason: String| Err(AppError::VerificationFailed(reason));
+    let mut warnings = Vec::new();
+
+    let duration = media
+        .duration
+        .or_else(|| {
+            media
+                .streams
+                .iter()
+                .filter_map(|s| s.duration)
+                .reduce(f64::max)
+        })
+        .filter(|d| *d > 0.0);
+    let Some(duration) = duration else {
+        return fail("The file has no playable duration".to_string());
+    };
+
+    let video = media.video();
+    let audio = media.audio();
+    if expected.video && video.is_none() {
+        return fail("The file has no video stream".to_string());
+    }
+    if expected.audio && audio.is_none() {
+        if !expected.audio_optional {
+            return fail("The file has no audio stream".to_string());
+        }
+        warnings.push("The file has no audio stream".to_string());
+    }
+    if !expected.video && expected.audio && video.is_some() {
+        warnings.push("The file has a video stream where only audio was expected".to_string());
+    }
+
+    for (name, stream) in [("video", video), ("audio", audio)] {
+        if let Some(length) = stream.and_then(|s| s.duration) {
+            if length < EMPTY_STREAM {
+                return fail(format!("The {} track is empty", name));
+            }
+        }
+    }
+
+    let tolerance = DURATION_TOLERANCE + duration * DURATION_TOLERANCE_SHARE;
+    if let Some(wanted) = expected.duration.filter(|d| *d > 0.0) {
+        if duration < wanted * TRUNCATED_SHARE {
+            return fail(format!(
+                "The file is truncated: {:.1}s of {:.1}s",
+                duration, wanted
+            ));
+        }
+        if duration < wanted - tolerance || duration > wanted + tolerance + expected.slack {
+            warnings.push(format!(
+                "Duration is {:.1}s, expected {:.1}s",
+                duration, wanted
+            ));
+        }
+    }
+
+    // A merge that stopped early leaves one track shorter than the other
+    if let (Some(v), Some(a)) = (
+        video.and_then(|s| s.duration),
+        audio.and_then(|s| s.duration),
+    ) {
+        if (v - a).abs() > tolerance {
+            warnings.push(format!(
+                "The audio track is {:.1}s but the video track is {:.1}s",
+                a, v
+            ));
+        }
+    }
+
+    for (name, stream, codec) in [
+        ("Video", video, &expected.video_codec),
+        ("Audio", audio, &expected.audio_codec),
+    ] {
+        if let (Some(actual), Some(codec)) = (stream.and_then(|s| s.codec.as_deref()), codec) {
+            if !actual.eq_ignore_ascii_case(codec) {
+                warnings.push(format!("{} codec is {}, expected {}", name, actual, codec));
+            }
+        }
+    }
+
+    Ok(VerificationReport {
+        verified: true,
+        duration: Some(duration),
+        expected_duration: expected.duration,
+        video_codec: video.and_then(|s| s.codec.clone()),
+        audio_codec: audio.and_then(|s| s.codec.clone()),
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    /// ffprobe output for a merged 1080p download
+    const MERGED: &str = r#"{
+        "streams": [
+            {"codec_name": "h264", "codec_type": "video", "duration": "212.040000", "disposition": {"attached_pic": 0}},
+            {"codec_name": "aac", "codec_type": "audio", "duration": "212.091000", "disposition": {"attached_pic": 0}}
+        ],
+        "format": {"duration": "212.091000"}
+    }"#;
+
+    /// ffprobe output for an mp3 with embedded cover art
+    const MP3: &str = r#"{
+        "streams": [
+            {"codec_name": "mp3", "codec_type": "audio", "duration": "212.100000", "disposition": {"attached_pic": 0}},
+            {"codec_name": "mjpeg", "codec_type": "video", "duration": "212.100000", "disposition": {"attached_pic": 1}}
+        ],
+        "format": {"duration": "212.100000"}
+    }"#;
+
+    fn request(mode: DownloadMode, start: Option<f64>, end: Option<f64>) -> DownloadRequest {
+        DownloadRequest {
+            mode,
+            start_time: start,
+            end_time: end,
+            ..test_support::download_request()
+        }
+    }
+
+    fn media(json: &str) -> MediaInfo {
+        probe::parse_media_info(json.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_cover_art_is_not_video() {
+        let mp3 = media(MP3);
+        assert_eq!(mp3.duration, Some(212.1));
+        assert_eq!(mp3.streams.len(), 2);
+        assert!(mp3.video().is_none());
+        assert_eq!(mp3.audio().unwrap().codec.as_deref(), Some("mp3"));
+    }
+
+    #[test]
+    fn test_expected_download() {
+        let video = DownloadMode::VideoWithAudio;
+        let expected =
+            Expected::download(&request(video.clone(), None, None), Some(212.0), Some(true));
+        assert!(expected.video && expected.audio);
+        assert_eq!(expected.duration, Some(212.0));
+
+        let range = request(video.clone(), Some(10.0), Some(40.0));
+        assert_eq!(Expected::download(&range, None, None).duration, Some(30.0));
+        let tail = request(video, Some(200.0), None);
+        assert_eq!(
+            Expected::download(&tail, Some(212.0), None).duration,
+            Some(12.0)
+        );
+        assert_eq!(Expected::download(&tail, None, None).duration, None);
+
+        let audio = Expected::download(&request(DownloadMode::AudioOnly, None, None), None, None);
+        assert!(!audio.video && audio.audio);
+        assert_eq!(audio.audio_codec.as_deref(), Some("mp3"));
+    }
+
+    #[test]
+    fn test_silent_download() {
+        let no_audio = MediaInfo {
+            streams: media(MERGED).streams[..1].to_vec(),
+            ..media(MERGED)
+        };
+        let silent = |source_audio| {
+            let request = request(DownloadMode::VideoWithAudio, None, None);
+            check(
+                &no_audio,
+                &Expected::download(&request, Some(212.0), source_audio),
+            )
+        };
+
+        // Not knowing whether the source has audio only warns
+        let warnings = silent(None).unwrap().warnings;
+        assert_eq!(warnings, vec!["The file has no audio stream"]);
+        // A source without audio is expected to be silent
+        assert!(silent(Some(false)).unwrap().warnings.is_empty());
+        // A source with audio must keep it
+        assert!(silent(Some(true)).is_err());
+    }
+
+    #[test]
+    fn test_check_passes() {
+        let video = Expected::download(
+            &request(DownloadMode::VideoWithAudio, None, None),
+            Some(212.0),
+            Some(true),
+        );
+        let report = check(&media(MERGED), &video).unwrap();
+        assert!(report.verified);
+        assert_eq!(report.duration, Some(212.091));
+        assert_eq!(report.video_codec.as_deref(), Some("h264"));
+        assert_eq!(report.audio_codec.as_deref(), Some("aac"));
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+
+        // Cover art is not an unexpected video stream
+        let audio = Expected::download(&request(DownloadMode::AudioOnly, None, None), None, None);
+        assert!(check(&media(MP3), &audio).unwrap().warnings.is_empty());
+    }
+
+    #[test]
+    fn test_check_fails() {
+        let video = Expected::download(
+            &request(DownloadMode::VideoWithAudio, None, None),
+            Some(212.0),
+            Some(true),
+        );
+
+        let no_audio = MediaInfo {
+            streams: media(MERGED).streams[..1].to_vec(),
+            ..media(MERGED)
+        };
+        assert!(check(&no_audio, &video).is_err());
+
+        let mut empty_audio = media(MERGED);
+        empty_audio.streams[1].duration = Some(0.0);
+        let error = check(&empty_audio, &video).unwrap_err().to_string();
+        assert!(error.contains("audio track is empty"), "{}", error);
+
+        let truncated = MediaInfo {
+            duration: Some(60.0),
+            ..Default::default()
+        };
+        let expected = Expected {
+            duration: Some(212.0),
+            ..Default::default()
+        };
+        let error = check(&truncated, &expected).unwrap_err().to_string();
+        assert!(error.contains("truncated"), "{}", error);
+
+        assert!(check(&MediaInfo::default(), &Expected::default()).is_err());
+    }
+
+    #[test]
+    fn test_check_warnings() {
+        // A few seconds short, with the audio cut off before the video
+        let mut short = media(MERGED);
+        short.duration = Some(205.0);
+        short.streams[1].duration = Some(205.0);
+        let video = Expected::download(
+            &request(DownloadMode::VideoWithAudio, None, None),
+            Some(212.0),
+            Some(true),
+        );
+        let warnings = check(&short, &video).unwrap().warnings;
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+
+        // Wrong codec
+        let audio = Expected::download(&request(DownloadMode::AudioOnly, None, None), None, None);
+        let opus = media(&MP3.replace("\"mp3\"", "\"opus\""));
+        let warnings = check(&opus, &audio).unwrap().warnings;
+        assert_eq!(warnings, vec!["Audio codec is opus, expected mp3"]);
+    }
+
+    #[test]
+    fn test_check_cut() {
+        let input = media(MERGED);
+        let mut cut = MediaInfo {
+            duration: Some(34.0),
+            ..input.clone()
+        };
+        for stream in &mut cut.streams {
+            stream.duration = Some(34.0);
+        }
+
+        // Stream copy may start at an earlier keyframe
+        let copy = Expected::cut(Some(&input), 60.0, 90.0);
+        assert!(check(&cut, &copy).unwrap().warnings.is_empty());
+
+        // Re-encoding cuts exactly and changes the codecs
+        let profile = EncodeProfile::new(Some(&input), "mp4", &Default::default()).unwrap();
+        let reencoded = copy.reencoded(&profile);
+        assert_eq!(reencoded.video_codec.as_deref(), Some("h264"));
+        assert_eq!(check(&cut, &reencoded).unwrap().warnings.len(), 1);
+
+        // Without a probe of the input only the length is checked
+        let unknown = Expected::cut(None, 60.0, 90.0);
+        assert!(!unknown.video && !unknown.audio);
+        assert!(check(&cut, &unknown).unwrap().warnings.is_empty());
+    }
+}
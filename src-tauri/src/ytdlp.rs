@@ -3,15 +3,22 @@
 //! Handles all interactions with the yt-dlp CLI tool.
 //! Commands are built using proper argument arrays to prevent injection.
 
-use crate::deps;
+use crate::deps::{self, Dependency};
 use crate::error::{AppError, Result};
+use crate::ffmpeg;
+use crate::settings::Settings;
+use crate::staging::StagedOutput;
 use crate::types::{
-    format_bytes, format_duration, AudioQuality, DownloadMode, Platform, ProgressStage,
-    ProgressUpdate, VideoFormat, VideoInfo, VideoQuality,
+    format_bytes, format_duration, parse_timestamp, AudioQuality, DownloadMode, DownloadRequest,
+    Platform, ProgressStage, ProgressUpdate, VideoFormat, VideoInfo, VideoQuality,
 };
+use crate::validation;
+use crate::verification::{Expected, VerifiedOutput};
 use regex::Regex;
 use serde::Deserialize;
+use std::path::Path;
 use std::process::Stdio;
+use std::sync::LazyLock;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
 use tokio::sync::mpsc;
@@ -20,11 +27,6 @@ use tokio::sync::mpsc;
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
-/// Get the yt-dlp command (local or system)
-async fn get_ytdlp_cmd() -> String {
-    deps::get_ytdlp_command().await
-}
-
 /// Raw format data from yt-dlp JSON output
 #[derive(Debug, Deserialize)]
 struct RawFormat {
@@ -50,12 +52,26 @@ struct RawVideoInfo {
     duration: Option<f64>,
     thumbnail: Option<String>,
     uploader: Option<String>,
+    upload_date: Option<String>,
     formats: Option<Vec<RawFormat>>,
+    /// Range of a YouTube clip within the full video
+    section_start: Option<f64>,
+    section_end: Option<f64>,
 }
 
-/// Check if yt-dlp is available
-pub async fn check_ytdlp() -> Result<()> {
-    let ytdlp_cmd = get_ytdlp_cmd().await;
+/// A supported URL and the time range embedded in it, if any
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedUrl {
+    pub platform: Platform,
+    /// Start time in seconds (`?t=90`, `&t=1m30s`, `#t=...`, `?start=30`)
+    pub start_time: Option<f64>,
+    /// End time in seconds (`?end=60` on YouTube embeds)
+    pub end_time: Option<f64>,
+}
+
+/// Check if the yt-dlp `settings` resolve to is available
+pub async fn check_ytdlp(settings: &Settings) -> Result<()> {
+    let ytdlp_cmd = deps::resolve_command(Dependency::Ytdlp, settings);
     let mut cmd = Command::new(&ytdlp_cmd);
     cmd.arg("--version");
 
@@ -71,12 +87,10 @@ pub async fn check_ytdlp() -> Result<()> {
     }
 }
 
-/// Validate that a URL is from a supported platform and return which platform it matches
-/// This is a security measure to prevent arbitrary URL processing
-pub fn validate_url(url: &str) -> Result<Platform> {
-    let url = url.trim();
-
-    let platform_patterns: &[(&[&str], Platform)] = &[
+/// URL patterns of every supported platform, compiled once since batch
+/// imports validate hundreds of links
+static PLATFORM_PATTERNS: LazyLock<Vec<(Regex, Platform)>> = LazyLock::new(|| {
+    let patterns: &[(&[&str], Platform)] = &[
         (
             &[
                 r"^https?://(www\.)?youtube\.com/watch\?v=[\w-]+",
@@ -84,6 +98,7 @@ pub fn validate_url(url: &str) -> Result<Platform> {
                 r"^https?://youtu\.be/[\w-]+",
                 r"^https?://(www\.)?youtube\.com/embed/[\w-]+",
                 r"^https?://m\.youtube\.com/watch\?v=[\w-]+",
+                r"^https?://(www\.)?youtube\.com/clip/[\w-]+",
             ],
             Platform::YouTube,
         ),
@@ -113,27 +128,102 @@ pub fn validate_url(url: &str) -> Result<Platform> {
             &[r"^https?://(www\.)?soundcloud\.com/[\w-]+/[\w-]+"],
             Platform::SoundCloud,
         ),
+        (
+            &[r"^https?://(www\.|m\.)?twitch\.tv/videos/\d+"],
+            Platform::Twitch,
+        ),
     ];
 
-    for (patterns, platform) in platform_patterns {
-        for pattern in *patterns {
-            let re = Regex::new(pattern).unwrap();
-            if re.is_match(url) {
-                return Ok(platform.clone());
-            }
-        }
-    }
+    patterns
+        .iter()
+        .flat_map(|(patterns, platform)| {
+            patterns
+                .iter()
+                .map(|pattern| (Regex::new(pattern).unwrap(), platform.clone()))
+        })
+        .collect()
+});
+
+/// Validate that a URL is from a supported platform and return which platform it matches
+/// This is a security measure to prevent arbitrary URL processing
+pub fn validate_url(url: &str) -> Result<Platform> {
+    let url = url.trim();
 
-    Err(AppError::InvalidUrl)
+    PLATFORM_PATTERNS
+        .iter()
+        .find(|(re, _)| re.is_match(url))
+        .map(|(_, platform)| platform.clone())
+        .ok_or(AppError::InvalidUrl)
 }
 
-/// Fetch video information using yt-dlp
-pub async fn fetch_video_info(url: &str) -> Result<VideoInfo> {
+/// Validate a URL like `validate_url` and pull out the start (and end) time
+/// the link points at
+pub fn parse_url(url: &str) -> Result<ParsedUrl> {
     let platform = validate_url(url)?;
+    let url = url.trim();
+
+    let (start_keys, end_keys): (&[&str], &[&str]) = match platform {
+        Platform::YouTube => (&["t", "start"], &["end"]),
+        Platform::Twitch => (&["t"], &[]),
+        _ => (&[], &[]),
+    };
+
+    let start_time = start_keys
+        .iter()
+        .find_map(|key| url_param(url, key))
+        .and_then(parse_url_time);
+    let end_time = end_keys
+        .iter()
+        .find_map(|key| url_param(url, key))
+        .and_then(parse_url_time)
+        .filter(|end| !matches!(start_time, Some(start) if start >= *end));
+
+    Ok(ParsedUrl {
+        platform,
+        start_time,
+        end_time,
+    })
+}
+
+/// Value of a query or fragment parameter, e.g. `t` in `?t=90` or `#t=1m30s`
+fn url_param<'a>(url: &'a str, name: &str) -> Option<&'a str> {
+    let (rest, fragment) = url.split_once('#').unwrap_or((url, ""));
+    let query = rest.split_once('?').map_or("", |(_, query)| query);
+
+    query
+        .split('&')
+        .chain(fragment.split('&'))
+        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+}
+
+/// Parse a time from a link; zero and malformed values are ignored
+fn parse_url_time(value: &str) -> Option<f64> {
+    parse_timestamp(value).filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+}
+
+/// Keep only the parts of an embedded range that fall inside the video
+fn embedded_range(
+    start: Option<f64>,
+    end: Option<f64>,
+    duration: f64,
+) -> (Option<f64>, Option<f64>) {
+    let within = |time: &f64| *time > 0.0 && (duration <= 0.0 || *time < duration);
+    let start = start.filter(within);
+    let end = end
+        .filter(within)
+        .filter(|end| !matches!(start, Some(start) if start >= *end));
+
+    (start, end)
+}
+
+/// Fetch video information using yt-dlp
+pub async fn fetch_video_info(url: &str, settings: &Settings) -> Result<VideoInfo> {
+    let parsed = parse_url(url)?;
+    let platform = parsed.platform;
 
     // Use yt-dlp to get JSON metadata
     // Arguments are passed as separate strings to prevent shell injection
-    let ytdlp_cmd = get_ytdlp_cmd().await;
+    let ytdlp_cmd = deps::resolve_command(Dependency::Ytdlp, settings);
     let mut cmd = Command::new(&ytdlp_cmd);
     cmd.args([
         "--dump-json",     // Output JSON metadata
@@ -141,8 +231,10 @@ pub async fn fetch_video_info(url: &str) -> Result<VideoInfo> {
         "--no-warnings",   // Suppress warnings
         "--no-playlist",   // Only process single video
         "--flat-playlist", // Don't extract playlist videos
-        url,
     ]);
+    cmd.args(ffmpeg_location_args(settings));
+    cmd.args(js_runtime_args(settings).await);
+    cmd.arg(url);
 
     #[cfg(windows)]
     cmd.creation_flags(CREATE_NO_WINDOW);
@@ -177,6 +269,13 @@ pub async fn fetch_video_info(url: &str) -> Result<VideoInfo> {
 
     let duration = raw.duration.unwrap_or(0.0);
 
+    // Pre-fill the cut range from the link, or from yt-dlp for YouTube clips
+    let (start_time, end_time) = embedded_range(
+        parsed.start_time.or(raw.section_start),
+        parsed.end_time.or(raw.section_end),
+        duration,
+    );
+
     // Extract video qualities (unique heights from video formats)
     let mut video_qualities = extract_video_qualities(&raw_formats, duration);
 
@@ -186,6 +285,7 @@ pub async fn fetch_video_info(url: &str) -> Result<VideoInfo> {
             height: 0,
             label: "Best available".to_string(),
             filesize_approx: None,
+            filesize: None,
         });
     }
 
@@ -217,18 +317,38 @@ pub async fn fetch_video_info(url: &str) -> Result<VideoInfo> {
         },
     ];
 
-    Ok(VideoInfo {
+    let info = VideoInfo {
         id: raw.id,
         title: raw.title,
         duration,
         duration_string: format_duration(duration),
         thumbnail: raw.thumbnail,
         uploader: raw.uploader,
+        upload_date: raw.upload_date,
         formats,
         video_qualities,
         audio_qualities,
         platform,
-    })
+        start_time,
+        end_time,
+        has_audio: source_has_audio(&raw_formats),
+    };
+    validation::remember(url, &info);
+
+    Ok(info)
+}
+
+/// Whether the source has audio: some format carries it, or every format
+/// says it has none. Many extractors leave `acodec` out, which says nothing.
+fn source_has_audio(raw_formats: &[RawFormat]) -> Option<bool> {
+    let audio = |f: &RawFormat| f.acodec.as_deref().map(|a| a != "none");
+    if raw_formats.iter().any(|f| audio(f) == Some(true)) {
+        Some(true)
+    } else if !raw_formats.is_empty() && raw_formats.iter().all(|f| audio(f) == Some(false)) {
+        Some(false)
+    } else {
+        None
+    }
 }
 
 /// Convert raw format to our format type
@@ -315,21 +435,18 @@ fn extract_video_qualities(raw_formats: &[RawFormat], duration: f64) -> Vec<Vide
         let format = best_per_height[&height];
 
         // Use filesize if available, otherwise estimate from bitrate * duration
-        let filesize_approx = format
-            .filesize
-            .or(format.filesize_approx)
-            .or_else(|| {
-                let video_tbr = format.tbr?;
-                let total_tbr = video_tbr + best_audio_tbr;
-                // tbr is in kbps: size = tbr * 1000 / 8 * duration
-                Some((total_tbr * 1000.0 / 8.0 * duration) as u64)
-            })
-            .map(format_bytes);
+        let filesize = format.filesize.or(format.filesize_approx).or_else(|| {
+            let video_tbr = format.tbr?;
+            let total_tbr = video_tbr + best_audio_tbr;
+            // tbr is in kbps: size = tbr * 1000 / 8 * duration
+            Some((total_tbr * 1000.0 / 8.0 * duration) as u64)
+        });
 
         qualities.push(VideoQuality {
             height,
             label: format!("{}p", height),
-            filesize_approx,
+            filesize_approx: filesize.map(format_bytes),
+            filesize,
         });
     }
 
@@ -368,18 +485,26 @@ fn extract_height(quality: &str) -> u32 {
     quality.trim_end_matches('p').parse().unwrap_or(0)
 }
 
-/// Download video with progress reporting
+/// Download video with progress reporting, returning the saved file with
+/// its verification report
 pub async fn download_video(
-    url: &str,
-    mode: &DownloadMode,
-    quality: &str,
-    output_path: &str,
-    start_time: Option<f64>,
-    end_time: Option<f64>,
+    request: &DownloadRequest,
+    settings: &Settings,
     progress_tx: mpsc::Sender<ProgressUpdate>,
-) -> Result<String> {
+) -> Result<VerifiedOutput> {
+    let url = request.url.as_str();
+    let mode = &request.mode;
+    let quality = request.quality.as_str();
+    let (start_time, end_time) = (request.start_time, request.end_time);
+
     validate_url(url)?;
 
+    // yt-dlp writes next to the destination and the result is moved into
+    // place only once it is complete and readable
+    let if_exists = request.if_exists.unwrap_or(settings.collision_policy);
+    let staged = StagedOutput::new(Path::new(&request.output_path), if_exists)?;
+    let output_path = staged.temp_path().to_string_lossy().to_string();
+
     let mut args = vec![
         "--newline".to_string(), // Progress on new lines
         "--no-warnings".to_string(),
@@ -429,8 +554,20 @@ pub async fn download_video(
         }
     }
 
+    // `%` starts a field in yt-dlp's output template; folders named after an
+    // uploader such as "100% Music" must be taken literally
     args.push("-o".to_string());
-    args.push(output_path.to_string());
+    args.push(output_path.replace('%', "%%"));
+
+    // Parallel fragments and optional external downloader
+    let aria2c_cmd = if settings.use_aria2c && deps::is_aria2c_available(settings).await {
+        Some(deps::resolve_command(Dependency::Aria2c, settings))
+    } else {
+        None
+    };
+    args.extend(downloader_args(settings, aria2c_cmd.as_deref()));
+    args.extend(ffmpeg_location_args(settings));
+    args.extend(js_runtime_args(settings).await);
 
     // yt-dlp supports --download-sections for cutting during download
     // This is more efficient than downloading then cutting with ffmpeg
@@ -471,7 +608,7 @@ pub async fn download_video(
         })
         .await;
 
-    let ytdlp_cmd = get_ytdlp_cmd().await;
+    let ytdlp_cmd = deps::resolve_command(Dependency::Ytdlp, settings);
     let mut cmd = Command::new(&ytdlp_cmd);
     cmd.args(&args)
         .stdout(Stdio::piped())
@@ -487,10 +624,6 @@ pub async fn download_video(
     let stdout = child.stdout.take().unwrap();
     let mut reader = BufReader::new(stdout);
 
-    // Parse progress from yt-dlp output
-    let progress_regex =
-        Regex::new(r"\[download\]\s+(\d+\.?\d*)%.*?(\d+\.?\d*\w+/s)?.*?ETA\s+(\S+)?").unwrap();
-
     // Read raw bytes to handle non-UTF-8 output (e.g. video titles with special characters)
     let mut raw_line = Vec::new();
     loop {
@@ -503,26 +636,21 @@ pub async fn download_video(
             break;
         }
         let line = String::from_utf8_lossy(&raw_line);
-        let line = line.trim_end_matches('\n').trim_end_matches('\r');
 
-        if let Some(caps) = progress_regex.captures(line) {
-            let percent: f64 = caps
-                .get(1)
-                .and_then(|m| m.as_str().parse().ok())
-                .unwrap_or(0.0);
-
-            let speed = caps.get(2).map(|m| m.as_str().to_string());
-            let eta = caps.get(3).map(|m| m.as_str().to_string());
-
-            let _ = progress_tx
-                .send(ProgressUpdate {
-                    stage: ProgressStage::Downloading,
-                    percent,
-                    message: format!("Downloading... {:.1}%", percent),
-                    speed,
-                    eta,
-                })
-                .await;
+        // aria2c redraws its readout with carriage returns, so one "line"
+        // can hold several progress updates
+        for segment in line.trim_end_matches('\n').split('\r') {
+            if let Some(progress) = parse_progress_line(segment) {
+                let _ = progress_tx
+                    .send(ProgressUpdate {
+                        stage: ProgressStage::Downloading,
+                        percent: progress.percent,
+                        message: format!("Downloading... {:.1}%", progress.percent),
+                        speed: progress.speed,
+                        eta: progress.eta,
+                    })
+                    .await;
+            }
         }
     }
 
@@ -538,7 +666,44 @@ pub async fn download_video(
     // Resolve actual output file: yt-dlp may change the extension during
     // post-processing (e.g. audio extraction, merging). Check the expected
     // path first, then search the directory for a matching file.
-    let actual_path = resolve_output_path(output_path);
+    let produced = resolve_output_path(&output_path);
+    let mut expected = Expected::download(
+        request,
+        validation::known_duration(url),
+        validation::known_audio(url),
+    );
+
+    // Re-encode into a second temp file if the download is over its size
+    let produced = match request.target_size {
+        Some(target_size) => {
+            let ext = Path::new(&produced)
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let sized = staged.scratch_path(&format!("sized.{}", ext));
+            let passlog = staged.scratch_path("passlog");
+            match ffmpeg::fit_to_size(
+                Path::new(&produced),
+                &sized,
+                &passlog,
+                target_size,
+                settings,
+                &progress_tx,
+            )
+            .await?
+            {
+                Some(profile) => {
+                    expected = expected.reencoded(&profile);
+                    sized.to_string_lossy().to_string()
+                }
+                None => produced,
+            }
+        }
+        None => produced,
+    };
+    let output = staged
+        .commit(Path::new(&produced), &expected, settings)
+        .await?;
 
     let _ = progress_tx
         .send(ProgressUpdate {
@@ -558,15 +723,116 @@ pub async fn download_video(
         })
         .await;
 
-    Ok(actual_path)
+    Ok(output)
+}
+
+/// Build the fragment concurrency and external downloader arguments
+fn downloader_args(settings: &Settings, aria2c_cmd: Option<&str>) -> Vec<String> {
+    let mut args = Vec::new();
+
+    if settings.concurrent_fragments > 1 {
+        args.push("--concurrent-fragments".to_string());
+        args.push(settings.concurrent_fragments.to_string());
+    }
+
+    if let Some(aria2c) = aria2c_cmd {
+        // yt-dlp accepts a path here and derives the downloader name from it
+        args.push("--downloader".to_string());
+        args.push(aria2c.to_string());
+        args.push("--downloader-args".to_string());
+        args.push("aria2c:-x 16 -s 16 -k 1M --summary-interval=1".to_string());
+    }
+
+    args
+}
+
+/// Point yt-dlp at the ffmpeg DLCut resolves, so merging and cutting use the
+/// same binary as everything else instead of whichever is on PATH
+///
+/// Empty when no ffmpeg is found; yt-dlp then reports it missing itself.
+fn ffmpeg_location_args(settings: &Settings) -> Vec<String> {
+    deps::resolve_binary(Dependency::Ffmpeg, settings)
+        .map(|ffmpeg| {
+            vec![
+                "--ffmpeg-location".to_string(),
+                ffmpeg.path.to_string_lossy().to_string(),
+            ]
+        })
+        .unwrap_or_default()
+}
+
+/// Build the `--js-runtimes` argument for the detected JavaScript runtime
+///
+/// Empty when no runtime is installed or yt-dlp predates the option.
+async fn js_runtime_args(settings: &Settings) -> Vec<String> {
+    let Some((runtime, binary)) = deps::resolve_js_runtime(settings) else {
+        return Vec::new();
+    };
+
+    if !deps::ytdlp_supports_js_runtimes(settings).await {
+        return Vec::new();
+    }
+
+    js_runtime_arg(runtime, &binary.path.to_string_lossy())
+}
+
+/// Format a runtime and its path the way `--js-runtimes` expects (`NAME:PATH`)
+fn js_runtime_arg(runtime: deps::JsRuntime, path: &str) -> Vec<String> {
+    vec![
+        "--js-runtimes".to_string(),
+        format!("{}:{}", runtime.ytdlp_name(), path),
+    ]
+}
+
+/// A single progress reading from the download output
+#[derive(Debug, PartialEq)]
+struct ParsedProgress {
+    percent: f64,
+    speed: Option<String>,
+    eta: Option<String>,
+}
+
+/// yt-dlp's native progress line, e.g. `[download]  42.0% of 10.00MiB at 1.00MiB/s ETA 00:05`
+static YTDLP_PROGRESS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[download\]\s+(\d+\.?\d*)%.*?(\d+\.?\d*\w+/s)?.*?ETA\s+(\S+)?").unwrap()
+});
+
+/// aria2c's console readout, e.g. `[#2089b0 1.2MiB/10MiB(12%) CN:16 DL:3.4MiB ETA:2s]`
+static ARIA2C_PROGRESS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[#\w+\s+[\d.]+\w*/[\d.]+\w*\((\d+)%\)(?:\s+CN:\d+)?(?:\s+SD:\d+)?(?:\s+DL:([\d.]+\w*))?(?:\s+ETA:(\w+))?\]").unwrap()
+});
+
+/// Parse a progress line from either yt-dlp or aria2c
+fn parse_progress_line(line: &str) -> Option<ParsedProgress> {
+    if let Some(caps) = YTDLP_PROGRESS.captures(line) {
+        return Some(ParsedProgress {
+            percent: caps
+                .get(1)
+                .and_then(|m| m.as_str().parse().ok())
+                .unwrap_or(0.0),
+            speed: caps.get(2).map(|m| m.as_str().to_string()),
+            eta: caps.get(3).map(|m| m.as_str().to_string()),
+        });
+    }
+
+    let caps = ARIA2C_PROGRESS.captures(line)?;
+    Some(ParsedProgress {
+        percent: caps
+            .get(1)
+            .and_then(|m| m.as_str().parse().ok())
+            .unwrap_or(0.0),
+        // aria2c reports bare rates, match yt-dlp's "/s" suffix
+        speed: caps.get(2).map(|m| format!("{}/s", m.as_str())),
+        eta: caps.get(3).map(|m| m.as_str().to_string()),
+    })
 }
 
 /// Resolve the actual output file path after yt-dlp finishes.
 /// yt-dlp may change the extension during post-processing (e.g. .webm -> .mp3),
-/// so the file might not be at the exact path we specified with -o.
+/// so the file might not be at the exact path we specified with -o. Only the
+/// expected file's own folder is searched, which may be a subfolder chosen by
+/// the output rules.
 fn resolve_output_path(expected: &str) -> String {
-    use std::path::Path;
-
     let expected_path = Path::new(expected);
     if expected_path.exists() {
         return expected.to_string();
@@ -574,6 +840,12 @@ fn resolve_output_path(expected: &str) -> String {
 
     // Search for files with the same stem but different extension
     if let (Some(dir), Some(stem)) = (expected_path.parent(), expected_path.file_stem()) {
+        // A bare file name has an empty parent: the working directory
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
         if let Ok(entries) = std::fs::read_dir(dir) {
             let stem_str = stem.to_string_lossy();
             // Find the most recently modified file matching the stem
@@ -605,6 +877,7 @@ fn resolve_output_path(expected: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support;
 
     #[test]
     fn test_validate_url() {
@@ -687,6 +960,148 @@ mod tests {
         assert!(validate_url("not a url").is_err());
         assert!(validate_url("").is_err());
         assert!(validate_url("https://vimeo.com/123456").is_err());
+
+        // Twitch VODs and YouTube clips
+        assert_eq!(
+            validate_url("https://www.twitch.tv/videos/1234567890").unwrap(),
+            Platform::Twitch
+        );
+        assert_eq!(
+            validate_url("https://youtube.com/clip/UgkxAbC123").unwrap(),
+            Platform::YouTube
+        );
+        assert!(validate_url("https://www.twitch.tv/somechannel").is_err());
+    }
+
+    #[test]
+    fn test_parse_url_times() {
+        let parsed = parse_url("https://youtu.be/dQw4w9WgXcQ?t=90").unwrap();
+        assert_eq!(parsed.platform, Platform::YouTube);
+        assert_eq!(parsed.start_time, Some(90.0));
+        assert_eq!(parsed.end_time, None);
+
+        let parsed = parse_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=1m30s").unwrap();
+        assert_eq!(parsed.start_time, Some(90.0));
+
+        let parsed = parse_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ#t=2m").unwrap();
+        assert_eq!(parsed.start_time, Some(120.0));
+
+        let parsed =
+            parse_url("https://www.youtube.com/embed/dQw4w9WgXcQ?start=30&end=60").unwrap();
+        assert_eq!(parsed.start_time, Some(30.0));
+        assert_eq!(parsed.end_time, Some(60.0));
+
+        let parsed = parse_url("https://www.twitch.tv/videos/1234567890?t=1h2m3s").unwrap();
+        assert_eq!(parsed.platform, Platform::Twitch);
+        assert_eq!(parsed.start_time, Some(3723.0));
+
+        // Ignored: no time, zero, garbage, similar names, end before start,
+        // and platforms without timestamps in links
+        let none = [
+            "https://youtu.be/dQw4w9WgXcQ",
+            "https://youtu.be/dQw4w9WgXcQ?t=0",
+            "https://youtu.be/dQw4w9WgXcQ?t=abc",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=x&st=90",
+            "https://x.com/user/status/1234567890?t=90",
+        ];
+        for url in none {
+            assert_eq!(parse_url(url).unwrap().start_time, None, "{}", url);
+        }
+        let parsed =
+            parse_url("https://www.youtube.com/embed/dQw4w9WgXcQ?start=60&end=30").unwrap();
+        assert_eq!(parsed.end_time, None);
+    }
+
+    #[test]
+    fn test_embedded_range() {
+        assert_eq!(
+            embedded_range(Some(30.0), Some(60.0), 100.0),
+            (Some(30.0), Some(60.0))
+        );
+        assert_eq!(embedded_range(Some(120.0), None, 100.0), (None, None));
+        assert_eq!(
+            embedded_range(Some(30.0), Some(150.0), 100.0),
+            (Some(30.0), None)
+        );
+        assert_eq!(embedded_range(Some(30.0), None, 0.0), (Some(30.0), None));
+    }
+
+    #[test]
+    fn test_parse_progress_line() {
+        let progress =
+            parse_progress_line("[download]  42.5% of 10.00MiB at  1.00MiB/s ETA 00:05").unwrap();
+        assert_eq!(progress.percent, 42.5);
+        assert_eq!(progress.eta.as_deref(), Some("00:05"));
+
+        let progress =
+            parse_progress_line("[#2089b0 1.2MiB/10MiB(12%) CN:16 DL:3.4MiB ETA:2s]").unwrap();
+        assert_eq!(
+            progress,
+            ParsedProgress {
+                percent: 12.0,
+                speed: Some("3.4MiB/s".to_string()),
+                eta: Some("2s".to_string()),
+            }
+        );
+
+        // Readout before aria2c knows the speed
+        let progress = parse_progress_line("[#2089b0 0B/0B(0%) CN:1]").unwrap();
+        assert_eq!(progress.percent, 0.0);
+        assert_eq!(progress.speed, None);
+
+        assert!(parse_progress_line("[info] Downloading 1 format(s): 137+140").is_none());
+        assert!(parse_progress_line("FILE: /tmp/video.mp4").is_none());
+    }
+
+    #[test]
+    fn test_downloader_args() {
+        assert!(downloader_args(&Settings::default(), None).is_empty());
+
+        let settings = Settings {
+            concurrent_fragments: 4,
+            use_aria2c: true,
+            ..Settings::default()
+        };
+        let args = downloader_args(&settings, Some("/opt/bin/aria2c"));
+        assert_eq!(
+            &args[..4],
+            [
+                "--concurrent-fragments",
+                "4",
+                "--downloader",
+                "/opt/bin/aria2c"
+            ]
+        );
+        assert_eq!(args[4], "--downloader-args");
+        assert!(args[5].starts_with("aria2c:"));
+    }
+
+    #[test]
+    fn test_ffmpeg_location_args() {
+        let mut settings = Settings::default();
+        settings.binary_paths.ffmpeg = Some("/opt/ffmpeg/bin/ffmpeg".to_string());
+        assert_eq!(
+            ffmpeg_location_args(&settings),
+            ["--ffmpeg-location", "/opt/ffmpeg/bin/ffmpeg"]
+        );
+    }
+
+    #[test]
+    fn test_js_runtime_arg() {
+        assert_eq!(
+            js_runtime_arg(
+                deps::JsRuntime::Deno,
+                "/home/user/.local/share/DLCut/bin/deno"
+            ),
+            [
+                "--js-runtimes",
+                "deno:/home/user/.local/share/DLCut/bin/deno"
+            ]
+        );
+        assert_eq!(
+            js_runtime_arg(deps::JsRuntime::QuickJs, "C:\\tools\\qjs.exe")[1],
+            "quickjs:C:\\tools\\qjs.exe"
+        );
     }
 
     #[test]
@@ -696,4 +1111,47 @@ mod tests {
         assert_eq!(extract_height("480p"), 480);
         assert_eq!(extract_height("unknown"), 0);
     }
+
+    #[test]
+    fn test_resolve_output_path_in_subfolder() {
+        let base = test_support::temp_dir("ytdlp-resolve");
+        let dir = base.join("Music").join("100% Artist");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Audio extraction renamed the file, and a same-named file elsewhere
+        // must not be picked up
+        std::fs::write(dir.join("Track.opus"), b"audio").unwrap();
+        std::fs::write(base.join("Track.webm"), b"other").unwrap();
+        let expected = dir.join("Track.mp3").to_string_lossy().to_string();
+        assert_eq!(
+            resolve_output_path(&expected),
+            dir.join("Track.opus").to_string_lossy()
+        );
+
+        // Nothing to find: the expected path is kept
+        let missing = dir.join("Missing.mp4").to_string_lossy().to_string();
+        assert_eq!(resolve_output_path(&missing), missing);
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_source_has_audio() {
+        let formats = |json: &str| serde_json::from_str::<Vec<RawFormat>>(json).unwrap();
+
+        // Video and audio served separately, as YouTube and Reddit do
+        let separate = formats(
+            r#"[{"format_id": "1", "ext": "mp4", "vcodec": "avc1", "acodec": "none"},
+                {"format_id": "2", "ext": "m4a", "vcodec": "none", "acodec": "mp4a"}]"#,
+        );
+        assert_eq!(source_has_audio(&separate), Some(true));
+
+        let silent =
+            formats(r#"[{"format_id": "1", "ext": "mp4", "vcodec": "avc1", "acodec": "none"}]"#);
+        assert_eq!(source_has_audio(&silent), Some(false));
+
+        let unknown = formats(r#"[{"format_id": "1", "ext": "mp4"}]"#);
+        assert_eq!(source_has_audio(&unknown), None);
+        assert_eq!(source_has_audio(&[]), None);
+    }
 }
@@ -113,6 +113,7 @@ interface VideoQuality {
   height: number;
   label: string;
   filesize_approx: string | null;
+  filesize: number | null;
 }
 
 interface AudioQuality {
@@ -123,7 +124,7 @@ interface AudioQuality {
 
 type DownloadMode = "video_with_audio" | "audio_only";
 
-type Platform = "youtube" | "tiktok" | "instagram" | "twitter" | "reddit" | "soundcloud";
+type Platform = "youtube" | "tiktok" | "instagram" | "twitter" | "reddit" | "soundcloud" | "twitch";
 
 interface VideoInfo {
   id: string;
@@ -132,10 +133,14 @@ interface VideoInfo {
   duration_string: string;
   thumbnail: string | null;
   uploader: string | null;
+  upload_date: string | null;
   formats: VideoFormat[];
   video_qualities: VideoQuality[];
   audio_qualities: AudioQuality[];
   platform: Platform;
+  start_time: number | null;
+  end_time: number | null;
+  has_audio: boolean | null;
 }
 
 interface ProgressUpdate {
@@ -146,12 +151,64 @@ interface ProgressUpdate {
   eta: string | null;
 }
 
+interface VerificationReport {
+  verified: boolean;
+  duration: number | null;
+  expected_duration: number | null;
+  video_codec: string | null;
+  audio_codec: string | null;
+  warnings: string[];
+}
+
+interface VerifiedOutput {
+  path: string;
+  verification: VerificationReport;
+}
+
+interface MediaStream {
+  index: number;
+  kind: "video" | "audio" | "subtitle" | "data" | "attachment" | "unknown";
+  codec: string | null;
+  profile: string | null;
+  language: string | null;
+  title: string | null;
+  attached_pic: boolean;
+  width: number | null;
+  height: number | null;
+  fps: number | null;
+  hdr: string | null;
+  rotation: number;
+  channels: number | null;
+  channel_layout: string | null;
+  sample_rate: number | null;
+}
+
+interface MediaInfo {
+  container: string;
+  container_long_name: string | null;
+  duration: number | null;
+  bit_rate: number | null;
+  size: number | null;
+  streams: MediaStream[];
+  chapters: { start: number; end: number; title: string | null }[];
+  tags: Record<string, string>;
+}
+
 interface DepsStatus {
   ytdlp_installed: boolean;
   ffmpeg_installed: boolean;
   ready: boolean;
 }
 
+interface DeepLinkRequest {
+  url: string;
+  platform: Platform;
+  mode: DownloadMode | null;
+  quality: string | null;
+  start_time: number | null;
+  end_time: number | null;
+}
+
 interface SetupProgress {
   message: string;
   progress: number;
@@ -175,6 +232,7 @@ const modeVideoBtn = document.getElementById("mode-video") as HTMLButtonElement;
 const modeAudioBtn = document.getElementById("mode-audio") as HTMLButtonElement;
 const qualitySection = document.getElementById("quality-section") as HTMLElement;
 const qualitySelect = document.getElementById("quality-select") as HTMLSelectElement;
+const sizeLimitSelect = document.getElementById("size-limit-select") as HTMLSelectElement;
 const cutSection = document.getElementById("cut-section") as HTMLElement;
 const startTimeInput = document.getElementById("start-time") as HTMLInputElement;
 const endTimeInput = document.getElementById("end-time") as HTMLInputElement;
@@ -328,7 +386,7 @@ async function init() {
     updateProgress(event.payload);
   });
 
-  await listen<string>("download-complete", (event: { payload: string; }) => {
+  await listen<VerifiedOutput>("download-complete", (event: { payload: VerifiedOutput; }) => {
     handleDownloadComplete(event.payload);
   });
 
@@ -339,8 +397,46 @@ async function init() {
   // Initialize cut tab
   await initCutTab();
 
+  // dlcut:// links, both the one that launched the app and later ones
+  await listen("deep-link", () => {
+    takePendingDeepLink();
+  });
+
+  await listen<string>("deep-link-error", (event: { payload: string }) => {
+    switchTab("download");
+    showError(urlError, event.payload);
+  });
+
   // Focus URL input on load
   urlInput.focus();
+
+  await takePendingDeepLink();
+}
+
+// Pre-fill the download form from a dlcut:// link (validated by the backend)
+async function takePendingDeepLink() {
+  const link = await invoke<DeepLinkRequest | null>("take_pending_deep_link");
+  if (!link || isDownloading) return;
+
+  switchTab("download");
+  if (fetchTimeout) clearTimeout(fetchTimeout);
+  urlInput.value = link.url;
+  await fetchVideoInfo(link.url);
+  if (!currentVideoInfo) return;
+
+  if (link.mode && !modeVideoBtn.disabled) {
+    handleModeChange(link.mode);
+  }
+
+  if (link.quality) {
+    const option = Array.from(qualitySelect.options).find((o) => o.value === link.quality);
+    if (option) {
+      qualitySelect.value = option.value;
+      handleQualityChange();
+    }
+  }
+
+  setSliderRange(link.start_time, link.end_time);
 }
 
 // URL input handler with debounce
@@ -386,13 +482,15 @@ function isSupportedUrl(url: string): boolean {
     url.includes("youtube.com/shorts/") ||
     url.includes("youtube.com/embed/") ||
     url.includes("m.youtube.com/watch") ||
+    url.includes("youtube.com/clip/") ||
     url.includes("tiktok.com/") ||
     url.includes("instagram.com/") ||
     url.includes("twitter.com/") ||
     url.includes("x.com/") ||
     url.includes("reddit.com/") ||
     url.includes("v.redd.it/") ||
-    url.includes("soundcloud.com/")
+    url.includes("soundcloud.com/") ||
+    url.includes("twitch.tv/videos/")
   );
 }
 
@@ -404,6 +502,7 @@ function platformDisplayName(platform: Platform): string {
     twitter: "X / Twitter",
     reddit: "Reddit",
     soundcloud: "SoundCloud",
+    twitch: "Twitch",
   };
   return names[platform];
 }
@@ -461,8 +560,9 @@ function displayVideoInfo(info: VideoInfo) {
     modeVideoBtn.disabled = false;
   }
 
-  // Initialize slider with video duration
+  // Initialize slider with video duration, starting at any time in the link
   resetSlider();
+  setSliderRange(info.start_time, info.end_time);
 
   // Populate quality options based on current mode
   populateQualityOptions();
@@ -605,6 +705,25 @@ function formatTime(seconds: number): string {
   return `${mins}:${secs.toString().padStart(2, "0")}`;
 }
 
+// Select a range in seconds and open the cut options; ignored if out of bounds
+function setSliderRange(startTime: number | null, endTime: number | null) {
+  if (!currentVideoInfo || (startTime === null && endTime === null)) return;
+
+  const duration = currentVideoInfo.duration;
+  if (duration <= 0) return;
+
+  const start = Math.min(startTime ?? 0, duration);
+  const end = Math.min(endTime ?? duration, duration);
+  if (end <= start) return;
+
+  sliderStartPercent = (start / duration) * 100;
+  sliderEndPercent = (end / duration) * 100;
+  updateSliderUI();
+
+  const cutCollapsible = cutSection.querySelector("details");
+  if (cutCollapsible) cutCollapsible.open = true;
+}
+
 function resetSlider() {
   sliderStartPercent = 0;
   sliderEndPercent = 100;
@@ -653,20 +772,30 @@ async function handleDownload() {
   const ext = currentMode === "video_with_audio" ? "mp4" : "mp3";
   const fileTypeName = currentMode === "video_with_audio" ? "Video" : "Audio";
 
-  // Generate filename
-  const filename = await invoke<string>("generate_filename", {
-    title: currentVideoInfo.title,
-    formatExt: ext,
-  });
+  // Get cut times from slider (stored as seconds in hidden inputs)
+  const startTime = startTimeInput.value ? parseFloat(startTimeInput.value) : null;
+  const endTime = endTimeInput.value ? parseFloat(endTimeInput.value) : null;
 
-  // Get default download directory
-  const defaultDir = await invoke<string | null>("get_default_download_dir");
+  // Suggest a path from the output rules and filename template in the settings
+  let defaultPath: string;
+  try {
+    defaultPath = await invoke<string>("suggest_output_path", {
+      info: currentVideoInfo,
+      mode: currentMode,
+      quality,
+      startTime,
+      endTime,
+    });
+  } catch (error) {
+    showStatus(`${error}`, "error");
+    return;
+  }
 
   // Open save dialog using Tauri's dialog plugin
   const { save } = await import("@tauri-apps/plugin-dialog");
 
   const outputPath = await save({
-    defaultPath: defaultDir ? `${defaultDir}/${filename}` : filename,
+    defaultPath,
     filters: [
       { name: fileTypeName, extensions: [ext] },
       { name: "All Files", extensions: ["*"] },
@@ -679,10 +808,6 @@ async function handleDownload() {
     return;
   }
 
-  // Get cut times from slider (stored as seconds in hidden inputs)
-  const startTime = startTimeInput.value ? parseFloat(startTimeInput.value) : null;
-  const endTime = endTimeInput.value ? parseFloat(endTimeInput.value) : null;
-
   // Start download
   isDownloading = true;
   hide(downloadSection);
@@ -698,6 +823,9 @@ async function handleDownload() {
         output_path: outputPath,
         start_time: startTime,
         end_time: endTime,
+        // The save dialog already asked before replacing a file
+        if_exists: "overwrite",
+        target_size: sizeLimit(sizeLimitSelect),
       },
     });
   } catch (error) {
@@ -705,6 +833,11 @@ async function handleDownload() {
   }
 }
 
+// Size limit in bytes picked in a select, or null for none
+function sizeLimit(select: HTMLSelectElement): number | null {
+  return select.value ? Number(select.value) : null;
+}
+
 // Update progress display
 function updateProgress(progress: ProgressUpdate) {
   progressMessage.textContent = progress.message;
@@ -725,14 +858,20 @@ function updateProgress(progress: ProgressUpdate) {
 }
 
 // Handle download completion
-function handleDownloadComplete(path: string) {
+function handleDownloadComplete(output: VerifiedOutput) {
   isDownloading = false;
-  lastDownloadedPath = path;
+  lastDownloadedPath = output.path;
   hide(progressSection);
   show(downloadSection);
   // Unhide button before showing status so resize captures full height
   openFolderBtn.classList.remove("hidden");
-  showStatus(`Downloaded successfully to:\n${path}`, "success");
+  showStatus(`Downloaded successfully to:\n${output.path}${verificationNote(output.verification)}`, "success");
+}
+
+// Warnings from checking the finished file, appended to the status message
+function verificationNote(report: VerificationReport): string {
+  if (report.warnings.length === 0) return "";
+  return `\n\nWarning:\n${report.warnings.join("\n")}`;
 }
 
 // Open folder containing the downloaded file
@@ -846,6 +985,8 @@ const cutPlayIcon = document.getElementById("cut-play-icon") as unknown as SVGEl
 const cutPauseIcon = document.getElementById("cut-pause-icon") as unknown as SVGElement;
 const cutCurrentTime = document.getElementById("cut-current-time") as HTMLSpanElement;
 const cutTotalTime = document.getElementById("cut-total-time") as HTMLSpanElement;
+const cutMediaInfo = document.getElementById("cut-media-info") as HTMLDivElement;
+const cutStreamList = document.getElementById("cut-stream-list") as HTMLDivElement;
 const cutTimeline = document.getElementById("cut-timeline") as HTMLElement;
 const cutTimelineSelection = document.getElementById("cut-timeline-selection") as HTMLElement;
 const cutTimelineDimLeft = document.getElementById("cut-timeline-dim-left") as HTMLElement;
@@ -859,6 +1000,12 @@ const cutGotoStart = document.getElementById("cut-goto-start") as HTMLButtonElem
 const cutGotoEnd = document.getElementById("cut-goto-end") as HTMLButtonElement;
 const cutActionSection = document.getElementById("cut-action-section") as HTMLElement;
 const cutBtn = document.getElementById("cut-btn") as HTMLButtonElement;
+const cutQualitySelect = document.getElementById("cut-quality-select") as HTMLSelectElement;
+const cutSizeLimitSelect = document.getElementById("cut-size-limit-select") as HTMLSelectElement;
+const cutReframeSelect = document.getElementById("cut-reframe-select") as HTMLSelectElement;
+const cutFillSelect = document.getElementById("cut-fill-select") as HTMLSelectElement;
+const cutReframePreviewBtn = document.getElementById("cut-reframe-preview-btn") as HTMLButtonElement;
+const cutReframePreview = document.getElementById("cut-reframe-preview") as HTMLImageElement;
 const cutProgressSection = document.getElementById("cut-progress-section") as HTMLElement;
 const cutProgressMessage = document.getElementById("cut-progress-message") as HTMLSpanElement;
 const cutProgressPercent = document.getElementById("cut-progress-percent") as HTMLSpanElement;
@@ -870,6 +1017,7 @@ const cutOpenFolderBtn = document.getElementById("cut-open-folder-btn") as HTMLB
 
 // Cut state
 let cutFilePath: string | null = null;
+let cutStreams: MediaStream[] = [];
 let cutVideoDuration = 0;
 let cutSliderStartPercent = 0;
 let cutSliderEndPercent = 100;
@@ -932,6 +1080,22 @@ async function loadCutFile(filePath: string) {
   cutSkeletonSection.classList.remove("hidden");
   resizeWindowToContent();
 
+  // Stream details load alongside the preview; they are only informational
+  cutMediaInfo.classList.add("hidden");
+  cutStreams = [];
+  renderStreamList();
+  cutReframePreview.classList.add("hidden");
+  invoke<MediaInfo>("probe_media", { path: filePath })
+    .then((media) => {
+      if (cutFilePath !== filePath) return;
+      cutMediaInfo.textContent = describeMedia(media);
+      cutMediaInfo.classList.remove("hidden");
+      cutStreams = media.streams;
+      renderStreamList();
+      resizeWindowToContent();
+    })
+    .catch(() => {});
+
   // Revoke previous blob URL if any
   if (cutBlobUrl) {
     URL.revokeObjectURL(cutBlobUrl);
@@ -946,6 +1110,113 @@ async function loadCutFile(filePath: string) {
   cutVideo.load();
 }
 
+// One-line summary of the opened file, e.g. "h264 1920×1080 29.97 fps · aac stereo"
+function describeMedia(media: MediaInfo): string {
+  const parts: string[] = [];
+
+  const video = media.streams.find((s) => s.kind === "video" && !s.attached_pic);
+  if (video) {
+    const details = [video.codec ?? "video"];
+    if (video.width && video.height) details.push(`${video.width}×${video.height}`);
+    if (video.fps) details.push(`${Math.round(video.fps * 100) / 100} fps`);
+    if (video.hdr) details.push(video.hdr);
+    if (video.rotation) details.push(`rotated ${video.rotation}°`);
+    parts.push(details.join(" "));
+  }
+
+  const audio = media.streams.filter((s) => s.kind === "audio");
+  if (audio.length > 0) {
+    const first = audio[0];
+    const details = [first.codec ?? "audio"];
+    if (first.channel_layout) details.push(first.channel_layout);
+    if (audio.length > 1) details.push(`+${audio.length - 1} more`);
+    parts.push(details.join(" "));
+  }
+
+  const subtitles = media.streams.filter((s) => s.kind === "subtitle").length;
+  if (subtitles > 0) parts.push(`${subtitles} subtitle${subtitles > 1 ? "s" : ""}`);
+  if (media.chapters.length > 0) {
+    parts.push(`${media.chapters.length} chapter${media.chapters.length > 1 ? "s" : ""}`);
+  }
+
+  return parts.join(" · ");
+}
+
+// Checklist of the file's streams, shown when there is more to choose from
+// than one video and one audio track
+function renderStreamList() {
+  cutStreamList.replaceChildren();
+  const choices = cutStreams.filter((s) => s.kind !== "data" && s.kind !== "unknown");
+  cutStreamList.classList.toggle("hidden", choices.length <= 2);
+
+  for (const stream of choices) {
+    const checkbox = document.createElement("input");
+    checkbox.type = "checkbox";
+    checkbox.checked = true;
+    checkbox.value = `${stream.index}`;
+
+    const label = document.createElement("label");
+    label.append(checkbox, describeStream(stream));
+    cutStreamList.append(label);
+  }
+}
+
+// e.g. "#2 audio · ac3 5.1 · eng · Commentary"
+function describeStream(stream: MediaStream): string {
+  const kind = stream.attached_pic ? "cover art" : stream.kind;
+  const details = [stream.codec ?? "unknown"];
+  if (stream.channel_layout) details.push(stream.channel_layout);
+  if (stream.width && stream.height) details.push(`${stream.width}×${stream.height}`);
+
+  const parts = [`#${stream.index} ${kind}`, details.join(" ")];
+  if (stream.language) parts.push(stream.language);
+  if (stream.title) parts.push(stream.title);
+  return parts.join(" · ");
+}
+
+// Indices of the checked streams, or null to keep everything
+function selectedStreams(): number[] | null {
+  const boxes = Array.from(cutStreamList.querySelectorAll<HTMLInputElement>("input"));
+  if (boxes.every((box) => box.checked)) return null;
+  return boxes.filter((box) => box.checked).map((box) => Number(box.value));
+}
+
+// Aspect ratio and fill chosen for the cut, or null to keep the frame
+function selectedReframe(): { aspect: string; fill: string } | null {
+  if (!cutReframeSelect.value) return null;
+  return { aspect: cutReframeSelect.value, fill: cutFillSelect.value };
+}
+
+function handleReframeChange() {
+  const reframing = selectedReframe() !== null;
+  cutFillSelect.classList.toggle("hidden", !reframing);
+  cutReframePreviewBtn.classList.toggle("hidden", !reframing);
+  cutReframePreview.classList.add("hidden");
+  resizeWindowToContent();
+}
+
+// Render the frame under the playhead as the cut would be reframed
+async function handleReframePreview() {
+  const reframe = selectedReframe();
+  if (!reframe || !cutFilePath) return;
+
+  cutReframePreviewBtn.disabled = true;
+  try {
+    const path = await invoke<string>("preview_reframe", {
+      inputPath: cutFilePath,
+      time: cutVideo.currentTime,
+      reframe,
+    });
+    cutReframePreview.src = convertFileSrc(path);
+    cutReframePreview.classList.remove("hidden");
+    cutReframePreview.onload = () => resizeWindowToContent();
+  } catch (error) {
+    showCutStatus(`${error}`, "error");
+  } finally {
+    cutReframePreviewBtn.disabled = false;
+  }
+}
+
 // Video loaded - show preview and controls
 function handleCutVideoLoaded() {
   cutVideoDuration = cutVideo.duration;
@@ -1171,6 +1442,12 @@ async function handleCut() {
       outputPath,
       startTime,
       endTime,
+      options: {
+        streams: selectedStreams(),
+        quality: { preset: cutQualitySelect.value },
+        target_size: sizeLimit(cutSizeLimitSelect),
+        reframe: selectedReframe(),
+      },
     });
   } catch (error) {
     handleCutError(`${error}`);
@@ -1183,12 +1460,12 @@ function handleCutProgress(progress: ProgressUpdate) {
   cutProgressFill.style.width = `${progress.percent}%`;
 }
 
-function handleCutComplete(path: string) {
+function handleCutComplete(output: VerifiedOutput) {
   isCutting = false;
-  lastCutPath = path;
+  lastCutPath = output.path;
   cutProgressSection.classList.add("hidden");
   cutActionSection.classList.remove("hidden");
-  showCutStatus(`Cut saved to:\n${path}`, "success");
+  showCutStatus(`Cut saved to:\n${output.path}${verificationNote(output.verification)}`, "success");
   cutOpenFolderBtn.classList.remove("hidden");
   resizeWindowToContent();
 }
@@ -1316,6 +1593,11 @@ async function initCutTab() {
     updatePlayheadPosition(cutSliderEndPercent);
   });
 
+  // Reframing
+  cutReframeSelect.addEventListener("change", handleReframeChange);
+  cutFillSelect.addEventListener("change", () => cutReframePreview.classList.add("hidden"));
+  cutReframePreviewBtn.addEventListener("click", handleReframePreview);
+
   // Cut button
   cutBtn.addEventListener("click", handleCut);
 
@@ -1327,7 +1609,7 @@ async function initCutTab() {
     handleCutProgress(event.payload);
   });
 
-  await listen<string>("cut-complete", (event: { payload: string }) => {
+  await listen<VerifiedOutput>("cut-complete", (event: { payload: VerifiedOutput }) => {
     handleCutComplete(event.payload);
   });
 